@@ -10,12 +10,14 @@ use std::{
     fs::File,
     io::Error as IoError,
     net::{IpAddr, SocketAddr},
+    ops::RangeInclusive,
     path::PathBuf,
     str::FromStr,
     sync::Arc,
     time::Duration,
 };
 use thiserror::Error;
+use tuic_quinn::PaddingPolicy;
 use uuid::Uuid;
 
 const HELP_MSG: &str = r#"
@@ -110,6 +112,32 @@ pub struct Relay {
         deserialize_with = "deserialize_duration"
     )]
     pub gc_lifetime: Duration,
+
+    #[serde(default)]
+    pub padding: Padding,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Padding {
+    #[serde(deserialize_with = "deserialize_padding", default)]
+    pub authenticate: Option<RangeInclusive<u16>>,
+
+    #[serde(deserialize_with = "deserialize_padding", default)]
+    pub heartbeat: Option<RangeInclusive<u16>>,
+
+    #[serde(deserialize_with = "deserialize_padding", default)]
+    pub connect: Option<RangeInclusive<u16>>,
+}
+
+impl From<Padding> for PaddingPolicy {
+    fn from(padding: Padding) -> Self {
+        Self {
+            handshake: padding.authenticate,
+            heartbeat: padding.heartbeat,
+            connect: padding.connect,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -136,12 +164,8 @@ impl Config {
 
         while let Some(arg) = parser.next()? {
             match arg {
-                Arg::Short('c') | Arg::Long("config") => {
-                    if path.is_none() {
-                        path = Some(parser.value()?);
-                    } else {
-                        return Err(ConfigError::Argument(arg.unexpected()));
-                    }
+                Arg::Short('c') | Arg::Long("config") if path.is_none() => {
+                    path = Some(parser.value()?);
                 }
                 Arg::Short('v') | Arg::Long("version") => {
                     return Err(ConfigError::Version(env!("CARGO_PKG_VERSION")))
@@ -292,6 +316,19 @@ where
         .map_err(DeError::custom)
 }
 
+pub fn deserialize_padding<'de, D>(deserializer: D) -> Result<Option<RangeInclusive<u16>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let [min, max] = <[u16; 2]>::deserialize(deserializer)?;
+
+    if min > max {
+        return Err(DeError::custom("invalid padding range"));
+    }
+
+    Ok(Some(min..=max))
+}
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error(transparent)]
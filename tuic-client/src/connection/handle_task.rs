@@ -1,12 +1,17 @@
 use super::Connection;
 use crate::{error::Error, socks5::UDP_SESSIONS as SOCKS5_UDP_SESSIONS, utils::UdpRelayMode};
 use bytes::Bytes;
-use quinn::ZeroRttAccepted;
+use quinn::{SendDatagramError, ZeroRttAccepted};
 use socks5_proto::Address as Socks5Address;
-use std::time::Duration;
-use tokio::time;
-use tuic::Address;
-use tuic_quinn::{Connect, Packet};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::{sync::Mutex as AsyncMutex, time};
+use tuic::{Address, AddressError, Capabilities, Header, Packet as PacketHeader};
+use tuic_quinn::{
+    Connect, Error as ModelError, Extension, Packet, PacketReceiver, PacketSender, Task,
+};
 
 impl Connection {
     pub async fn authenticate(self, zero_rtt_accepted: Option<ZeroRttAccepted>) {
@@ -43,7 +48,7 @@ impl Connection {
     pub async fn packet(&self, pkt: Bytes, addr: Address, assoc_id: u16) -> Result<(), Error> {
         let addr_display = addr.to_string();
 
-        match self.udp_relay_mode {
+        match self.udp_relay_mode() {
             UdpRelayMode::Native => {
                 log::info!("[relay] [packet] [{assoc_id:#06x}] [to-native] to {addr_display}");
                 match self.model.packet_native(pkt, addr, assoc_id) {
@@ -66,11 +71,98 @@ impl Connection {
                     }
                 }
             }
+            UdpRelayMode::Stream => {
+                log::info!("[relay] [packet] [{assoc_id:#06x}] [to-stream] to {addr_display}");
+                let res = match self.packet_stream(assoc_id).await {
+                    Ok(send) => send.lock().await.send(pkt, addr).await,
+                    Err(err) => Err(err),
+                };
+
+                match res {
+                    Ok(()) => Ok(()),
+                    Err(err) => {
+                        // the next packet opens a new stream
+                        self.packet_streams.lock().await.remove(&assoc_id);
+                        log::warn!(
+                            "[relay] [packet] [{assoc_id:#06x}] [to-stream] to {addr_display}: {err}"
+                        );
+                        Err(Error::Model(err))
+                    }
+                }
+            }
+            UdpRelayMode::Auto => {
+                if self.fits_in_datagram(&pkt, &addr) {
+                    log::info!("[relay] [packet] [{assoc_id:#06x}] [to-native] to {addr_display}");
+                    match self
+                        .model
+                        .packet_native(pkt.clone(), addr.clone(), assoc_id)
+                    {
+                        Ok(()) => return Ok(()),
+                        // the path MTU shrank since checking
+                        Err(ModelError::SendDatagram(SendDatagramError::TooLarge)) => {}
+                        Err(ModelError::SendDatagram(err)) => {
+                            log::warn!("[relay] [packet] [{assoc_id:#06x}] [to-native] datagrams unavailable, switching to quic: {err}");
+                            self.datagram_unavailable.store(true, Ordering::Relaxed);
+                        }
+                        Err(err) => {
+                            log::warn!("[relay] [packet] [{assoc_id:#06x}] [to-native] to {addr_display}: {err}");
+                            return Err(Error::Model(err));
+                        }
+                    }
+                }
+
+                log::info!("[relay] [packet] [{assoc_id:#06x}] [to-quic] to {addr_display}");
+                match self.model.packet_quic(pkt, addr, assoc_id).await {
+                    Ok(()) => Ok(()),
+                    Err(err) => {
+                        log::warn!(
+                            "[relay] [packet] [{assoc_id:#06x}] [to-quic] to {addr_display}: {err}"
+                        );
+                        Err(Error::Model(err))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the packet can be sent in a single QUIC datagram, so it is neither fragmented nor rejected as too large
+    fn fits_in_datagram(&self, pkt: &[u8], addr: &Address) -> bool {
+        if self.datagram_unavailable.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let header_len = Header::Packet(PacketHeader::new(0, 0, 1, 0, 0, addr.clone())).len();
+        self.conn
+            .max_datagram_size()
+            .is_some_and(|max| header_len + pkt.len() <= max)
+    }
+
+    /// Returns the sending half of the stream of the UDP session, opening the stream if there is none yet
+    async fn packet_stream(
+        &self,
+        assoc_id: u16,
+    ) -> Result<Arc<AsyncMutex<PacketSender>>, ModelError> {
+        let mut streams = self.packet_streams.lock().await;
+
+        if let Some(send) = streams.get(&assoc_id) {
+            return Ok(send.clone());
         }
+
+        let (send, recv) = self.model.packet_stream(assoc_id).await?.into_split();
+        let send = Arc::new(AsyncMutex::new(send));
+        streams.insert(assoc_id, send.clone());
+        tokio::spawn(Self::handle_packet_stream(recv));
+
+        Ok(send)
     }
 
     pub async fn dissociate(&self, assoc_id: u16) -> Result<(), Error> {
         log::info!("[relay] [dissociate] [{assoc_id:#06x}]");
+
+        if let Some(send) = self.packet_streams.lock().await.remove(&assoc_id) {
+            let _ = send.lock().await.close().await;
+        }
+
         match self.model.dissociate(assoc_id).await {
             Ok(()) => Ok(()),
             Err(err) => {
@@ -99,6 +191,75 @@ impl Connection {
         }
     }
 
+    pub async fn handle_task(self, task: Task) {
+        let res = match task {
+            Task::Packet(pkt) => match self.udp_relay_mode() {
+                UdpRelayMode::Native if pkt.is_from_native() => {
+                    Self::handle_packet(pkt).await;
+                    Ok(())
+                }
+                UdpRelayMode::Quic if pkt.is_from_quic() => {
+                    Self::handle_packet(pkt).await;
+                    Ok(())
+                }
+                UdpRelayMode::Auto => {
+                    Self::handle_packet(pkt).await;
+                    Ok(())
+                }
+                _ => Err(Error::WrongPacketSource),
+            },
+            Task::Capabilities(caps) => {
+                self.handle_capabilities(caps);
+                Ok(())
+            }
+            Task::Extension(ext) => {
+                Self::handle_extension(ext);
+                Ok(())
+            }
+            Task::Padding => Ok(()),
+            _ => unreachable!(), // already filtered in `tuic_quinn`
+        };
+
+        if let Err(err) = res {
+            log::warn!("[relay] incoming task error: {err}");
+        }
+    }
+
+    pub fn handle_capabilities(&self, caps: Capabilities) {
+        log::info!("[relay] [capabilities] {bits:#018x}", bits = caps.bits());
+
+        let mode = self.udp_relay_mode();
+        if mode != self.udp_relay_mode {
+            log::warn!(
+                "[relay] [capabilities] UDP relay mode {configured} not supported by the server, falling back to {mode}",
+                configured = self.udp_relay_mode,
+            );
+        }
+    }
+
+    pub fn handle_extension(ext: Extension) {
+        // no extension is supported yet, dropping the streams it came with
+        log::debug!(
+            "[relay] [extension] [{type_code:#04x}] ignoring unknown extension",
+            type_code = ext.type_code(),
+        );
+    }
+
+    async fn handle_packet_stream(mut recv: PacketReceiver) {
+        let assoc_id = recv.assoc_id();
+
+        loop {
+            match recv.recv().await {
+                Ok(Some(pkt)) => Self::handle_packet(pkt).await,
+                Ok(None) => break,
+                Err(err) => {
+                    log::warn!("[relay] [packet] [{assoc_id:#06x}] [from-stream] packet receiving error: {err}");
+                    break;
+                }
+            }
+        }
+    }
+
     pub async fn handle_packet(pkt: Packet) {
         let assoc_id = pkt.assoc_id();
         let pkt_id = pkt.pkt_id();
@@ -107,6 +268,8 @@ impl Connection {
             "native"
         } else if pkt.is_from_quic() {
             "quic"
+        } else if pkt.is_from_stream() {
+            "stream"
         } else {
             unreachable!()
         };
@@ -121,12 +284,12 @@ impl Connection {
             Ok(Some((pkt, addr, _))) => {
                 log::info!("[relay] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] from {addr}");
 
-                let addr = match addr {
-                    Address::None => unreachable!(),
-                    Address::DomainAddress(domain, port) => {
-                        Socks5Address::DomainAddress(domain.as_bytes().to_vec(), port)
+                let addr = match to_socks5_addr(addr) {
+                    Ok(addr) => addr,
+                    Err(err) => {
+                        log::warn!("[relay] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] invalid source address: {err}");
+                        return;
                     }
-                    Address::SocketAddress(addr) => Socks5Address::SocketAddress(addr),
                 };
 
                 let session = SOCKS5_UDP_SESSIONS
@@ -152,3 +315,11 @@ impl Connection {
         }
     }
 }
+
+fn to_socks5_addr(addr: Address) -> Result<Socks5Address, AddressError> {
+    match addr {
+        Address::SocketAddress(addr) => Ok(Socks5Address::SocketAddress(addr)),
+        addr => <(Vec<u8>, u16)>::try_from(addr)
+            .map(|(domain, port)| Socks5Address::DomainAddress(domain, port)),
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     utils::{self, CongestionControl, ServerAddr, UdpRelayMode},
 };
 use crossbeam_utils::atomic::AtomicCell;
+use futures_util::StreamExt;
 use once_cell::sync::OnceCell;
 use quinn::{
     congestion::{BbrConfig, CubicConfig, NewRenoConfig},
@@ -12,28 +13,27 @@ use quinn::{
 };
 use quinn::crypto::rustls::QuicClientConfig;
 
-use register_count::Counter;
 use rustls::{ClientConfig as RustlsClientConfig};
 
 use std::{
+    collections::HashMap,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
-    sync::{atomic::AtomicU32, Arc},
+    sync::{atomic::AtomicBool, Arc},
     time::Duration,
 };
-use tokio::sync::RwLock as AsyncRwLock;
+use tokio::sync::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
 use tokio::{sync::OnceCell as AsyncOnceCell, time};
 
-use tuic_quinn::{side, Connection as Model};
+use tuic::Capabilities;
+use tuic_quinn::{side, Connection as Model, Error as ModelError, PacketSender, PaddingPolicy};
 use uuid::Uuid;
 
-mod handle_stream;
 mod handle_task;
 
 static ENDPOINT: OnceCell<AsyncRwLock<Endpoint>> = OnceCell::new();
 static CONNECTION: AsyncOnceCell<AsyncRwLock<Connection>> = AsyncOnceCell::const_new();
 static TIMEOUT: AtomicCell<Duration> = AtomicCell::new(Duration::from_secs(0));
 
-pub const ERROR_CODE: VarInt = VarInt::from_u32(0);
 const DEFAULT_CONCURRENT_STREAMS: u32 = 32;
 
 #[derive(Clone)]
@@ -43,10 +43,8 @@ pub struct Connection {
     uuid: Uuid,
     password: Arc<[u8]>,
     udp_relay_mode: UdpRelayMode,
-    remote_uni_stream_cnt: Counter,
-    remote_bi_stream_cnt: Counter,
-    max_concurrent_uni_streams: Arc<AtomicU32>,
-    max_concurrent_bi_streams: Arc<AtomicU32>,
+    packet_streams: Arc<AsyncMutex<HashMap<u16, Arc<AsyncMutex<PacketSender>>>>>,
+    datagram_unavailable: Arc<AtomicBool>,
 }
 
 impl Connection {
@@ -118,6 +116,7 @@ impl Connection {
             heartbeat: cfg.heartbeat,
             gc_interval: cfg.gc_interval,
             gc_lifetime: cfg.gc_lifetime,
+            padding: PaddingPolicy::from(cfg.padding),
         };
 
         ENDPOINT
@@ -174,17 +173,18 @@ impl Connection {
         heartbeat: Duration,
         gc_interval: Duration,
         gc_lifetime: Duration,
+        padding: PaddingPolicy,
     ) -> Self {
         let conn = Self {
             conn: conn.clone(),
-            model: Model::<side::Client>::new(conn),
+            model: Model::<side::Client>::new(conn)
+                .with_padding(padding)
+                .with_max_concurrent_streams(DEFAULT_CONCURRENT_STREAMS),
             uuid,
             password,
             udp_relay_mode,
-            remote_uni_stream_cnt: Counter::new(),
-            remote_bi_stream_cnt: Counter::new(),
-            max_concurrent_uni_streams: Arc::new(AtomicU32::new(DEFAULT_CONCURRENT_STREAMS)),
-            max_concurrent_bi_streams: Arc::new(AtomicU32::new(DEFAULT_CONCURRENT_STREAMS)),
+            packet_streams: Arc::new(AsyncMutex::new(HashMap::new())),
+            datagram_unavailable: Arc::new(AtomicBool::new(false)),
         };
 
         tokio::spawn(
@@ -208,31 +208,46 @@ impl Connection {
         tokio::spawn(self.clone().heartbeat(heartbeat));
         tokio::spawn(self.clone().collect_garbage(gc_interval, gc_lifetime));
 
-        let err = loop {
-            tokio::select! {
-                res = self.accept_uni_stream() => match res {
-                    Ok((recv, reg)) => tokio::spawn(self.clone().handle_uni_stream(recv, reg)),
-                    Err(err) => break err,
-                },
-                res = self.accept_bi_stream() => match res {
-                    Ok((send, recv, reg)) => tokio::spawn(self.clone().handle_bi_stream(send, recv, reg)),
-                    Err(err) => break err,
-                },
-                res = self.accept_datagram() => match res {
-                    Ok(dg) => tokio::spawn(self.clone().handle_datagram(dg)),
-                    Err(err) => break err,
-                },
-            }
-            ;
-        };
+        let mut tasks = self.model.tasks();
 
-        log::warn!("[relay] connection error: {err}");
+        while let Some(res) = tasks.next().await {
+            match res {
+                Ok(task) => {
+                    tokio::spawn(self.clone().handle_task(task));
+                }
+                Err(ModelError::Connection(err)) => {
+                    log::warn!("[relay] connection error: {err}", err = Error::from(err));
+                }
+                Err(err) => log::warn!("[relay] incoming task error: {err}"),
+            }
+        }
     }
 
     fn is_closed(&self) -> bool {
         self.conn.close_reason().is_some()
     }
 
+    /// The configured UDP relay mode, unless the server advertised not supporting it. Mode `stream` falls back to the other lossless mode `quic` first
+    fn udp_relay_mode(&self) -> UdpRelayMode {
+        let Some(caps) = self.model.peer_capabilities() else {
+            return self.udp_relay_mode;
+        };
+
+        let native = caps.contains(Capabilities::UDP_RELAY_NATIVE);
+        let quic = caps.contains(Capabilities::UDP_RELAY_QUIC);
+        let stream = caps.contains(Capabilities::UDP_RELAY_STREAM);
+
+        match self.udp_relay_mode {
+            UdpRelayMode::Native if !native && quic => UdpRelayMode::Quic,
+            UdpRelayMode::Quic if !quic && native => UdpRelayMode::Native,
+            UdpRelayMode::Stream if !stream && quic => UdpRelayMode::Quic,
+            UdpRelayMode::Stream if !stream && native => UdpRelayMode::Native,
+            UdpRelayMode::Auto if !native && quic => UdpRelayMode::Quic,
+            UdpRelayMode::Auto if !quic && native => UdpRelayMode::Native,
+            mode => mode,
+        }
+    }
+
     async fn collect_garbage(self, gc_interval: Duration, gc_lifetime: Duration) {
         loop {
             time::sleep(gc_interval).await;
@@ -257,6 +272,7 @@ struct Endpoint {
     heartbeat: Duration,
     gc_interval: Duration,
     gc_lifetime: Duration,
+    padding: PaddingPolicy,
 }
 
 impl Endpoint {
@@ -266,9 +282,9 @@ impl Endpoint {
         for addr in self.server.resolve().await? {
             let connect_to = async {
                 let match_ipv4 =
-                    addr.is_ipv4() && self.ep.local_addr().map_or(false, |addr| addr.is_ipv4());
+                    addr.is_ipv4() && self.ep.local_addr().is_ok_and(|addr| addr.is_ipv4());
                 let match_ipv6 =
-                    addr.is_ipv6() && self.ep.local_addr().map_or(false, |addr| addr.is_ipv6());
+                    addr.is_ipv6() && self.ep.local_addr().is_ok_and(|addr| addr.is_ipv6());
 
                 if !match_ipv4 && !match_ipv6 {
                     let bind_addr = if addr.is_ipv4() {
@@ -310,6 +326,7 @@ impl Endpoint {
                         self.heartbeat,
                         self.gc_interval,
                         self.gc_lifetime,
+                        self.padding.clone(),
                     ));
                 }
                 Err(err) => last_err = Some(err),
@@ -1,14 +1,14 @@
-use quinn::{ConnectError, ConnectionError};
+use quinn::{ConnectError, ConnectionError, ReadError, WriteError};
 use rustls::Error as RustlsError;
 use std::io::Error as IoError;
 use thiserror::Error;
-use tuic_quinn::Error as ModelError;
+use tuic_quinn::{Error as ModelError, ErrorCode};
 
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error(transparent)]
-    Io(#[from] IoError),
+    Io(IoError),
     #[error(transparent)]
     Connect(#[from] ConnectError),
     #[error(transparent)]
@@ -27,12 +27,39 @@ pub enum Error {
     WrongPacketSource,
     #[error("invalid socks5 authentication")]
     InvalidSocks5Auth,
+    #[error("connection closed by the server: {0}")]
+    ClosedByServer(ErrorCode),
+    #[error("stream reset by the server: {0}")]
+    ResetByServer(ErrorCode),
     #[error("other error")]
     Other(String),
 }
 
 impl From<ConnectionError> for Error {
     fn from(err: ConnectionError) -> Self {
-        Self::Io(IoError::from(err))
+        match err {
+            ConnectionError::ApplicationClosed(close) => {
+                Self::ClosedByServer(ErrorCode::from(close.error_code))
+            }
+            err => Self::Io(IoError::from(err)),
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(err: IoError) -> Self {
+        let code = err.get_ref().and_then(|inner| {
+            match (inner.downcast_ref(), inner.downcast_ref()) {
+                (Some(ReadError::Reset(code)), _) | (_, Some(WriteError::Stopped(code))) => {
+                    Some(*code)
+                }
+                _ => None,
+            }
+        });
+
+        match code {
+            Some(code) => Self::ResetByServer(ErrorCode::from(code)),
+            None => Self::Io(err),
+        }
     }
 }
@@ -1,13 +1,13 @@
 use super::{udp_session::UdpSession, Server, UDP_SESSIONS};
-use crate::connection::{Connection as TuicConnection, ERROR_CODE};
+use crate::{connection::Connection as TuicConnection, error::Error};
 use socks5_proto::{Address, Reply};
 use socks5_server::{
     connection::{associate, bind, connect},
     Associate, Bind, Connect,
 };
 use tokio::io::{self, AsyncWriteExt};
-use tokio_util::compat::FuturesAsyncReadCompatExt;
-use tuic::Address as TuicAddress;
+use tuic::{Address as TuicAddress, AddressError, ConnectStatus};
+use tuic_quinn::ErrorCode;
 
 impl Server {
     pub async fn handle_associate(
@@ -55,16 +55,15 @@ impl Server {
                             }
                         };
 
-                        let forward = async move {
-                            let target_addr = match target_addr {
-                                Address::DomainAddress(domain, port) => {
-                                    let domain_string: String =
-                                        String::from_utf8_lossy(&domain).to_string();
-                                    TuicAddress::DomainAddress(domain_string, port)
-                                }
-                                Address::SocketAddress(addr) => TuicAddress::SocketAddress(addr),
-                            };
+                        let target_addr = match to_tuic_addr(target_addr) {
+                            Ok(addr) => addr,
+                            Err(err) => {
+                                log::warn!("[socks5] [{peer_addr}] [associate] [{assoc_id:#06x}] invalid target address: {err}");
+                                continue;
+                            }
+                        };
 
+                        let forward = async move {
                             match TuicConnection::get().await {
                                 Ok(conn) => conn.packet(pkt, target_addr, assoc_id).await,
                                 Err(err) => Err(err),
@@ -154,11 +153,25 @@ impl Server {
 
     pub async fn handle_connect(conn: Connect<connect::state::NeedReply>, addr: Address) {
         let peer_addr = conn.peer_addr().unwrap();
-        let target_addr = match addr {
-            Address::DomainAddress(domain, port) => {
-                TuicAddress::DomainAddress(String::from_utf8(domain).unwrap().to_string(), port)
+        let target_addr = match to_tuic_addr(addr) {
+            Ok(addr) => addr,
+            Err(err) => {
+                log::warn!("[socks5] [{peer_addr}] [connect] invalid target address: {err}");
+
+                match conn
+                    .reply(Reply::AddressTypeNotSupported, Address::unspecified())
+                    .await
+                {
+                    Ok(mut conn) => {
+                        let _ = conn.shutdown().await;
+                    }
+                    Err((err, _)) => {
+                        log::warn!("[socks5] [{peer_addr}] [connect] command reply error: {err}")
+                    }
+                }
+
+                return;
             }
-            Address::SocketAddress(addr) => TuicAddress::SocketAddress(addr),
         };
 
         let relay = match TuicConnection::get().await {
@@ -166,44 +179,79 @@ impl Server {
             Err(err) => Err(err),
         };
 
-        match relay {
-            Ok(relay) => {
-                let mut relay = relay.compat();
-
-                match conn.reply(Reply::Succeeded, Address::unspecified()).await {
-                    Ok(mut conn) => match io::copy_bidirectional(&mut conn, &mut relay).await {
-                        Ok(_) => {}
-                        Err(err) => {
-                            let _ = conn.shutdown().await;
-                            let _ = relay.get_mut().reset(ERROR_CODE);
-                            if err.kind() == io::ErrorKind::BrokenPipe {
-                                log::debug!("[socks5] [{peer_addr}] [connect] [{target_addr}] TCP stream broken pipe error: {err}");
-                                return;
-                            }
-                            log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] TCP stream relaying error: {err}");
-                        }
-                    },
-                    Err((err, _)) => {
-                        let _ = relay.shutdown().await;
-                        log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] command reply error: {err}");
-                    }
+        // wait for the outcome if the server reports it, so the SOCKS5 client sees failures instead of a reset
+        let relay = match relay {
+            Ok(mut relay) => match relay.result().await {
+                Ok(None | Some(ConnectStatus::Connected)) => Ok(relay.compat()),
+                Ok(Some(status)) => {
+                    log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] server failed connecting to target: {status}");
+                    Err(to_socks5_reply(status))
                 }
-            }
+                Err(err) => {
+                    log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] failed reading connect result: {err}");
+                    Err(Reply::GeneralFailure)
+                }
+            },
             Err(err) => {
                 log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] unable to relay TCP stream: {err}");
+                Err(Reply::GeneralFailure)
+            }
+        };
 
-                match conn
-                    .reply(Reply::GeneralFailure, Address::unspecified())
-                    .await
-                {
-                    Ok(mut conn) => {
+        match relay {
+            Ok(mut relay) => match conn.reply(Reply::Succeeded, Address::unspecified()).await {
+                Ok(mut conn) => match io::copy_bidirectional(&mut conn, &mut relay).await {
+                    Ok(_) => {}
+                    Err(err) => {
                         let _ = conn.shutdown().await;
+                        let _ = relay.get_mut().reset(ErrorCode::Unspecified);
+                        if err.kind() == io::ErrorKind::BrokenPipe {
+                            log::debug!("[socks5] [{peer_addr}] [connect] [{target_addr}] TCP stream broken pipe error: {err}");
+                            return;
+                        }
+                        log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] TCP stream relaying error: {err}", err = Error::from(err));
                     }
-                    Err((err, _)) => {
-                        log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] command reply error: {err}")
-                    }
+                },
+                Err((err, _)) => {
+                    let _ = relay.shutdown().await;
+                    log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] command reply error: {err}");
+                }
+            },
+            Err(reply) => match conn.reply(reply, Address::unspecified()).await {
+                Ok(mut conn) => {
+                    let _ = conn.shutdown().await;
+                }
+                Err((err, _)) => {
+                    log::warn!("[socks5] [{peer_addr}] [connect] [{target_addr}] command reply error: {err}")
                 }
+            },
+        }
+    }
+}
+
+fn to_socks5_reply(status: ConnectStatus) -> Reply {
+    match status {
+        ConnectStatus::Connected => Reply::Succeeded,
+        ConnectStatus::Refused => Reply::ConnectionRefused,
+        ConnectStatus::Unreachable | ConnectStatus::DnsFailure => Reply::HostUnreachable,
+        ConnectStatus::Denied => Reply::ConnectionNotAllowed,
+        ConnectStatus::Timeout => Reply::TtlExpired,
+        _ => Reply::GeneralFailure,
+    }
+}
+
+/// Domains are left for the server to resolve as they are, only their length is limited by the protocol
+fn to_tuic_addr(addr: Address) -> Result<TuicAddress, AddressError> {
+    match addr {
+        Address::DomainAddress(domain, port) => {
+            let domain = String::from_utf8_lossy(&domain).into_owned();
+
+            if domain.len() > TuicAddress::MAX_DOMAIN_LEN {
+                return Err(AddressError::DomainTooLong(domain.len()));
             }
+
+            Ok(TuicAddress::DomainAddress(domain, port))
         }
+        Address::SocketAddress(addr) => Ok(TuicAddress::from(addr)),
     }
 }
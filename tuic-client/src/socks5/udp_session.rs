@@ -6,7 +6,7 @@ use socks5_proto::{Address, UdpHeader};
 use socks5_server::AssociatedUdpSocket;
 use std::{
     collections::HashMap,
-    io::{Error as IoError, ErrorKind},
+    io::Error as IoError,
     net::{IpAddr, SocketAddr, UdpSocket as StdUdpSocket},
     sync::Arc,
 };
@@ -100,7 +100,7 @@ impl UdpSession {
             Ok((pkt, udp_header, src_addr)) => (pkt, udp_header, src_addr),
             Err((err, _)) => {
                 let msg = format!("recv from error: {err}");
-                return Err(IoError::new(ErrorKind::Other, msg))?;
+                return Err(IoError::other(msg))?;
             }
         };
         let dst_addr = udp_header.address;
@@ -128,20 +128,16 @@ impl UdpSession {
                 }
             };
             if src_addr != connected_addr {
-                Err(IoError::new(
-                    ErrorKind::Other,
-                    format!("invalid source address: {src_addr}"),
-                ))?;
+                Err(IoError::other(format!(
+                    "invalid source address: {src_addr}"
+                )))?;
             }
         } else {
             self.socket.get_ref().connect(src_addr).await?;
         }
 
         if frag != 0 {
-            Err(IoError::new(
-                ErrorKind::Other,
-                "fragmented packet is not supported",
-            ))?;
+            Err(IoError::other("fragmented packet is not supported"))?;
         }
 
         log::debug!(
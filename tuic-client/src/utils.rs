@@ -5,6 +5,7 @@ use rustls::pki_types::{CertificateDer};
 
 use rustls_pemfile::Item;
 use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
     fs::{self, File},
     io::BufReader,
     net::{IpAddr, SocketAddr},
@@ -69,10 +70,23 @@ impl ServerAddr {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub enum UdpRelayMode {
     Native,
     Quic,
+    Stream,
+    Auto,
+}
+
+impl Display for UdpRelayMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Native => write!(f, "native"),
+            Self::Quic => write!(f, "quic"),
+            Self::Stream => write!(f, "stream"),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
 }
 
 impl FromStr for UdpRelayMode {
@@ -83,6 +97,10 @@ impl FromStr for UdpRelayMode {
             Ok(Self::Native)
         } else if s.eq_ignore_ascii_case("quic") {
             Ok(Self::Quic)
+        } else if s.eq_ignore_ascii_case("stream") {
+            Ok(Self::Stream)
+        } else if s.eq_ignore_ascii_case("auto") {
+            Ok(Self::Auto)
         } else {
             Err("invalid UDP relay mode")
         }
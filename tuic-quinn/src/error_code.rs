@@ -0,0 +1,106 @@
+#[cfg(feature = "quinn")]
+use quinn::VarInt;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The application error code a connection is closed or a stream is reset with, telling the peer the reason
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorCode {
+    /// `0x00` - no specific reason
+    Unspecified,
+    /// `0x01` - the client failed to authenticate
+    AuthFailed,
+    /// `0x02` - the client did not authenticate in time
+    AuthTimeout,
+    /// `0x03` - the peer sent an invalid command
+    BadCommand,
+    /// `0x04` - the relay target could not be reached
+    TargetUnreachable,
+    /// `0x05` - the server does not allow the relay
+    PolicyDenied,
+    /// `0x06` - the server is shutting down
+    ServerShutdown,
+    /// `0x07` - the server has too many tasks waiting for the authentication
+    TooManyTasks,
+    /// An error code not known by this implementation, which should be treated as unspecified
+    Unknown(u64),
+}
+
+impl ErrorCode {
+    const UNSPECIFIED: u64 = 0x00;
+    const AUTH_FAILED: u64 = 0x01;
+    const AUTH_TIMEOUT: u64 = 0x02;
+    const BAD_COMMAND: u64 = 0x03;
+    const TARGET_UNREACHABLE: u64 = 0x04;
+    const POLICY_DENIED: u64 = 0x05;
+    const SERVER_SHUTDOWN: u64 = 0x06;
+    const TOO_MANY_TASKS: u64 = 0x07;
+
+    /// Returns the error code
+    pub const fn code(&self) -> u64 {
+        match self {
+            Self::Unspecified => Self::UNSPECIFIED,
+            Self::AuthFailed => Self::AUTH_FAILED,
+            Self::AuthTimeout => Self::AUTH_TIMEOUT,
+            Self::BadCommand => Self::BAD_COMMAND,
+            Self::TargetUnreachable => Self::TARGET_UNREACHABLE,
+            Self::PolicyDenied => Self::POLICY_DENIED,
+            Self::ServerShutdown => Self::SERVER_SHUTDOWN,
+            Self::TooManyTasks => Self::TOO_MANY_TASKS,
+            Self::Unknown(code) => *code,
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Unspecified => write!(f, "unspecified error"),
+            Self::AuthFailed => write!(f, "authentication failed"),
+            Self::AuthTimeout => write!(f, "authentication timed out"),
+            Self::BadCommand => write!(f, "bad command"),
+            Self::TargetUnreachable => write!(f, "target unreachable"),
+            Self::PolicyDenied => write!(f, "denied by server policy"),
+            Self::ServerShutdown => write!(f, "server shutting down"),
+            Self::TooManyTasks => write!(f, "too many pending tasks"),
+            Self::Unknown(code) => write!(f, "unknown error code {code:#x}"),
+        }
+    }
+}
+
+impl From<u64> for ErrorCode {
+    fn from(code: u64) -> Self {
+        match code {
+            Self::UNSPECIFIED => Self::Unspecified,
+            Self::AUTH_FAILED => Self::AuthFailed,
+            Self::AUTH_TIMEOUT => Self::AuthTimeout,
+            Self::BAD_COMMAND => Self::BadCommand,
+            Self::TARGET_UNREACHABLE => Self::TargetUnreachable,
+            Self::POLICY_DENIED => Self::PolicyDenied,
+            Self::SERVER_SHUTDOWN => Self::ServerShutdown,
+            Self::TOO_MANY_TASKS => Self::TooManyTasks,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for u64 {
+    fn from(code: ErrorCode) -> Self {
+        code.code()
+    }
+}
+
+#[cfg(feature = "quinn")]
+impl From<VarInt> for ErrorCode {
+    fn from(code: VarInt) -> Self {
+        Self::from(code.into_inner())
+    }
+}
+
+#[cfg(feature = "quinn")]
+impl From<ErrorCode> for VarInt {
+    fn from(code: ErrorCode) -> Self {
+        // unknown codes not fitting in a `VarInt` are sent as unspecified
+        VarInt::from_u64(code.code()).unwrap_or(VarInt::from_u32(0))
+    }
+}
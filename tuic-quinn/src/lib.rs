@@ -1,29 +1,60 @@
 #![doc = include_str!("../README.md")]
 
-use self::side::Side;
+use self::{
+    side::Side,
+    tasks::{AuthState, Authenticator, StreamReg},
+};
 use bytes::{BufMut, Bytes, BytesMut};
 use futures_util::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+#[cfg(feature = "quinn")]
 pub use quinn;
-use quinn::{ClosedStream, Connection as QuinnConnection, ConnectionError, RecvStream, SendDatagramError, SendStream, VarInt};
+#[cfg(feature = "quinn")]
+use quinn::{Connection as QuinnConnection, ConnectionError, SendDatagramError};
+use rand::Rng;
+use register_count::Register;
 use std::{
     fmt::{Debug, Formatter, Result as FmtResult},
-    io::{Cursor, Error as IoError},
+    io::{Error as IoError, ErrorKind},
+    ops::RangeInclusive,
     pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
     task::{Context, Poll},
     time::Duration,
 };
 use thiserror::Error;
+use tokio::sync::watch::Sender as WatchSender;
 use tuic::{
     model::{
         side::{Rx, Tx},
-        AssembleError, Authenticate as AuthenticateModel, Connect as ConnectModel,
-        Connection as ConnectionModel, KeyingMaterialExporter as KeyingMaterialExporterImpl,
-        Packet as PacketModel,
+        AssembleError, Assembler, Authenticate as AuthenticateModel, BufferPool,
+        Connect as ConnectModel, Connection as ConnectionModel, FragmentError,
+        KeyingMaterialExporter as KeyingMaterialExporterImpl, Packet as PacketModel,
+        ReassemblyLimits,
     },
-    Address, Header, UnmarshalError,
+    Address, AddressError, Capabilities, Connect as ConnectHeader, ConnectResult, ConnectStatus,
+    Dissociate, Extension as ExtensionHeader, Header, HeaderRef, Packet as PacketHeader, PacketRef,
+    Padding, Status, UnmarshalError,
 };
 use uuid::Uuid;
 
+mod error_code;
+mod tasks;
+mod transport;
+
+#[cfg(feature = "loopback")]
+pub mod loopback;
+
+#[cfg(feature = "tokio-io")]
+mod tokio_io;
+
+pub use self::{error_code::ErrorCode, tasks::Tasks, transport::Transport};
+
+#[cfg(feature = "tokio-io")]
+pub use self::tokio_io::Compat;
+
 pub mod side {
     //! Side marker types for a connection.
 
@@ -40,35 +71,93 @@ pub mod side {
 
 /// The TUIC Connection.
 ///
-/// This struct takes a clone of `quinn::Connection` for performing TUIC operations.
+/// This struct takes a clone of the [`Transport`] (`quinn::Connection` by default, with the `quinn` feature) for performing TUIC operations.
 ///
 /// See more details about the TUIC protocol at [SPEC.md](https://github.com/EAimTY/tuic/blob/dev/tuic/SPEC.md)
 #[derive(Clone)]
-pub struct Connection<Side> {
-    conn: QuinnConnection,
+pub struct Connection<
+    Side,
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    conn: T,
     model: ConnectionModel<Bytes>,
+    pool: BufferPool,
+    capabilities: Capabilities,
+    peer_capabilities: Arc<OnceLock<Capabilities>>,
+    padding: PaddingPolicy,
+    max_concurrent_streams: u32,
+    task_negotiation_timeout: Option<Duration>,
+    auth: Option<Arc<WatchSender<AuthState>>>,
+    authenticator: Option<Authenticator<T>>,
+    auth_timeout: Option<Duration>,
+    max_pending_tasks: usize,
+    is_draining: Arc<AtomicBool>,
     _marker: Side,
 }
 
-impl<Side> Connection<Side> {
+/// The optional features supported by this crate
+const DEFAULT_CAPABILITIES: Capabilities = Capabilities::new(
+    Capabilities::UDP_RELAY_NATIVE.bits()
+        | Capabilities::UDP_RELAY_QUIC.bits()
+        | Capabilities::EXTENSION.bits()
+        | Capabilities::CONNECT_RESULT.bits()
+        | Capabilities::PADDING.bits()
+        | Capabilities::UDP_RELAY_STREAM.bits(),
+);
+
+/// The initial limit of concurrent streams the peer may open, see [`Connection::with_max_concurrent_streams()`]
+pub const DEFAULT_CONCURRENT_STREAMS: u32 = 32;
+
+/// The limit of tasks held back until the connection is authenticated, see [`Connection::with_max_pending_tasks()`]
+pub const DEFAULT_PENDING_TASKS: usize = 64;
+
+/// The lengths of the `Padding` commands sent along with other commands, picked at random from each range. `None` disables the padding.
+///
+/// The handshake padding, which peers not knowing the command never read, is always sent. The others are only sent if both peers advertised [`Capabilities::PADDING`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PaddingPolicy {
+    /// Appended to the stream of `Authenticate` (client) or `Capabilities` (server)
+    pub handshake: Option<RangeInclusive<u16>>,
+    /// Appended to `Heartbeat` datagrams (client)
+    pub heartbeat: Option<RangeInclusive<u16>>,
+    /// Sent before `Connect` (client) or `ConnectResult` (server) on the relay stream
+    pub connect: Option<RangeInclusive<u16>>,
+}
+
+impl PaddingPolicy {
+    fn pick(range: &Option<RangeInclusive<u16>>) -> Option<u16> {
+        range
+            .as_ref()
+            .filter(|range| !range.is_empty())
+            .map(|range| rand::thread_rng().gen_range(range.clone()))
+    }
+}
+
+impl<Side, T> Connection<Side, T>
+where
+    T: Transport,
+{
     /// Sends a `Packet` using UDP relay mode `native`.
     pub fn packet_native(
         &self,
         pkt: impl AsRef<[u8]>,
         addr: Address,
         assoc_id: u16,
-    ) -> Result<(), Error> {
-        let Some(max_pkt_size) = self.conn.max_datagram_size() else {
-            return Err(Error::SendDatagram(SendDatagramError::Disabled));
-        };
+    ) -> Result<(), Error<T>> {
+        let max_pkt_size = self.conn.max_datagram_size().map_err(Error::SendDatagram)?;
 
         let model = self.model.send_packet(assoc_id, addr, max_pkt_size);
 
-        for (header, frag) in model.into_fragments(pkt) {
+        for (header, frag) in model.try_into_fragments(&pkt)? {
             let mut buf = BytesMut::with_capacity(header.len() + frag.len());
-            header.write(&mut buf);
+            header.try_write(&mut buf)?;
             buf.put_slice(frag);
-            self.conn.send_datagram(Bytes::from(buf))?;
+            self.conn
+                .send_datagram(Bytes::from(buf))
+                .map_err(Error::SendDatagram)?;
         }
 
         Ok(())
@@ -80,11 +169,11 @@ impl<Side> Connection<Side> {
         pkt: impl AsRef<[u8]>,
         addr: Address,
         assoc_id: u16,
-    ) -> Result<(), Error> {
+    ) -> Result<(), Error<T>> {
         let model = self.model.send_packet(assoc_id, addr, u16::MAX as usize);
 
-        for (header, frag) in model.into_fragments(pkt) {
-            let mut send = self.conn.open_uni().await?;
+        for (header, frag) in model.try_into_fragments(&pkt)? {
+            let mut send = self.conn.open_uni().await.map_err(Error::Connection)?;
             header.async_marshal(&mut send).await?;
             AsyncWriteExt::write_all(&mut send, frag).await?;
             send.close().await?;
@@ -93,6 +182,55 @@ impl<Side> Connection<Side> {
         Ok(())
     }
 
+    /// Sends an `Extension` command through a unidirectional stream. The stream is returned for sending extension specific data, and should be finished by the caller.
+    pub async fn extension_uni_stream(
+        &self,
+        ext: ExtensionHeader,
+    ) -> Result<T::SendStream, Error<T>> {
+        let mut send = self.conn.open_uni().await.map_err(Error::Connection)?;
+        Header::Extension(ext).async_marshal(&mut send).await?;
+        Ok(send)
+    }
+
+    /// Sends an `Extension` command through a bidirectional stream. The streams are returned for exchanging extension specific data.
+    pub async fn extension_bi_stream(
+        &self,
+        ext: ExtensionHeader,
+    ) -> Result<(T::SendStream, T::RecvStream), Error<T>> {
+        let (mut send, recv) = self.conn.open_bi().await.map_err(Error::Connection)?;
+        Header::Extension(ext).async_marshal(&mut send).await?;
+        Ok((send, recv))
+    }
+
+    /// Sends an `Extension` command through a QUIC datagram.
+    pub fn extension_datagram(&self, ext: ExtensionHeader) -> Result<(), Error<T>> {
+        let header = Header::Extension(ext);
+        let mut buf = BytesMut::with_capacity(header.len());
+        header.write(&mut buf);
+        self.conn
+            .send_datagram(Bytes::from(buf))
+            .map_err(Error::SendDatagram)?;
+        Ok(())
+    }
+
+    /// Sets the capabilities advertised to the peer. Defaults to all optional features this crate supports.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Returns the capabilities advertised to the peer
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Returns the capabilities advertised by the peer, or `None` if the peer has not advertised any (yet).
+    ///
+    /// Peers not supporting the `Capabilities` command never advertise.
+    pub fn peer_capabilities(&self) -> Option<Capabilities> {
+        self.peer_capabilities.get().copied()
+    }
+
     /// Returns the number of `Connect` tasks
     pub fn task_connect_count(&self) -> usize {
         self.model.task_connect_count()
@@ -103,68 +241,250 @@ impl<Side> Connection<Side> {
         self.model.task_associate_count()
     }
 
-    /// Removes packet fragments that can not be reassembled within the specified timeout
+    /// Closes a UDP session locally as a received `Dissociate` does, discarding its incomplete packets, without notifying the peer
+    pub fn close_udp_session(&self, assoc_id: u16) {
+        self.model.recv_dissociate(Dissociate::new(assoc_id));
+    }
+
+    /// Removes packet fragments that can not be reassembled within the specified timeout, along with the UDP sessions left with neither pending packets nor an assembled one
     pub fn collect_garbage(&self, timeout: Duration) {
         self.model.collect_garbage(timeout);
     }
 
-    fn keying_material_exporter(&self) -> KeyingMaterialExporter {
+    /// Returns the limits of packet reassembly
+    pub fn reassembly_limits(&self) -> ReassemblyLimits {
+        self.model.limits()
+    }
+
+    /// Sets the limits of packet reassembly
+    pub fn set_reassembly_limits(&self, limits: ReassemblyLimits) {
+        self.model.set_limits(limits);
+    }
+
+    /// Sets the pool of memory that [`Packet::accept()`] assembles fragmented packets with, e.g. to share one between connections. Defaults to a pool of this connection only.
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Sets the padding sent along with other commands. Defaults to no padding.
+    pub fn with_padding(mut self, padding: PaddingPolicy) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns the padding sent along with other commands
+    pub fn padding(&self) -> &PaddingPolicy {
+        &self.padding
+    }
+
+    /// Sets the limit of concurrent unidirectional and bidirectional streams the peer may open, which must match the one configured in the transport. [`tasks()`](Self::tasks) doubles it through the transport whenever it is reached. Defaults to [`DEFAULT_CONCURRENT_STREAMS`].
+    pub fn with_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.max_concurrent_streams = max;
+        self
+    }
+
+    /// Sets the time [`tasks()`](Self::tasks) waits for a stream to carry a command, after which [`Error::TaskNegotiationTimeout`] is yielded. Defaults to no timeout. A tokio runtime is required if it is set.
+    pub fn with_task_negotiation_timeout(mut self, timeout: Duration) -> Self {
+        self.task_negotiation_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends a `Padding` command with `len` filler bytes on a new unidirectional stream.
+    ///
+    /// The peer should have advertised [`Capabilities::PADDING`], as peers not knowing the command treat it as an error.
+    pub async fn send_padding(&self, len: u16) -> Result<(), Error<T>> {
+        let mut send = self.conn.open_uni().await.map_err(Error::Connection)?;
+        Header::Padding(Padding::new(len))
+            .async_marshal(&mut send)
+            .await?;
+        send.close().await?;
+        Ok(())
+    }
+
+    /// Starts draining the connection. [`tasks()`](Self::tasks) refuses the bidirectional streams the peer opens afterwards by resetting them with [`ErrorCode::ServerShutdown`], and drops the packets that would start a new UDP session, while the existing tasks are left to finish. Packets of existing UDP sessions, `Dissociate` and `Heartbeat` are still accepted.
+    ///
+    /// The connection is not closed by this. Wait for [`task_connect_count()`](Self::task_connect_count) and [`task_associate_count()`](Self::task_associate_count) to reach zero before closing it.
+    pub fn drain(&self) {
+        self.is_draining.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` if the connection is draining, see [`drain()`](Self::drain)
+    pub fn is_draining(&self) -> bool {
+        self.is_draining.load(Ordering::Acquire)
+    }
+
+    /// Immediately closes the connection with the given error code, which is also sent as the reason in text.
+    pub fn close(&self, error_code: ErrorCode) {
+        self.conn
+            .close(error_code, error_code.to_string().as_bytes());
+    }
+
+    /// Returns `true` if both sides support the features
+    fn is_negotiated(&self, caps: Capabilities) -> bool {
+        self.capabilities.contains(caps)
+            && self
+                .peer_capabilities()
+                .is_some_and(|peer| peer.contains(caps))
+    }
+
+    /// Applies the side effects of an accepted task to the connection model
+    fn commit(&self, task: &mut Task<T>) {
+        match task {
+            Task::Connect(conn) => conn.commit(&self.model),
+            Task::Dissociate(assoc_id) => self.close_udp_session(*assoc_id),
+            _ => {}
+        }
+    }
+
+    fn keying_material_exporter(&self) -> KeyingMaterialExporter<T> {
         KeyingMaterialExporter(self.conn.clone())
     }
 }
 
-impl Connection<side::Client> {
+impl<T> Connection<side::Client, T>
+where
+    T: Transport,
+{
     /// Creates a new client side `Connection`.
-    pub fn new(conn: QuinnConnection) -> Self {
+    pub fn new(conn: T) -> Self {
         Self {
             conn,
             model: ConnectionModel::new(),
+            pool: BufferPool::new(),
+            capabilities: DEFAULT_CAPABILITIES,
+            peer_capabilities: Arc::new(OnceLock::new()),
+            padding: PaddingPolicy::default(),
+            max_concurrent_streams: DEFAULT_CONCURRENT_STREAMS,
+            task_negotiation_timeout: None,
+            auth: None,
+            authenticator: None,
+            auth_timeout: None,
+            max_pending_tasks: DEFAULT_PENDING_TASKS,
+            is_draining: Arc::new(AtomicBool::new(false)),
             _marker: side::Client,
         }
     }
 
-    /// Sends an `Authenticate` command.
-    pub async fn authenticate(&self, uuid: Uuid, password: impl AsRef<[u8]>) -> Result<(), Error> {
+    /// Sends an `Authenticate` command, followed by a `Capabilities` command advertising [`capabilities()`](Self::capabilities) and the handshake padding.
+    pub async fn authenticate(
+        &self,
+        uuid: Uuid,
+        password: impl AsRef<[u8]>,
+    ) -> Result<(), Error<T>> {
         let model = self
             .model
             .send_authenticate(uuid, password, &self.keying_material_exporter());
 
-        let mut send = self.conn.open_uni().await?;
+        let mut send = self.conn.open_uni().await.map_err(Error::Connection)?;
         model.header().async_marshal(&mut send).await?;
+        Header::Capabilities(self.capabilities)
+            .async_marshal(&mut send)
+            .await?;
+
+        // servers stop reading the stream after the commands they know
+        if let Some(len) = PaddingPolicy::pick(&self.padding.handshake) {
+            Header::Padding(Padding::new(len))
+                .async_marshal(&mut send)
+                .await?;
+        }
+
         send.close().await?;
         Ok(())
     }
 
     /// Sends a `Connect` command.
-    pub async fn connect(&self, addr: Address) -> Result<Connect, Error> {
+    ///
+    /// If both sides support [`Capabilities::CONNECT_RESULT`], the server is asked to report the outcome, see [`Connect::result()`].
+    pub async fn connect(&self, addr: Address) -> Result<Connect<T>, Error<T>> {
         let model = self.model.send_connect(addr);
-        let (mut send, recv) = self.conn.open_bi().await?;
+        let (mut send, recv) = self.conn.open_bi().await.map_err(Error::Connection)?;
+
+        let padding = self.is_negotiated(Capabilities::PADDING);
+        if let Some(len) = PaddingPolicy::pick(&self.padding.connect).filter(|_| padding) {
+            Header::Padding(Padding::new(len))
+                .async_marshal(&mut send)
+                .await?;
+        }
+
+        // the server learns which features to use from the stream itself, as it can not tell if our `Capabilities` were received before opening it
+        let request_result = self.is_negotiated(Capabilities::CONNECT_RESULT);
+        if request_result {
+            let mut stream_caps = Capabilities::CONNECT_RESULT;
+            if padding {
+                stream_caps = stream_caps | Capabilities::PADDING;
+            }
+
+            Header::Capabilities(stream_caps)
+                .async_marshal(&mut send)
+                .await?;
+        }
+
         model.header().async_marshal(&mut send).await?;
-        Ok(Connect::new(Side::Client(model), send, recv))
+        Ok(Connect::new(
+            Side::Client(model),
+            send,
+            recv,
+            request_result,
+        ))
+    }
+
+    /// Opens the stream of a UDP session for UDP relay mode `stream`, carrying its `Packet`s in both directions.
+    ///
+    /// The server only learns about the stream when the first packet is sent. The peer should have advertised [`Capabilities::UDP_RELAY_STREAM`].
+    pub async fn packet_stream(&self, assoc_id: u16) -> Result<PacketStream<T>, Error<T>> {
+        let (send, recv) = self.conn.open_bi().await.map_err(Error::Connection)?;
+        Ok(PacketStream::new(
+            self.model.clone(),
+            self.pool.clone(),
+            assoc_id,
+            send,
+            recv,
+            None,
+            true,
+        ))
     }
 
     /// Sends a `Dissociate` command.
-    pub async fn dissociate(&self, assoc_id: u16) -> Result<(), Error> {
+    pub async fn dissociate(&self, assoc_id: u16) -> Result<(), Error<T>> {
         let model = self.model.send_dissociate(assoc_id);
-        let mut send = self.conn.open_uni().await?;
+        let mut send = self.conn.open_uni().await.map_err(Error::Connection)?;
         model.header().async_marshal(&mut send).await?;
         send.close().await?;
         Ok(())
     }
 
-    /// Sends a `Heartbeat` command.
-    pub async fn heartbeat(&self) -> Result<(), Error> {
+    /// Sends a `Heartbeat` command, followed by the heartbeat padding.
+    pub async fn heartbeat(&self) -> Result<(), Error<T>> {
         let model = self.model.send_heartbeat();
         let mut buf = Vec::with_capacity(model.header().len());
         model.header().async_marshal(&mut buf).await.unwrap();
-        self.conn.send_datagram(Bytes::from(buf))?;
+
+        // servers only read the `Heartbeat`, and the padding must still fit in the datagram
+        let room = self
+            .conn
+            .max_datagram_size()
+            .ok()
+            .and_then(|max| max.checked_sub(buf.len() + 4));
+
+        let padding = PaddingPolicy::pick(&self.padding.heartbeat)
+            .filter(|_| self.is_negotiated(Capabilities::PADDING));
+
+        if let (Some(len), Some(room)) = (padding, room) {
+            let len = len.min(u16::try_from(room).unwrap_or(u16::MAX));
+            Header::Padding(Padding::new(len)).write(&mut buf);
+        }
+
+        self.conn
+            .send_datagram(Bytes::from(buf))
+            .map_err(Error::SendDatagram)?;
         Ok(())
     }
 
-    /// Try to parse a `quinn::RecvStream` as a TUIC command.
+    /// Try to parse a `RecvStream` as a TUIC command.
     ///
-    /// The `quinn::RecvStream` should be accepted by `quinn::Connection::accept_uni()` from the same `quinn::Connection`.
-    pub async fn accept_uni_stream(&self, mut recv: RecvStream) -> Result<Task, Error> {
+    /// The `RecvStream` should be accepted by `Transport::accept_uni()` from the same transport.
+    pub async fn accept_uni_stream(&self, mut recv: T::RecvStream) -> Result<Task<T>, Error<T>> {
         let header = match Header::async_unmarshal(&mut recv).await {
             Ok(header) => header,
             Err(err) => return Err(Error::UnmarshalUniStream(err, recv)),
@@ -176,26 +496,41 @@ impl Connection<side::Client> {
             Header::Packet(pkt) => {
                 let assoc_id = pkt.assoc_id();
                 let pkt_id = pkt.pkt_id();
-                self.model
-                    .recv_packet(pkt)
-                    .map_or(Err(Error::InvalidUdpSession(assoc_id, pkt_id)), |pkt| {
-                        Ok(Task::Packet(Packet::new(pkt, PacketSource::Quic(recv))))
-                    })
+                self.model.recv_packet(pkt).map_or(
+                    Err(Error::InvalidUdpSession(assoc_id, pkt_id)),
+                    |pkt| {
+                        Ok(Task::Packet(Packet::new(
+                            pkt.into(),
+                            PacketSource::Quic(recv),
+                            self.pool.clone(),
+                        )))
+                    },
+                )
             }
             Header::Dissociate(_) => Err(Error::BadCommandUniStream("dissociate", recv)),
             Header::Heartbeat(_) => Err(Error::BadCommandUniStream("heartbeat", recv)),
+            Header::Capabilities(caps) => {
+                let _ = self.peer_capabilities.set(caps);
+                Ok(Task::Capabilities(caps))
+            }
+            Header::ConnectResult(_) => Err(Error::BadCommandUniStream("connect_result", recv)),
+            Header::Padding(_) => Ok(Task::Padding),
+            Header::Extension(ext) => Ok(Task::Extension(Extension::new(
+                ext,
+                ExtensionSource::UniStream(recv),
+            ))),
             _ => unreachable!(),
         }
     }
 
-    /// Try to parse a pair of `quinn::SendStream` and `quinn::RecvStream` as a TUIC command.
+    /// Try to parse a pair of `SendStream` and `RecvStream` as a TUIC command.
     ///
-    /// The pair of stream should be accepted by `quinn::Connection::accept_bi()` from the same `quinn::Connection`.
+    /// The pair of stream should be accepted by `Transport::accept_bi()` from the same transport.
     pub async fn accept_bi_stream(
         &self,
-        send: SendStream,
-        mut recv: RecvStream,
-    ) -> Result<Task, Error> {
+        send: T::SendStream,
+        mut recv: T::RecvStream,
+    ) -> Result<Task<T>, Error<T>> {
         let header = match Header::async_unmarshal(&mut recv).await {
             Ok(header) => header,
             Err(err) => return Err(Error::UnmarshalBiStream(err, send, recv)),
@@ -207,63 +542,154 @@ impl Connection<side::Client> {
             Header::Packet(_) => Err(Error::BadCommandBiStream("packet", send, recv)),
             Header::Dissociate(_) => Err(Error::BadCommandBiStream("dissociate", send, recv)),
             Header::Heartbeat(_) => Err(Error::BadCommandBiStream("heartbeat", send, recv)),
+            Header::Capabilities(_) => Err(Error::BadCommandBiStream("capabilities", send, recv)),
+            Header::ConnectResult(_) => {
+                Err(Error::BadCommandBiStream("connect_result", send, recv))
+            }
+            Header::Padding(_) => Ok(Task::Padding),
+            Header::Extension(ext) => Ok(Task::Extension(Extension::new(
+                ext,
+                ExtensionSource::BiStream(send, recv),
+            ))),
             _ => unreachable!(),
         }
     }
 
     /// Try to parse a QUIC Datagram as a TUIC command.
     ///
-    /// The Datagram should be accepted by `quinn::Connection::read_datagram()` from the same `quinn::Connection`.
-    pub fn accept_datagram(&self, dg: Bytes) -> Result<Task, Error> {
-        let mut dg = Cursor::new(dg);
-
-        let header = match Header::unmarshal(&mut dg) {
+    /// The Datagram should be accepted by `Transport::read_datagram()` from the same transport.
+    pub fn accept_datagram(&self, dg: Bytes) -> Result<Task<T>, Error<T>> {
+        let (header, pos) = match decode_datagram(&dg) {
             Ok(header) => header,
-            Err(err) => return Err(Error::UnmarshalDatagram(err, dg.into_inner())),
+            Err(err) => return Err(Error::UnmarshalDatagram(err, dg)),
         };
 
         match header {
-            Header::Authenticate(_) => {
-                Err(Error::BadCommandDatagram("authenticate", dg.into_inner()))
-            }
-            Header::Connect(_) => Err(Error::BadCommandDatagram("connect", dg.into_inner())),
-            Header::Packet(pkt) => {
-                let assoc_id = pkt.assoc_id();
-                let pkt_id = pkt.pkt_id();
-                if let Some(pkt) = self.model.recv_packet(pkt) {
-                    let pos = dg.position() as usize;
-                    let mut buf = dg.into_inner();
-                    if (pos + pkt.size() as usize) <= buf.len() {
-                        buf = buf.slice(pos..pos + pkt.size() as usize);
-                        Ok(Task::Packet(Packet::new(pkt, PacketSource::Native(buf))))
-                    } else {
-                        Err(Error::PayloadLength(pkt.size() as usize, buf.len() - pos))
-                    }
+            HeaderRef::Authenticate(_) => Err(Error::BadCommandDatagram("authenticate", dg)),
+            HeaderRef::Connect(_) => Err(Error::BadCommandDatagram("connect", dg)),
+            HeaderRef::Packet(pkt) => {
+                if !self.model.has_udp_session(pkt.assoc_id()) {
+                    Err(Error::InvalidUdpSession(pkt.assoc_id(), pkt.pkt_id()))
+                } else if (pos + pkt.size() as usize) <= dg.len() {
+                    let head =
+                        PacketHead::datagram(self.model.clone(), &pkt, dg.slice(..pos), true);
+                    let buf = dg.slice(pos..pos + pkt.size() as usize);
+                    Ok(Task::Packet(Packet::new(
+                        head,
+                        PacketSource::Native(buf),
+                        self.pool.clone(),
+                    )))
                 } else {
-                    Err(Error::InvalidUdpSession(assoc_id, pkt_id))
+                    Err(Error::PayloadLength(pkt.size() as usize, dg.len() - pos))
                 }
             }
-            Header::Dissociate(_) => Err(Error::BadCommandDatagram("dissociate", dg.into_inner())),
-            Header::Heartbeat(_) => Err(Error::BadCommandDatagram("heartbeat", dg.into_inner())),
+            HeaderRef::Dissociate(_) => Err(Error::BadCommandDatagram("dissociate", dg)),
+            HeaderRef::Heartbeat(_) => Err(Error::BadCommandDatagram("heartbeat", dg)),
+            HeaderRef::Capabilities(_) => Err(Error::BadCommandDatagram("capabilities", dg)),
+            HeaderRef::ConnectResult(_) => Err(Error::BadCommandDatagram("connect_result", dg)),
+            HeaderRef::Padding(_) => Ok(Task::Padding),
+            HeaderRef::Extension(ext) => Ok(Task::Extension(Extension::new(
+                ext.into_owned(),
+                ExtensionSource::Datagram,
+            ))),
             _ => unreachable!(),
         }
     }
 }
 
-impl Connection<side::Server> {
+impl<T> Connection<side::Server, T>
+where
+    T: Transport,
+{
     /// Creates a new server side `Connection`.
-    pub fn new(conn: QuinnConnection) -> Self {
+    pub fn new(conn: T) -> Self {
         Self {
             conn,
             model: ConnectionModel::new(),
+            pool: BufferPool::new(),
+            capabilities: DEFAULT_CAPABILITIES,
+            peer_capabilities: Arc::new(OnceLock::new()),
+            padding: PaddingPolicy::default(),
+            max_concurrent_streams: DEFAULT_CONCURRENT_STREAMS,
+            task_negotiation_timeout: None,
+            auth: Some(Arc::new(WatchSender::new(AuthState::Pending))),
+            authenticator: None,
+            auth_timeout: None,
+            max_pending_tasks: DEFAULT_PENDING_TASKS,
+            is_draining: Arc::new(AtomicBool::new(false)),
             _marker: side::Server,
         }
     }
 
-    /// Try to parse a `quinn::RecvStream` as a TUIC command.
+    /// Sets the callback validating the `Authenticate` commands received by [`tasks()`](Self::tasks).
+    ///
+    /// If it returns `true`, the connection is marked authenticated, the held tasks are released and the command is yielded. Otherwise [`Error::AuthFailed`] is yielded and the held tasks are cancelled. Without it, the commands are yielded as is, and the connection has to be marked with [`set_authenticated()`](Self::set_authenticated).
+    pub fn with_authenticator(
+        mut self,
+        authenticator: impl Fn(&Authenticate<T>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Sets the time [`tasks()`](Self::tasks) waits for the connection to be authenticated, after which [`Error::AuthTimeout`] is yielded and the held tasks are cancelled. Defaults to no timeout. A tokio runtime is required if it is set.
+    pub fn with_auth_timeout(mut self, timeout: Duration) -> Self {
+        self.auth_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the limit of tasks [`tasks()`](Self::tasks) holds back until the connection is authenticated. Tasks beyond it are dropped with [`Error::TooManyPendingTasks`], resetting the streams they came on with [`ErrorCode::TooManyTasks`]. Defaults to [`DEFAULT_PENDING_TASKS`].
+    pub fn with_max_pending_tasks(mut self, max: usize) -> Self {
+        self.max_pending_tasks = max;
+        self
+    }
+
+    /// Marks the connection as authenticated by the client `uuid`, releasing the tasks [`tasks()`](Self::tasks) holds back.
+    pub fn set_authenticated(&self, uuid: Uuid) {
+        if let Some(auth) = &self.auth {
+            auth.send_replace(AuthState::Authenticated(uuid));
+        }
+    }
+
+    /// Returns the UUID of the client, or `None` if the connection is not authenticated (yet).
+    pub fn authenticated(&self) -> Option<Uuid> {
+        match *self.auth.as_ref()?.borrow() {
+            AuthState::Authenticated(uuid) => Some(uuid),
+            AuthState::Pending | AuthState::Failed => None,
+        }
+    }
+
+    /// Sends a `Capabilities` command advertising [`capabilities()`](Self::capabilities), followed by the handshake padding.
+    ///
+    /// This should only be sent after the client is authenticated, and only if the client advertised its capabilities, as older clients do not know the command.
+    pub async fn advertise_capabilities(&self) -> Result<(), Error<T>> {
+        let mut send = self.conn.open_uni().await.map_err(Error::Connection)?;
+        Header::Capabilities(self.capabilities)
+            .async_marshal(&mut send)
+            .await?;
+
+        // clients stop reading the stream after the `Capabilities`
+        if let Some(len) = PaddingPolicy::pick(&self.padding.handshake) {
+            Header::Padding(Padding::new(len))
+                .async_marshal(&mut send)
+                .await?;
+        }
+
+        send.close().await?;
+        Ok(())
+    }
+
+    /// Try to parse a `RecvStream` as a TUIC command.
     ///
-    /// The `quinn::RecvStream` should be accepted by `quinn::Connection::accept_uni()` from the same `quinn::Connection`.
-    pub async fn accept_uni_stream(&self, mut recv: RecvStream) -> Result<Task, Error> {
+    /// The `RecvStream` should be accepted by `Transport::accept_uni()` from the same transport.
+    pub async fn accept_uni_stream(&self, recv: T::RecvStream) -> Result<Task<T>, Error<T>> {
+        let mut task = self.parse_uni_stream(recv).await?;
+        self.commit(&mut task);
+        Ok(task)
+    }
+
+    /// Parses a `RecvStream` without applying the command to the connection model, which is left to [`commit()`](Self::commit) once the task is accepted
+    async fn parse_uni_stream(&self, mut recv: T::RecvStream) -> Result<Task<T>, Error<T>> {
         let header = match Header::async_unmarshal(&mut recv).await {
             Ok(header) => header,
             Err(err) => return Err(Error::UnmarshalUniStream(err, recv)),
@@ -271,6 +697,11 @@ impl Connection<side::Server> {
 
         match header {
             Header::Authenticate(auth) => {
+                // clients not supporting `Capabilities` finish the stream right after `Authenticate`
+                if let Ok(Header::Capabilities(caps)) = Header::async_unmarshal(&mut recv).await {
+                    let _ = self.peer_capabilities.set(caps);
+                }
+
                 let model = self.model.recv_authenticate(auth);
                 Ok(Task::Authenticate(Authenticate::new(
                     model,
@@ -280,76 +711,156 @@ impl Connection<side::Server> {
             Header::Connect(_) => Err(Error::BadCommandUniStream("connect", recv)),
             Header::Packet(pkt) => {
                 let model = self.model.recv_packet_unrestricted(pkt);
-                Ok(Task::Packet(Packet::new(model, PacketSource::Quic(recv))))
-            }
-            Header::Dissociate(dissoc) => {
-                let model = self.model.recv_dissociate(dissoc);
-                Ok(Task::Dissociate(model.assoc_id()))
+                Ok(Task::Packet(Packet::new(
+                    model.into(),
+                    PacketSource::Quic(recv),
+                    self.pool.clone(),
+                )))
             }
+            Header::Dissociate(dissoc) => Ok(Task::Dissociate(dissoc.assoc_id())),
             Header::Heartbeat(_) => Err(Error::BadCommandUniStream("heartbeat", recv)),
+            Header::Capabilities(_) => Err(Error::BadCommandUniStream("capabilities", recv)),
+            Header::ConnectResult(_) => Err(Error::BadCommandUniStream("connect_result", recv)),
+            Header::Padding(_) => Ok(Task::Padding),
+            Header::Extension(ext) => Ok(Task::Extension(Extension::new(
+                ext,
+                ExtensionSource::UniStream(recv),
+            ))),
             _ => unreachable!(),
         }
     }
 
-    /// Try to parse a pair of `quinn::SendStream` and `quinn::RecvStream` as a TUIC command.
+    /// Try to parse a pair of `SendStream` and `RecvStream` as a TUIC command.
     ///
-    /// The pair of stream should be accepted by `quinn::Connection::accept_bi()` from the same `quinn::Connection`.
+    /// The pair of stream should be accepted by `Transport::accept_bi()` from the same transport.
     pub async fn accept_bi_stream(
         &self,
-        send: SendStream,
-        mut recv: RecvStream,
-    ) -> Result<Task, Error> {
-        let header = match Header::async_unmarshal(&mut recv).await {
-            Ok(header) => header,
-            Err(err) => return Err(Error::UnmarshalBiStream(err, send, recv)),
+        send: T::SendStream,
+        recv: T::RecvStream,
+    ) -> Result<Task<T>, Error<T>> {
+        let mut task = self.parse_bi_stream(send, recv).await?;
+        self.commit(&mut task);
+        Ok(task)
+    }
+
+    /// Parses a pair of `SendStream` and `RecvStream` without applying the command to the connection model, which is left to [`commit()`](Self::commit) once the task is accepted
+    async fn parse_bi_stream(
+        &self,
+        send: T::SendStream,
+        mut recv: T::RecvStream,
+    ) -> Result<Task<T>, Error<T>> {
+        // the command can be preceded by a `Padding` and a `Capabilities` requesting optional features for this stream only
+        let mut stream_caps = None;
+        let mut padded = false;
+
+        let header = loop {
+            match Header::async_unmarshal(&mut recv).await {
+                Ok(Header::Padding(_)) if padded => {
+                    return Err(Error::BadCommandBiStream("padding", send, recv));
+                }
+                Ok(Header::Padding(_)) => padded = true,
+                Ok(Header::Capabilities(_)) if stream_caps.is_some() => {
+                    return Err(Error::BadCommandBiStream("capabilities", send, recv));
+                }
+                Ok(Header::Capabilities(caps)) => stream_caps = Some(caps),
+                Ok(header) => break header,
+                Err(err) => return Err(Error::UnmarshalBiStream(err, send, recv)),
+            }
         };
 
+        let stream_caps = stream_caps.unwrap_or(Capabilities::empty());
+
         match header {
             Header::Authenticate(_) => Err(Error::BadCommandBiStream("authenticate", send, recv)),
             Header::Connect(conn) => {
-                let model = self.model.recv_connect(conn);
-                Ok(Task::Connect(Connect::new(Side::Server(model), send, recv)))
+                let respond = stream_caps.contains(Capabilities::CONNECT_RESULT);
+                let padding = PaddingPolicy::pick(&self.padding.connect)
+                    .filter(|_| stream_caps.contains(Capabilities::PADDING));
+                Ok(Task::Connect(
+                    Connect::new(Side::Server(ConnectHead::Header(conn)), send, recv, respond)
+                        .with_padding(padding),
+                ))
             }
-            Header::Packet(_) => Err(Error::BadCommandBiStream("packet", send, recv)),
+            Header::Packet(pkt) => Ok(Task::PacketStream(PacketStream::new(
+                self.model.clone(),
+                self.pool.clone(),
+                pkt.assoc_id(),
+                send,
+                recv,
+                Some(pkt),
+                false,
+            ))),
             Header::Dissociate(_) => Err(Error::BadCommandBiStream("dissociate", send, recv)),
             Header::Heartbeat(_) => Err(Error::BadCommandBiStream("heartbeat", send, recv)),
+            Header::Capabilities(_) => Err(Error::BadCommandBiStream("capabilities", send, recv)),
+            Header::ConnectResult(_) => {
+                Err(Error::BadCommandBiStream("connect_result", send, recv))
+            }
+            Header::Padding(_) => Ok(Task::Padding),
+            Header::Extension(ext) => Ok(Task::Extension(Extension::new(
+                ext,
+                ExtensionSource::BiStream(send, recv),
+            ))),
             _ => unreachable!(),
         }
     }
 
     /// Try to parse a QUIC Datagram as a TUIC command.
     ///
-    /// The Datagram should be accepted by `quinn::Connection::read_datagram()` from the same `quinn::Connection`.
-    pub fn accept_datagram(&self, dg: Bytes) -> Result<Task, Error> {
-        let mut dg = Cursor::new(dg);
-
-        let header = match Header::unmarshal(&mut dg) {
+    /// The Datagram should be accepted by `Transport::read_datagram()` from the same transport.
+    pub fn accept_datagram(&self, dg: Bytes) -> Result<Task<T>, Error<T>> {
+        let (header, pos) = match decode_datagram(&dg) {
             Ok(header) => header,
-            Err(err) => return Err(Error::UnmarshalDatagram(err, dg.into_inner())),
+            Err(err) => return Err(Error::UnmarshalDatagram(err, dg)),
         };
 
         match header {
-            Header::Authenticate(_) => {
-                Err(Error::BadCommandDatagram("authenticate", dg.into_inner()))
-            }
-            Header::Connect(_) => Err(Error::BadCommandDatagram("connect", dg.into_inner())),
-            Header::Packet(pkt) => {
-                let model = self.model.recv_packet_unrestricted(pkt);
-                let pos = dg.position() as usize;
-                let buf = dg.into_inner().slice(pos..pos + model.size() as usize);
-                Ok(Task::Packet(Packet::new(model, PacketSource::Native(buf))))
+            HeaderRef::Authenticate(_) => Err(Error::BadCommandDatagram("authenticate", dg)),
+            HeaderRef::Connect(_) => Err(Error::BadCommandDatagram("connect", dg)),
+            HeaderRef::Packet(pkt) => {
+                if pos + pkt.size() as usize > dg.len() {
+                    return Err(Error::PayloadLength(pkt.size() as usize, dg.len() - pos));
+                }
+
+                let head = PacketHead::datagram(self.model.clone(), &pkt, dg.slice(..pos), false);
+                let buf = dg.slice(pos..pos + pkt.size() as usize);
+                Ok(Task::Packet(Packet::new(
+                    head,
+                    PacketSource::Native(buf),
+                    self.pool.clone(),
+                )))
             }
-            Header::Dissociate(_) => Err(Error::BadCommandDatagram("dissociate", dg.into_inner())),
-            Header::Heartbeat(hb) => {
+            HeaderRef::Dissociate(_) => Err(Error::BadCommandDatagram("dissociate", dg)),
+            HeaderRef::Heartbeat(hb) => {
                 let _ = self.model.recv_heartbeat(hb);
                 Ok(Task::Heartbeat)
             }
+            HeaderRef::Capabilities(_) => Err(Error::BadCommandDatagram("capabilities", dg)),
+            HeaderRef::ConnectResult(_) => Err(Error::BadCommandDatagram("connect_result", dg)),
+            HeaderRef::Padding(_) => Ok(Task::Padding),
+            HeaderRef::Extension(ext) => Ok(Task::Extension(Extension::new(
+                ext.into_owned(),
+                ExtensionSource::Datagram,
+            ))),
             _ => unreachable!(),
         }
     }
 }
 
-impl<Side> Debug for Connection<Side> {
+/// Decodes the header of a datagram in place, returning it with the offset of the payload.
+///
+/// A datagram is never continued, so a truncated header is reported as an unexpected EOF.
+fn decode_datagram(dg: &[u8]) -> Result<(HeaderRef<'_>, usize), UnmarshalError> {
+    match HeaderRef::decode(dg)? {
+        Status::Complete(header, pos) => Ok((header, pos)),
+        Status::Incomplete(_) => Err(UnmarshalError::Io(IoError::from(ErrorKind::UnexpectedEof))),
+    }
+}
+
+impl<Side, T> Debug for Connection<Side, T>
+where
+    T: Transport,
+{
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("Connection")
             .field("conn", &self.conn)
@@ -360,13 +871,21 @@ impl<Side> Debug for Connection<Side> {
 
 /// A received `Authenticate` command.
 #[derive(Debug)]
-pub struct Authenticate {
+pub struct Authenticate<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
     model: AuthenticateModel<Rx>,
-    exporter: KeyingMaterialExporter,
+    exporter: KeyingMaterialExporter<T>,
 }
 
-impl Authenticate {
-    fn new(model: AuthenticateModel<Rx>, exporter: KeyingMaterialExporter) -> Self {
+impl<T> Authenticate<T>
+where
+    T: Transport,
+{
+    fn new(model: AuthenticateModel<Rx>, exporter: KeyingMaterialExporter<T>) -> Self {
         Self { model, exporter }
     }
 
@@ -387,19 +906,55 @@ impl Authenticate {
 }
 
 /// A received `Connect` command.
-pub struct Connect {
-    model: Side<ConnectModel<Tx>, ConnectModel<Rx>>,
-    send: SendStream,
-    recv: RecvStream,
+pub struct Connect<#[cfg(feature = "quinn")] T = QuinnConnection, #[cfg(not(feature = "quinn"))] T>
+where
+    T: Transport,
+{
+    model: Side<ConnectModel<Tx>, ConnectHead>,
+    send: T::SendStream,
+    recv: T::RecvStream,
+    result_pending: bool,
+    result_padding: Option<u16>,
+    reg: StreamReg,
+}
+
+/// A received `Connect` is only counted by the connection model once it passes the authentication gate
+#[derive(Debug)]
+enum ConnectHead {
+    Header(ConnectHeader),
+    Model(ConnectModel<Rx>),
 }
 
-impl Connect {
+impl<T> Connect<T>
+where
+    T: Transport,
+{
     fn new(
-        model: Side<ConnectModel<Tx>, ConnectModel<Rx>>,
-        send: SendStream,
-        recv: RecvStream,
+        model: Side<ConnectModel<Tx>, ConnectHead>,
+        send: T::SendStream,
+        recv: T::RecvStream,
+        result_pending: bool,
     ) -> Self {
-        Self { model, send, recv }
+        Self {
+            model,
+            send,
+            recv,
+            result_pending,
+            result_padding: None,
+            reg: StreamReg::default(),
+        }
+    }
+
+    fn with_padding(mut self, padding: Option<u16>) -> Self {
+        self.result_padding = padding;
+        self
+    }
+
+    /// Registers a received `Connect` as a task of the connection model
+    fn commit(&mut self, model: &ConnectionModel<Bytes>) {
+        if let Side::Server(ConnectHead::Header(header)) = &self.model {
+            self.model = Side::Server(ConnectHead::Model(model.recv_connect(header.clone())));
+        }
     }
 
     /// Returns the `Connect` address
@@ -411,22 +966,109 @@ impl Connect {
                 };
                 conn.addr()
             }
-            Side::Server(model) => model.addr(),
+            Side::Server(ConnectHead::Header(header)) => header.addr(),
+            Side::Server(ConnectHead::Model(model)) => model.addr(),
+        }
+    }
+
+    /// Reads the `ConnectResult` of the server. It must be called before reading any relayed data.
+    ///
+    /// Returns `None` if the result was not requested (see [`Connection::connect()`]) or has already been read.
+    pub async fn result(&mut self) -> Result<Option<ConnectStatus>, Error<T>> {
+        if !matches!(self.model, Side::Client(_)) || !self.result_pending {
+            return Ok(None);
         }
+
+        self.result_pending = false;
+        read_connect_result(&mut self.recv).await.map(Some)
+    }
+
+    /// Returns `true` if the client requested a `ConnectResult` that has not been read (on the client side) or sent (on the server side) yet
+    pub fn is_result_pending(&self) -> bool {
+        self.result_pending
+    }
+
+    /// Sends a `ConnectResult` to the client if it requested one, otherwise does nothing. It must be called before writing any relayed data.
+    pub async fn respond(&mut self, status: ConnectStatus) -> Result<(), Error<T>> {
+        if !matches!(self.model, Side::Server(_)) || !self.result_pending {
+            return Ok(());
+        }
+
+        self.result_pending = false;
+        send_connect_result(&mut self.send, status, self.result_padding).await
     }
 
     /// Immediately closes the `Connect` streams with the given error code. Returns the result of closing the send and receive streams, respectively.
     pub fn reset(
         &mut self,
-        error_code: VarInt,
-    ) -> (Result<(), ClosedStream>, Result<(), ClosedStream>) {
-        let send_res = self.send.reset(error_code);
-        let recv_res = self.recv.stop(error_code);
+        error_code: ErrorCode,
+    ) -> (Result<(), T::ClosedStream>, Result<(), T::ClosedStream>) {
+        let send_res = T::reset(&mut self.send, error_code);
+        let recv_res = T::stop(&mut self.recv, error_code);
         (send_res, recv_res)
     }
+
+    /// Splits the `Connect` into owned read and write halves, which can be used and closed independently.
+    ///
+    /// A pending `ConnectResult` is read by [`ConnectReadHalf::result()`] on the client side, and sent by [`ConnectWriteHalf::respond()`] on the server side.
+    pub fn into_split(self) -> (ConnectReadHalf<T>, ConnectWriteHalf<T>) {
+        let is_client = matches!(self.model, Side::Client(_));
+
+        let read = ConnectReadHalf {
+            recv: self.recv,
+            result_pending: is_client && self.result_pending,
+            reg: self.reg.clone(),
+        };
+
+        let write = ConnectWriteHalf {
+            send: self.send,
+            result_pending: !is_client && self.result_pending,
+            result_padding: self.result_padding,
+            reg: self.reg,
+        };
+
+        (read, write)
+    }
+}
+
+async fn read_connect_result<T>(recv: &mut T::RecvStream) -> Result<ConnectStatus, Error<T>>
+where
+    T: Transport,
+{
+    loop {
+        match Header::async_unmarshal(&mut *recv).await {
+            Ok(Header::ConnectResult(result)) => return Ok(result.status()),
+            Ok(Header::Padding(_)) => {}
+            Ok(header) => return Err(Error::BadConnectResult(header.type_code())),
+            Err(err) => return Err(Error::UnmarshalConnectResult(err)),
+        }
+    }
 }
 
-impl AsyncRead for Connect {
+async fn send_connect_result<T>(
+    send: &mut T::SendStream,
+    status: ConnectStatus,
+    padding: Option<u16>,
+) -> Result<(), Error<T>>
+where
+    T: Transport,
+{
+    if let Some(len) = padding {
+        Header::Padding(Padding::new(len))
+            .async_marshal(&mut *send)
+            .await?;
+    }
+
+    Header::ConnectResult(ConnectResult::new(status))
+        .async_marshal(send)
+        .await?;
+    Ok(())
+}
+
+impl<T> AsyncRead for Connect<T>
+where
+    T: Transport,
+{
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -436,7 +1078,10 @@ impl AsyncRead for Connect {
     }
 }
 
-impl AsyncWrite for Connect {
+impl<T> AsyncWrite for Connect<T>
+where
+    T: Transport,
+{
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -454,7 +1099,10 @@ impl AsyncWrite for Connect {
     }
 }
 
-impl Debug for Connect {
+impl<T> Debug for Connect<T>
+where
+    T: Transport,
+{
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         let model = match &self.model {
             Side::Client(model) => model as &dyn Debug,
@@ -465,46 +1113,275 @@ impl Debug for Connect {
             .field("model", model)
             .field("send", &self.send)
             .field("recv", &self.recv)
+            .field("result_pending", &self.result_pending)
+            .field("result_padding", &self.result_padding)
+            .finish()
+    }
+}
+
+/// The owned read half of a [`Connect`], returned by [`Connect::into_split()`].
+pub struct ConnectReadHalf<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    recv: T::RecvStream,
+    result_pending: bool,
+    reg: StreamReg,
+}
+
+impl<T> ConnectReadHalf<T>
+where
+    T: Transport,
+{
+    /// Reads the `ConnectResult` of the server. It must be called before reading any relayed data.
+    ///
+    /// Returns `None` on the server side, or if the result was not requested or has already been read.
+    pub async fn result(&mut self) -> Result<Option<ConnectStatus>, Error<T>> {
+        if !self.result_pending {
+            return Ok(None);
+        }
+
+        self.result_pending = false;
+        read_connect_result(&mut self.recv).await.map(Some)
+    }
+
+    /// Immediately stops the receive stream with the given error code.
+    pub fn stop(&mut self, error_code: ErrorCode) -> Result<(), T::ClosedStream> {
+        T::stop(&mut self.recv, error_code)
+    }
+}
+
+impl<T> AsyncRead for ConnectReadHalf<T>
+where
+    T: Transport,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, IoError>> {
+        AsyncRead::poll_read(Pin::new(&mut self.get_mut().recv), cx, buf)
+    }
+}
+
+impl<T> Debug for ConnectReadHalf<T>
+where
+    T: Transport,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ConnectReadHalf")
+            .field("recv", &self.recv)
+            .field("result_pending", &self.result_pending)
+            .field("reg", &self.reg)
+            .finish()
+    }
+}
+
+/// The owned write half of a [`Connect`], returned by [`Connect::into_split()`].
+pub struct ConnectWriteHalf<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    send: T::SendStream,
+    result_pending: bool,
+    result_padding: Option<u16>,
+    reg: StreamReg,
+}
+
+impl<T> ConnectWriteHalf<T>
+where
+    T: Transport,
+{
+    /// Sends a `ConnectResult` to the client if it requested one, otherwise does nothing. It must be called before writing any relayed data.
+    pub async fn respond(&mut self, status: ConnectStatus) -> Result<(), Error<T>> {
+        if !self.result_pending {
+            return Ok(());
+        }
+
+        self.result_pending = false;
+        send_connect_result(&mut self.send, status, self.result_padding).await
+    }
+
+    /// Immediately resets the send stream with the given error code.
+    pub fn reset(&mut self, error_code: ErrorCode) -> Result<(), T::ClosedStream> {
+        T::reset(&mut self.send, error_code)
+    }
+}
+
+impl<T> AsyncWrite for ConnectWriteHalf<T>
+where
+    T: Transport,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        AsyncWrite::poll_write(Pin::new(&mut self.get_mut().send), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        AsyncWrite::poll_flush(Pin::new(&mut self.get_mut().send), cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        AsyncWrite::poll_close(Pin::new(&mut self.get_mut().send), cx)
+    }
+}
+
+impl<T> Debug for ConnectWriteHalf<T>
+where
+    T: Transport,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ConnectWriteHalf")
+            .field("send", &self.send)
+            .field("result_pending", &self.result_pending)
+            .field("result_padding", &self.result_padding)
+            .field("reg", &self.reg)
             .finish()
     }
 }
 
 /// A received `Packet` command.
 #[derive(Debug)]
-pub struct Packet {
-    model: PacketModel<Rx, Bytes>,
-    src: PacketSource,
+pub struct Packet<#[cfg(feature = "quinn")] T = QuinnConnection, #[cfg(not(feature = "quinn"))] T>
+where
+    T: Transport,
+{
+    head: PacketHead,
+    src: PacketSource<T>,
+    pool: BufferPool,
+    reg: StreamReg,
+}
+
+#[derive(Debug)]
+enum PacketHead {
+    Model(PacketModel<Rx, Bytes>),
+    /// The encoded header of a packet received in a datagram. It is only decoded into the model once the packet is accepted, so no address is copied out of a packet dropped before
+    Datagram {
+        model: ConnectionModel<Bytes>,
+        header: Bytes,
+        restricted: bool,
+        assoc_id: u16,
+        pkt_id: u16,
+        frag_id: u8,
+        frag_total: u8,
+    },
+}
+
+impl PacketHead {
+    fn datagram(
+        model: ConnectionModel<Bytes>,
+        pkt: &PacketRef<'_>,
+        header: Bytes,
+        restricted: bool,
+    ) -> Self {
+        Self::Datagram {
+            model,
+            header,
+            restricted,
+            assoc_id: pkt.assoc_id(),
+            pkt_id: pkt.pkt_id(),
+            frag_id: pkt.frag_id(),
+            frag_total: pkt.frag_total(),
+        }
+    }
+
+    fn into_model<T>(self) -> Result<PacketModel<Rx, Bytes>, Error<T>>
+    where
+        T: Transport,
+    {
+        match self {
+            Self::Model(model) => Ok(model),
+            Self::Datagram {
+                model,
+                header,
+                restricted,
+                assoc_id,
+                pkt_id,
+                ..
+            } => {
+                // the header was decoded as a `Packet` when the datagram was accepted
+                let Ok((HeaderRef::Packet(pkt), _)) = decode_datagram(&header) else {
+                    unreachable!()
+                };
+
+                if restricted {
+                    model
+                        .recv_packet(pkt.into_owned())
+                        .ok_or(Error::InvalidUdpSession(assoc_id, pkt_id))
+                } else {
+                    Ok(model.recv_packet_unrestricted(pkt.into_owned()))
+                }
+            }
+        }
+    }
+}
+
+impl From<PacketModel<Rx, Bytes>> for PacketHead {
+    fn from(model: PacketModel<Rx, Bytes>) -> Self {
+        Self::Model(model)
+    }
 }
 
 #[derive(Debug)]
-enum PacketSource {
-    Quic(RecvStream),
+enum PacketSource<T>
+where
+    T: Transport,
+{
+    Quic(T::RecvStream),
     Native(Bytes),
+    Stream(Bytes),
 }
 
-impl Packet {
-    fn new(model: PacketModel<Rx, Bytes>, src: PacketSource) -> Self {
-        Self { src, model }
+impl<T> Packet<T>
+where
+    T: Transport,
+{
+    fn new(head: PacketHead, src: PacketSource<T>, pool: BufferPool) -> Self {
+        Self {
+            src,
+            head,
+            pool,
+            reg: StreamReg::default(),
+        }
     }
 
     /// Returns the UDP session ID
     pub fn assoc_id(&self) -> u16 {
-        self.model.assoc_id()
+        match &self.head {
+            PacketHead::Model(model) => model.assoc_id(),
+            PacketHead::Datagram { assoc_id, .. } => *assoc_id,
+        }
     }
 
     /// Returns the packet ID
     pub fn pkt_id(&self) -> u16 {
-        self.model.pkt_id()
+        match &self.head {
+            PacketHead::Model(model) => model.pkt_id(),
+            PacketHead::Datagram { pkt_id, .. } => *pkt_id,
+        }
     }
 
     /// Returns the fragment ID
     pub fn frag_id(&self) -> u8 {
-        self.model.frag_id()
+        match &self.head {
+            PacketHead::Model(model) => model.frag_id(),
+            PacketHead::Datagram { frag_id, .. } => *frag_id,
+        }
     }
 
     /// Returns the total number of fragments
     pub fn frag_total(&self) -> u8 {
-        self.model.frag_total()
+        match &self.head {
+            PacketHead::Model(model) => model.frag_total(),
+            PacketHead::Datagram { frag_total, .. } => *frag_total,
+        }
     }
 
     /// Whether the packet is from UDP relay mode `quic`
@@ -517,76 +1394,474 @@ impl Packet {
         matches!(self.src, PacketSource::Native(_))
     }
 
+    /// Whether the packet is from UDP relay mode `stream`
+    pub fn is_from_stream(&self) -> bool {
+        matches!(self.src, PacketSource::Stream(_))
+    }
+
     /// Accepts the packet payload. If the packet is fragmented and not yet fully assembled, `Ok(None)` is returned.
-    pub async fn accept(self) -> Result<Option<(Bytes, Address, u16)>, Error> {
+    ///
+    /// A packet that is not fragmented is returned without copying, the others are assembled with the [`BufferPool`] of the connection.
+    pub async fn accept(self) -> Result<Option<(Bytes, Address, u16)>, Error<T>> {
+        let mut buf = self.pool.buffer();
+
+        Ok(self
+            .accept_into(&mut buf)
+            .await?
+            .map(|(addr, assoc_id)| (buf.into_bytes(), addr, assoc_id)))
+    }
+
+    /// Accepts the packet payload, assembling it into `buf`. If the packet is fragmented and not yet fully assembled, `Ok(None)` is returned and `buf` is left untouched.
+    ///
+    /// This allows assembling into a caller-provided buffer, e.g. one taken from a buffer pool.
+    pub async fn accept_into<A>(self, buf: &mut A) -> Result<Option<(Address, u16)>, Error<T>>
+    where
+        A: Assembler<Bytes>,
+    {
+        let model = self.head.into_model()?;
+
         let pkt = match self.src {
             PacketSource::Quic(mut recv) => {
-                let mut buf = vec![0; self.model.size() as usize];
+                let mut buf = BytesMut::zeroed(model.size() as usize);
                 AsyncReadExt::read_exact(&mut recv, &mut buf).await?;
-                Bytes::from(buf)
+                buf.freeze()
             }
-            PacketSource::Native(pkt) => pkt,
+            PacketSource::Native(pkt) | PacketSource::Stream(pkt) => pkt,
         };
 
-        let mut asm = Vec::new();
+        Ok(model.assemble(pkt)?.map(|pkt| pkt.assemble(buf)))
+    }
+}
 
-        Ok(self
-            .model
-            .assemble(pkt)?
-            .map(|pkt| pkt.assemble(&mut asm))
-            .map(|(addr, assoc_id)| (Bytes::from(asm), addr, assoc_id)))
+/// The stream of a UDP session in UDP relay mode `stream`, carrying its `Packet`s in both directions.
+#[derive(Debug)]
+pub struct PacketStream<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    send: PacketSender<T>,
+    recv: PacketReceiver<T>,
+}
+
+impl<T> PacketStream<T>
+where
+    T: Transport,
+{
+    /// Packets received by the client must be on a known UDP session, while the server creates the session on the first packet
+    fn new(
+        model: ConnectionModel<Bytes>,
+        pool: BufferPool,
+        assoc_id: u16,
+        send: T::SendStream,
+        recv: T::RecvStream,
+        first: Option<PacketHeader>,
+        restricted: bool,
+    ) -> Self {
+        Self {
+            send: PacketSender {
+                model: model.clone(),
+                assoc_id,
+                send,
+                reg: StreamReg::default(),
+            },
+            recv: PacketReceiver {
+                model,
+                pool,
+                assoc_id,
+                recv,
+                first,
+                restricted,
+                reg: StreamReg::default(),
+            },
+        }
+    }
+
+    /// Returns the UDP session ID
+    pub fn assoc_id(&self) -> u16 {
+        self.send.assoc_id
+    }
+
+    /// Sends a packet on the stream, see [`PacketSender::send()`].
+    pub async fn send(&mut self, pkt: impl AsRef<[u8]>, addr: Address) -> Result<(), Error<T>> {
+        self.send.send(pkt, addr).await
+    }
+
+    /// Receives a packet from the stream, see [`PacketReceiver::recv()`].
+    pub async fn recv(&mut self) -> Result<Option<Packet<T>>, Error<T>> {
+        self.recv.recv().await
+    }
+
+    /// Splits the stream into its sending and receiving halves, so packets can be sent and received concurrently.
+    pub fn into_split(self) -> (PacketSender<T>, PacketReceiver<T>) {
+        (self.send, self.recv)
+    }
+}
+
+/// The sending half of a [`PacketStream`].
+#[derive(Debug)]
+pub struct PacketSender<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    model: ConnectionModel<Bytes>,
+    assoc_id: u16,
+    send: T::SendStream,
+    reg: StreamReg,
+}
+
+impl<T> PacketSender<T>
+where
+    T: Transport,
+{
+    /// Returns the UDP session ID
+    pub fn assoc_id(&self) -> u16 {
+        self.assoc_id
+    }
+
+    /// Sends a packet. It is only fragmented if the payload does not fit in a single `Packet`, as the stream has no size limit.
+    pub async fn send(&mut self, pkt: impl AsRef<[u8]>, addr: Address) -> Result<(), Error<T>> {
+        let model = self.model.send_packet(self.assoc_id, addr, usize::MAX);
+
+        for (header, frag) in model.try_into_fragments(&pkt)? {
+            header.async_marshal(&mut self.send).await?;
+            AsyncWriteExt::write_all(&mut self.send, frag).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Finishes the stream. The peer receives the end of the stream after all sent packets.
+    pub async fn close(&mut self) -> Result<(), Error<T>> {
+        self.send.close().await?;
+        Ok(())
+    }
+}
+
+/// The receiving half of a [`PacketStream`].
+#[derive(Debug)]
+pub struct PacketReceiver<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    model: ConnectionModel<Bytes>,
+    pool: BufferPool,
+    assoc_id: u16,
+    recv: T::RecvStream,
+    first: Option<PacketHeader>,
+    restricted: bool,
+    reg: StreamReg,
+}
+
+impl<T> PacketReceiver<T>
+where
+    T: Transport,
+{
+    /// Returns the UDP session ID
+    pub fn assoc_id(&self) -> u16 {
+        self.assoc_id
+    }
+
+    /// Receives the next `Packet`, with its payload already read from the stream. Returns `None` if the peer finished the stream.
+    pub async fn recv(&mut self) -> Result<Option<Packet<T>>, Error<T>> {
+        let header = match self.first.take() {
+            Some(header) => header,
+            None => {
+                // the end of the stream is only expected between packets
+                let mut ver = [0; 1];
+                if AsyncReadExt::read(&mut self.recv, &mut ver).await? == 0 {
+                    return Ok(None);
+                }
+
+                match Header::async_unmarshal(&mut (&ver[..]).chain(&mut self.recv)).await {
+                    Ok(Header::Packet(pkt)) => pkt,
+                    Ok(header) => return Err(Error::BadPacketStream(header.type_code())),
+                    Err(err) => return Err(Error::UnmarshalPacketStream(err)),
+                }
+            }
+        };
+
+        if header.assoc_id() != self.assoc_id {
+            return Err(Error::PacketStreamAssocId(self.assoc_id, header.assoc_id()));
+        }
+
+        let pkt_id = header.pkt_id();
+        let model = if self.restricted {
+            self.model
+                .recv_packet(header)
+                .ok_or(Error::InvalidUdpSession(self.assoc_id, pkt_id))?
+        } else {
+            self.model.recv_packet_unrestricted(header)
+        };
+
+        let mut buf = BytesMut::zeroed(model.size() as usize);
+        AsyncReadExt::read_exact(&mut self.recv, &mut buf).await?;
+
+        Ok(Some(Packet::new(
+            model.into(),
+            PacketSource::Stream(buf.freeze()),
+            self.pool.clone(),
+        )))
+    }
+}
+
+/// A received `Extension` command.
+#[derive(Debug)]
+pub struct Extension<
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    header: ExtensionHeader,
+    src: ExtensionSource<T>,
+    reg: StreamReg,
+}
+
+#[derive(Debug)]
+enum ExtensionSource<T>
+where
+    T: Transport,
+{
+    UniStream(T::RecvStream),
+    BiStream(T::SendStream, T::RecvStream),
+    Datagram,
+}
+
+impl<T> Extension<T>
+where
+    T: Transport,
+{
+    fn new(header: ExtensionHeader, src: ExtensionSource<T>) -> Self {
+        Self {
+            header,
+            src,
+            reg: StreamReg::default(),
+        }
+    }
+
+    /// Returns the command type code
+    pub fn type_code(&self) -> u8 {
+        self.header.type_code()
+    }
+
+    /// Returns the payload
+    pub fn payload(&self) -> &[u8] {
+        self.header.payload()
+    }
+
+    /// Whether the command is from a unidirectional stream
+    pub fn is_from_uni_stream(&self) -> bool {
+        matches!(self.src, ExtensionSource::UniStream(_))
+    }
+
+    /// Whether the command is from a bidirectional stream
+    pub fn is_from_bi_stream(&self) -> bool {
+        matches!(self.src, ExtensionSource::BiStream(_, _))
+    }
+
+    /// Whether the command is from a QUIC datagram
+    pub fn is_from_datagram(&self) -> bool {
+        matches!(self.src, ExtensionSource::Datagram)
+    }
+
+    /// Returns the command header and the streams it was received on, for reading / writing extension specific data.
+    pub fn into_parts(
+        self,
+    ) -> (
+        ExtensionHeader,
+        Option<T::SendStream>,
+        Option<T::RecvStream>,
+    ) {
+        match self.src {
+            ExtensionSource::UniStream(recv) => (self.header, None, Some(recv)),
+            ExtensionSource::BiStream(send, recv) => (self.header, Some(send), Some(recv)),
+            ExtensionSource::Datagram => (self.header, None, None),
+        }
     }
 }
 
 /// Type of tasks that can be received.
 #[non_exhaustive]
 #[derive(Debug)]
-pub enum Task {
-    Authenticate(Authenticate),
-    Connect(Connect),
-    Packet(Packet),
+pub enum Task<#[cfg(feature = "quinn")] T = QuinnConnection, #[cfg(not(feature = "quinn"))] T>
+where
+    T: Transport,
+{
+    Authenticate(Authenticate<T>),
+    Connect(Connect<T>),
+    Packet(Packet<T>),
+    PacketStream(PacketStream<T>),
     Dissociate(u16),
     Heartbeat,
+    Capabilities(Capabilities),
+    Padding,
+    Extension(Extension<T>),
+}
+
+impl<T> Task<T>
+where
+    T: Transport,
+{
+    /// Counts the accepted stream as open for as long as the task holds it
+    fn register(&mut self, reg: Register) {
+        match self {
+            Task::Connect(conn) => conn.reg = StreamReg::new(reg),
+            Task::Packet(pkt) => pkt.reg = StreamReg::new(reg),
+            Task::PacketStream(stream) => {
+                stream.send.reg = StreamReg::new(reg.clone());
+                stream.recv.reg = StreamReg::new(reg);
+            }
+            Task::Extension(ext) => ext.reg = StreamReg::new(reg),
+            _ => {}
+        }
+    }
+
+    /// Resets and stops the streams the task came on with `error_code`
+    fn refuse(self, error_code: ErrorCode) {
+        match self {
+            Task::Connect(mut conn) => {
+                let _ = T::reset(&mut conn.send, error_code);
+                let _ = T::stop(&mut conn.recv, error_code);
+            }
+            Task::Packet(Packet {
+                src: PacketSource::Quic(mut recv),
+                ..
+            }) => {
+                let _ = T::stop(&mut recv, error_code);
+            }
+            Task::PacketStream(mut stream) => {
+                let _ = T::reset(&mut stream.send.send, error_code);
+                let _ = T::stop(&mut stream.recv.recv, error_code);
+            }
+            Task::Extension(Extension {
+                src: ExtensionSource::UniStream(mut recv),
+                ..
+            }) => {
+                let _ = T::stop(&mut recv, error_code);
+            }
+            Task::Extension(Extension {
+                src: ExtensionSource::BiStream(mut send, mut recv),
+                ..
+            }) => {
+                let _ = T::reset(&mut send, error_code);
+                let _ = T::stop(&mut recv, error_code);
+            }
+            _ => {}
+        }
+    }
 }
 
 #[derive(Debug)]
-struct KeyingMaterialExporter(QuinnConnection);
+struct KeyingMaterialExporter<T>(T);
 
-impl KeyingMaterialExporterImpl for KeyingMaterialExporter {
+impl<T> KeyingMaterialExporterImpl for KeyingMaterialExporter<T>
+where
+    T: Transport,
+{
     fn export_keying_material(&self, label: &[u8], context: &[u8]) -> [u8; 32] {
         let mut buf = [0; 32];
-        self.0
-            .export_keying_material(&mut buf, label, context)
-            .unwrap();
+        self.0.export_keying_material(&mut buf, label, context);
         buf
     }
 }
 
 /// Errors that can occur when processing a task.
 #[derive(Debug, Error)]
-pub enum Error {
+pub enum Error<#[cfg(feature = "quinn")] T = QuinnConnection, #[cfg(not(feature = "quinn"))] T>
+where
+    T: Transport,
+{
     #[error(transparent)]
     Io(#[from] IoError),
     #[error(transparent)]
-    Connection(#[from] ConnectionError),
+    Connection(T::ConnectionError),
     #[error(transparent)]
-    SendDatagram(#[from] SendDatagramError),
+    SendDatagram(T::SendDatagramError),
     #[error("expecting payload length {0} but got {1}")]
     PayloadLength(usize, usize),
     #[error("packet {1:#06x} on invalid udp session {0:#06x}")]
     InvalidUdpSession(u16, u16),
     #[error(transparent)]
     Assemble(#[from] AssembleError),
+    #[error(transparent)]
+    Fragment(#[from] FragmentError),
+    #[error(transparent)]
+    Address(#[from] AddressError),
     #[error("error unmarshalling uni_stream: {0}")]
-    UnmarshalUniStream(UnmarshalError, RecvStream),
+    UnmarshalUniStream(UnmarshalError, T::RecvStream),
     #[error("error unmarshalling bi_stream: {0}")]
-    UnmarshalBiStream(UnmarshalError, SendStream, RecvStream),
+    UnmarshalBiStream(UnmarshalError, T::SendStream, T::RecvStream),
     #[error("error unmarshalling datagram: {0}")]
     UnmarshalDatagram(UnmarshalError, Bytes),
     #[error("bad command `{0}` from uni_stream")]
-    BadCommandUniStream(&'static str, RecvStream),
+    BadCommandUniStream(&'static str, T::RecvStream),
     #[error("bad command `{0}` from bi_stream")]
-    BadCommandBiStream(&'static str, SendStream, RecvStream),
+    BadCommandBiStream(&'static str, T::SendStream, T::RecvStream),
     #[error("bad command `{0}` from datagram")]
     BadCommandDatagram(&'static str, Bytes),
+    #[error("error unmarshalling connect result: {0}")]
+    UnmarshalConnectResult(UnmarshalError),
+    #[error("bad command {0:#04x} instead of connect result")]
+    BadConnectResult(u8),
+    #[error("error unmarshalling packet stream: {0}")]
+    UnmarshalPacketStream(UnmarshalError),
+    #[error("bad command {0:#04x} from packet stream")]
+    BadPacketStream(u8),
+    #[error("packet on udp session {1:#06x} from the packet stream of udp session {0:#06x}")]
+    PacketStreamAssocId(u16, u16),
+    #[error("task negotiation timed out")]
+    TaskNegotiationTimeout,
+    #[error("duplicated authentication")]
+    DuplicatedAuth,
+    #[error("authentication failed: {0}")]
+    AuthFailed(Uuid),
+    #[error("authentication timed out")]
+    AuthTimeout,
+    #[error("too many tasks waiting for authentication")]
+    TooManyPendingTasks,
+}
+
+impl<T> Error<T>
+where
+    T: Transport,
+{
+    /// Returns `true` if the error is caused by a malformed command from the peer, rather than by the transport, a timeout or a limit
+    pub fn is_bad_command(&self) -> bool {
+        match self {
+            Self::UnmarshalUniStream(UnmarshalError::Io(err), _)
+            | Self::UnmarshalBiStream(UnmarshalError::Io(err), _, _) => {
+                // a stream ending early is a truncated command, other errors come from the transport
+                err.kind() == ErrorKind::UnexpectedEof
+            }
+            Self::Io(_)
+            | Self::Connection(_)
+            | Self::SendDatagram(_)
+            | Self::TaskNegotiationTimeout
+            | Self::DuplicatedAuth
+            | Self::AuthFailed(_)
+            | Self::AuthTimeout
+            | Self::TooManyPendingTasks => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(feature = "quinn")]
+impl From<ConnectionError> for Error {
+    fn from(err: ConnectionError) -> Self {
+        Self::Connection(err)
+    }
+}
+
+#[cfg(feature = "quinn")]
+impl From<SendDatagramError> for Error {
+    fn from(err: SendDatagramError) -> Self {
+        Self::SendDatagram(err)
+    }
 }
@@ -0,0 +1,493 @@
+//! An in-memory [`Transport`] for tests.
+//!
+//! [`pair()`] creates two connected transports living in the same process, with no socket or TLS involved. Streams are reliable in-memory pipes, while datagrams go through an impaired link with configurable loss, reordering, delay and MTU.
+
+use crate::{ErrorCode, Transport};
+use bytes::Bytes;
+use futures_util::{AsyncRead, AsyncWrite};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        Mutex as AsyncMutex,
+    },
+    time,
+};
+
+/// The impairments of the datagram link, applied in both directions
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The maximum datagram size. `None` disables datagrams
+    pub mtu: Option<usize>,
+    /// The probability of a datagram being dropped, in `[0, 1]`
+    pub loss: f64,
+    /// The probability of a datagram being held back and delivered after the next one, in `[0, 1]`
+    pub reorder: f64,
+    /// The delay before a datagram is delivered. A tokio runtime is required if it is not zero
+    pub delay: Duration,
+    /// The seed of the randomness, so impairments are reproducible
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mtu: Some(1200),
+            loss: 0.0,
+            reorder: 0.0,
+            delay: Duration::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+/// Creates a pair of connected transports, returned as `(client, server)`
+pub fn pair(config: Config) -> (Loopback, Loopback) {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let secret = rng.next_u64();
+
+    let (client_uni_tx, server_uni_rx) = mpsc::unbounded_channel();
+    let (server_uni_tx, client_uni_rx) = mpsc::unbounded_channel();
+    let (client_bi_tx, server_bi_rx) = mpsc::unbounded_channel();
+    let (server_bi_tx, client_bi_rx) = mpsc::unbounded_channel();
+    let (client_dg_tx, server_dg_rx) = mpsc::unbounded_channel();
+    let (server_dg_tx, client_dg_rx) = mpsc::unbounded_channel();
+
+    let shared = Arc::new(Shared {
+        senders: Mutex::new(Some([
+            Senders {
+                uni: client_uni_tx,
+                bi: client_bi_tx,
+                dg: client_dg_tx,
+            },
+            Senders {
+                uni: server_uni_tx,
+                bi: server_bi_tx,
+                dg: server_dg_tx,
+            },
+        ])),
+        close_reason: Mutex::new(None),
+        pipes: Mutex::new(Vec::new()),
+        secret,
+    });
+
+    let client = Loopback::new(
+        0,
+        shared.clone(),
+        Receivers {
+            uni: client_uni_rx,
+            bi: client_bi_rx,
+            dg: client_dg_rx,
+        },
+        config.clone(),
+        StdRng::seed_from_u64(rng.next_u64()),
+    );
+    let server = Loopback::new(
+        1,
+        shared,
+        Receivers {
+            uni: server_uni_rx,
+            bi: server_bi_rx,
+            dg: server_dg_rx,
+        },
+        config,
+        StdRng::seed_from_u64(rng.next_u64()),
+    );
+
+    (client, server)
+}
+
+/// One side of an in-memory connection created by [`pair()`]
+#[derive(Clone)]
+pub struct Loopback {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    side: usize,
+    shared: Arc<Shared>,
+    uni: AsyncMutex<UnboundedReceiver<RecvStream>>,
+    bi: AsyncMutex<UnboundedReceiver<(SendStream, RecvStream)>>,
+    dg: AsyncMutex<UnboundedReceiver<Bytes>>,
+    config: Config,
+    link: Mutex<Link>,
+}
+
+struct Shared {
+    senders: Mutex<Option<[Senders; 2]>>,
+    close_reason: Mutex<Option<ErrorCode>>,
+    pipes: Mutex<Vec<Weak<Mutex<Pipe>>>>,
+    secret: u64,
+}
+
+impl Shared {
+    fn close(&self, error_code: ErrorCode) {
+        let mut close_reason = self.close_reason.lock().unwrap();
+        if close_reason.is_some() {
+            return;
+        }
+        *close_reason = Some(error_code);
+        drop(close_reason);
+
+        // dropping the senders wakes up everything waiting on the peer
+        self.senders.lock().unwrap().take();
+
+        for pipe in self.pipes.lock().unwrap().drain(..) {
+            if let Some(pipe) = pipe.upgrade() {
+                let mut pipe = pipe.lock().unwrap();
+                pipe.reset.get_or_insert(error_code);
+                pipe.stopped.get_or_insert(error_code);
+                pipe.wake();
+            }
+        }
+    }
+}
+
+struct Senders {
+    uni: UnboundedSender<RecvStream>,
+    bi: UnboundedSender<(SendStream, RecvStream)>,
+    dg: UnboundedSender<Bytes>,
+}
+
+struct Receivers {
+    uni: UnboundedReceiver<RecvStream>,
+    bi: UnboundedReceiver<(SendStream, RecvStream)>,
+    dg: UnboundedReceiver<Bytes>,
+}
+
+struct Link {
+    rng: StdRng,
+    held: Option<Bytes>,
+}
+
+impl Loopback {
+    fn new(side: usize, shared: Arc<Shared>, rx: Receivers, config: Config, rng: StdRng) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                side,
+                shared,
+                uni: AsyncMutex::new(rx.uni),
+                bi: AsyncMutex::new(rx.bi),
+                dg: AsyncMutex::new(rx.dg),
+                config,
+                link: Mutex::new(Link { rng, held: None }),
+            }),
+        }
+    }
+
+    /// Returns the error code the connection was closed with, if it is closed
+    pub fn close_reason(&self) -> Option<ErrorCode> {
+        *self.inner.shared.close_reason.lock().unwrap()
+    }
+
+    fn lost(&self) -> ConnectionError {
+        ConnectionError::Closed(self.close_reason().unwrap_or(ErrorCode::Unspecified))
+    }
+
+    fn pipe(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        if self.close_reason().is_some() {
+            return Err(self.lost());
+        }
+
+        let pipe = Arc::new(Mutex::new(Pipe::default()));
+        let mut pipes = self.inner.shared.pipes.lock().unwrap();
+        pipes.retain(|pipe| pipe.strong_count() > 0);
+        pipes.push(Arc::downgrade(&pipe));
+
+        Ok((SendStream(pipe.clone()), RecvStream(pipe)))
+    }
+
+    fn with_senders<R>(&self, f: impl FnOnce(&Senders) -> R) -> Option<R> {
+        let senders = self.inner.shared.senders.lock().unwrap();
+        senders.as_ref().map(|senders| f(&senders[self.inner.side]))
+    }
+
+    fn deliver(&self, tx: UnboundedSender<Bytes>, dg: Bytes) {
+        if self.inner.config.delay.is_zero() {
+            let _ = tx.send(dg);
+        } else {
+            let delay = self.inner.config.delay;
+            tokio::spawn(async move {
+                time::sleep(delay).await;
+                let _ = tx.send(dg);
+            });
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // like quinn, the connection is closed once all handles of a side are dropped
+        self.shared.close(ErrorCode::Unspecified);
+    }
+}
+
+impl Transport for Loopback {
+    type SendStream = SendStream;
+    type RecvStream = RecvStream;
+    type ConnectionError = ConnectionError;
+    type SendDatagramError = SendDatagramError;
+    type ClosedStream = ClosedStream;
+
+    async fn open_uni(&self) -> Result<SendStream, ConnectionError> {
+        let (send, recv) = self.pipe()?;
+        match self.with_senders(|tx| tx.uni.send(recv).is_ok()) {
+            Some(true) => Ok(send),
+            _ => Err(self.lost()),
+        }
+    }
+
+    async fn open_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        let (local_send, peer_recv) = self.pipe()?;
+        let (peer_send, local_recv) = self.pipe()?;
+        match self.with_senders(|tx| tx.bi.send((peer_send, peer_recv)).is_ok()) {
+            Some(true) => Ok((local_send, local_recv)),
+            _ => Err(self.lost()),
+        }
+    }
+
+    async fn accept_uni(&self) -> Result<RecvStream, ConnectionError> {
+        let recv = self.inner.uni.lock().await.recv().await;
+        recv.ok_or_else(|| self.lost())
+    }
+
+    async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        let bi = self.inner.bi.lock().await.recv().await;
+        bi.ok_or_else(|| self.lost())
+    }
+
+    fn send_datagram(&self, data: Bytes) -> Result<(), SendDatagramError> {
+        let max = self.max_datagram_size()?;
+        if data.len() > max {
+            return Err(SendDatagramError::TooLarge);
+        }
+
+        let Some(tx) = self.with_senders(|tx| tx.dg.clone()) else {
+            return Err(SendDatagramError::ConnectionLost(self.lost()));
+        };
+
+        let mut link = self.inner.link.lock().unwrap();
+
+        if link.rng.gen_bool(self.inner.config.loss) {
+            return Ok(());
+        }
+
+        if link.rng.gen_bool(self.inner.config.reorder) {
+            if let Some(prev) = link.held.replace(data) {
+                self.deliver(tx, prev);
+            }
+            return Ok(());
+        }
+
+        self.deliver(tx.clone(), data);
+        if let Some(prev) = link.held.take() {
+            self.deliver(tx, prev);
+        }
+
+        Ok(())
+    }
+
+    async fn read_datagram(&self) -> Result<Bytes, ConnectionError> {
+        let dg = self.inner.dg.lock().await.recv().await;
+        dg.ok_or_else(|| self.lost())
+    }
+
+    fn max_datagram_size(&self) -> Result<usize, SendDatagramError> {
+        if self.close_reason().is_some() {
+            return Err(SendDatagramError::ConnectionLost(self.lost()));
+        }
+        self.inner.config.mtu.ok_or(SendDatagramError::Disabled)
+    }
+
+    fn export_keying_material(&self, output: &mut [u8], label: &[u8], context: &[u8]) {
+        // not a real key derivation, only both sides agree on it
+        let mut hasher = DefaultHasher::new();
+        (self.inner.shared.secret, label, context).hash(&mut hasher);
+        StdRng::seed_from_u64(hasher.finish()).fill_bytes(output);
+    }
+
+    fn close(&self, error_code: ErrorCode, _reason: &[u8]) {
+        self.inner.shared.close(error_code);
+    }
+
+    fn reset(send: &mut SendStream, error_code: ErrorCode) -> Result<(), ClosedStream> {
+        let mut pipe = send.0.lock().unwrap();
+        if pipe.finished || pipe.reset.is_some() {
+            return Err(ClosedStream);
+        }
+        pipe.reset = Some(error_code);
+        pipe.wake();
+        Ok(())
+    }
+
+    fn stop(recv: &mut RecvStream, error_code: ErrorCode) -> Result<(), ClosedStream> {
+        let mut pipe = recv.0.lock().unwrap();
+        if pipe.stopped.is_some() {
+            return Err(ClosedStream);
+        }
+        pipe.stopped = Some(error_code);
+        pipe.buf.clear();
+        Ok(())
+    }
+}
+
+impl Debug for Loopback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Loopback")
+            .field("side", &["client", "server"][self.inner.side])
+            .field("config", &self.inner.config)
+            .field("close_reason", &self.close_reason())
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct Pipe {
+    buf: VecDeque<u8>,
+    finished: bool,
+    reset: Option<ErrorCode>,
+    stopped: Option<ErrorCode>,
+    reader: Option<Waker>,
+}
+
+impl Pipe {
+    fn wake(&mut self) {
+        if let Some(waker) = self.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The sending side of an in-memory stream. It is finished when dropped
+pub struct SendStream(Arc<Mutex<Pipe>>);
+
+impl AsyncWrite for SendStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        let mut pipe = self.0.lock().unwrap();
+
+        if let Some(code) = pipe.stopped {
+            return Poll::Ready(Err(IoError::new(
+                ErrorKind::ConnectionReset,
+                format!("stopped by peer: {code}"),
+            )));
+        }
+        if pipe.finished || pipe.reset.is_some() {
+            return Poll::Ready(Err(IoError::from(ErrorKind::BrokenPipe)));
+        }
+
+        pipe.buf.extend(buf);
+        pipe.wake();
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let mut pipe = self.0.lock().unwrap();
+        pipe.finished = true;
+        pipe.wake();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for SendStream {
+    fn drop(&mut self) {
+        let mut pipe = self.0.lock().unwrap();
+        pipe.finished = true;
+        pipe.wake();
+    }
+}
+
+impl Debug for SendStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("SendStream").finish_non_exhaustive()
+    }
+}
+
+/// The receiving side of an in-memory stream. It is stopped when dropped
+pub struct RecvStream(Arc<Mutex<Pipe>>);
+
+impl AsyncRead for RecvStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<IoResult<usize>> {
+        let mut pipe = self.0.lock().unwrap();
+
+        if !pipe.buf.is_empty() {
+            let len = buf.len().min(pipe.buf.len());
+            for (dst, src) in buf.iter_mut().zip(pipe.buf.drain(..len)) {
+                *dst = src;
+            }
+            return Poll::Ready(Ok(len));
+        }
+
+        if let Some(code) = pipe.reset {
+            return Poll::Ready(Err(IoError::new(
+                ErrorKind::ConnectionReset,
+                format!("reset by peer: {code}"),
+            )));
+        }
+        if pipe.finished {
+            return Poll::Ready(Ok(0));
+        }
+
+        pipe.reader = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for RecvStream {
+    fn drop(&mut self) {
+        let mut pipe = self.0.lock().unwrap();
+        pipe.stopped.get_or_insert(ErrorCode::Unspecified);
+        pipe.buf.clear();
+    }
+}
+
+impl Debug for RecvStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("RecvStream").finish_non_exhaustive()
+    }
+}
+
+/// Errors of a lost in-memory connection
+#[derive(Clone, Debug, Error)]
+pub enum ConnectionError {
+    #[error("connection closed: {0}")]
+    Closed(ErrorCode),
+}
+
+/// Errors of sending an in-memory datagram
+#[derive(Clone, Debug, Error)]
+pub enum SendDatagramError {
+    #[error("datagrams disabled")]
+    Disabled,
+    #[error("datagram too large")]
+    TooLarge,
+    #[error(transparent)]
+    ConnectionLost(ConnectionError),
+}
+
+/// The error of resetting / stopping an in-memory stream that is already closed
+#[derive(Clone, Copy, Debug, Error)]
+#[error("closed stream")]
+pub struct ClosedStream;
@@ -0,0 +1,453 @@
+use crate::{side, Authenticate, Connection, Error, ErrorCode, PacketSource, Task, Transport};
+use bytes::Bytes;
+use futures_util::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, Stream},
+    FutureExt, StreamExt,
+};
+#[cfg(feature = "quinn")]
+use quinn::Connection as QuinnConnection;
+use register_count::{Counter, Register};
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::time::{self, Sleep};
+use uuid::Uuid;
+
+type Accepting<T> = BoxFuture<'static, Result<Task<T>, Error<T>>>;
+type Gated<T> = BoxFuture<'static, Option<Result<Task<T>, Error<T>>>>;
+type BiStream<T> = (<T as Transport>::SendStream, <T as Transport>::RecvStream);
+type AcceptBi<T> = BoxFuture<'static, Result<BiStream<T>, <T as Transport>::ConnectionError>>;
+type ParseBi<Side, T> = fn(
+    Connection<Side, T>,
+    <T as Transport>::SendStream,
+    <T as Transport>::RecvStream,
+) -> Accepting<T>;
+
+/// The stream of tasks received from the peer, returned by `Connection::tasks()`.
+///
+/// It accepts unidirectional streams, bidirectional streams and datagrams from the transport, and parses them concurrently. Tasks are yielded in the order they are parsed. Errors parsing a single task are yielded without ending the stream, while a transport error ends it.
+#[must_use = "streams do nothing unless polled"]
+pub struct Tasks<
+    Side,
+    #[cfg(feature = "quinn")] T = QuinnConnection,
+    #[cfg(not(feature = "quinn"))] T,
+> where
+    T: Transport,
+{
+    conn: Connection<Side, T>,
+    accept_uni: BoxFuture<'static, Result<T::RecvStream, T::ConnectionError>>,
+    accept_bi: AcceptBi<T>,
+    read_datagram: BoxFuture<'static, Result<Bytes, T::ConnectionError>>,
+    parse_uni: fn(Connection<Side, T>, T::RecvStream) -> Accepting<T>,
+    parse_bi: ParseBi<Side, T>,
+    parse_datagram: fn(Connection<Side, T>, Bytes) -> Accepting<T>,
+    parsing: FuturesUnordered<Gated<T>>,
+    uni_streams: StreamLimit,
+    bi_streams: StreamLimit,
+    pending: Counter,
+    auth_timeout: Option<Pin<Box<Sleep>>>,
+    is_closed: bool,
+}
+
+impl<Side, T> Tasks<Side, T>
+where
+    Side: Clone + Send + Sync + 'static,
+    T: Transport,
+{
+    fn new(
+        conn: Connection<Side, T>,
+        parse_uni: fn(Connection<Side, T>, T::RecvStream) -> Accepting<T>,
+        parse_bi: ParseBi<Side, T>,
+        parse_datagram: fn(Connection<Side, T>, Bytes) -> Accepting<T>,
+    ) -> Self {
+        let max = conn.max_concurrent_streams;
+        let auth_timeout = conn
+            .auth
+            .as_ref()
+            .and(conn.auth_timeout)
+            .map(|timeout| Box::pin(time::sleep(timeout)));
+
+        Self {
+            accept_uni: accept_uni(conn.conn.clone()),
+            accept_bi: accept_bi(conn.conn.clone()),
+            read_datagram: read_datagram(conn.conn.clone()),
+            conn,
+            parse_uni,
+            parse_bi,
+            parse_datagram,
+            parsing: FuturesUnordered::new(),
+            uni_streams: StreamLimit::new(max),
+            bi_streams: StreamLimit::new(max),
+            pending: Counter::new(),
+            auth_timeout,
+            is_closed: false,
+        }
+    }
+
+    fn push(&mut self, parse: Accepting<T>, reg: Option<Register>) {
+        let conn = self.conn.clone();
+        let pending = self.pending.clone();
+
+        self.parsing.push(
+            async move {
+                let task = match conn.negotiate(parse).await {
+                    Ok(task) => task,
+                    Err(err) => return Some(Err(err)),
+                };
+                let task = match conn.gate(task, &pending).await? {
+                    Ok(task) => task,
+                    Err(err) => return Some(Err(err)),
+                };
+                let mut task = conn.refuse_if_draining(task)?;
+                conn.commit(&mut task);
+                if let Some(reg) = reg {
+                    task.register(reg);
+                }
+                Some(Ok(task))
+            }
+            .boxed(),
+        );
+    }
+}
+
+impl<Side, T> Stream for Tasks<Side, T>
+where
+    Side: Clone + Send + Sync + 'static,
+    T: Transport,
+{
+    type Item = Result<Task<T>, Error<T>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.is_closed {
+            return Poll::Ready(None);
+        }
+
+        if let Some(timeout) = &mut this.auth_timeout {
+            if timeout.poll_unpin(cx).is_ready() {
+                this.auth_timeout = None;
+
+                if this.conn.fail_authentication() {
+                    return Poll::Ready(Some(Err(Error::AuthTimeout)));
+                }
+            }
+        }
+
+        loop {
+            let mut accepted = false;
+
+            if let Poll::Ready(res) = this.accept_uni.poll_unpin(cx) {
+                let recv = match res {
+                    Ok(recv) => recv,
+                    Err(err) => return this.close(err),
+                };
+
+                this.accept_uni = accept_uni(this.conn.conn.clone());
+                accepted = true;
+
+                let reg = this.uni_streams.reg(|max| {
+                    this.conn
+                        .conn
+                        .set_max_concurrent_uni_streams(u64::from(max))
+                });
+                let parse = (this.parse_uni)(this.conn.clone(), recv);
+                this.push(parse, Some(reg));
+            }
+
+            if let Poll::Ready(res) = this.accept_bi.poll_unpin(cx) {
+                let (mut send, mut recv) = match res {
+                    Ok(bi) => bi,
+                    Err(err) => return this.close(err),
+                };
+
+                this.accept_bi = accept_bi(this.conn.conn.clone());
+                accepted = true;
+
+                if this.conn.is_draining() {
+                    let _ = T::reset(&mut send, ErrorCode::ServerShutdown);
+                    let _ = T::stop(&mut recv, ErrorCode::ServerShutdown);
+                    continue;
+                }
+
+                let reg = this
+                    .bi_streams
+                    .reg(|max| this.conn.conn.set_max_concurrent_bi_streams(u64::from(max)));
+                let parse = (this.parse_bi)(this.conn.clone(), send, recv);
+                this.push(parse, Some(reg));
+            }
+
+            if let Poll::Ready(res) = this.read_datagram.poll_unpin(cx) {
+                let dg = match res {
+                    Ok(dg) => dg,
+                    Err(err) => return this.close(err),
+                };
+
+                this.read_datagram = read_datagram(this.conn.conn.clone());
+                accepted = true;
+
+                let parse = (this.parse_datagram)(this.conn.clone(), dg);
+                this.push(parse, None);
+            }
+
+            if !accepted {
+                break;
+            }
+        }
+
+        loop {
+            match this.parsing.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some(res))) => return Poll::Ready(Some(res)),
+                // cancelled by a failed authentication
+                Poll::Ready(Some(None)) => {}
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<Side, T> Tasks<Side, T>
+where
+    T: Transport,
+{
+    /// Tasks still being parsed are dropped, as they can not be completed on a lost connection
+    fn close(&mut self, err: T::ConnectionError) -> Poll<Option<Result<Task<T>, Error<T>>>> {
+        self.is_closed = true;
+        self.parsing.clear();
+        Poll::Ready(Some(Err(Error::Connection(err))))
+    }
+}
+
+impl<Side, T> Unpin for Tasks<Side, T> where T: Transport {}
+
+impl<Side, T> Debug for Tasks<Side, T>
+where
+    T: Transport,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Tasks")
+            .field("conn", &self.conn)
+            .field("parsing", &self.parsing.len())
+            .field("uni_streams", &self.uni_streams)
+            .field("bi_streams", &self.bi_streams)
+            .field("pending", &self.pending.count())
+            .finish()
+    }
+}
+
+fn accept_uni<T>(conn: T) -> BoxFuture<'static, Result<T::RecvStream, T::ConnectionError>>
+where
+    T: Transport,
+{
+    async move { conn.accept_uni().await }.boxed()
+}
+
+fn accept_bi<T>(conn: T) -> AcceptBi<T>
+where
+    T: Transport,
+{
+    async move { conn.accept_bi().await }.boxed()
+}
+
+fn read_datagram<T>(conn: T) -> BoxFuture<'static, Result<Bytes, T::ConnectionError>>
+where
+    T: Transport,
+{
+    async move { conn.read_datagram().await }.boxed()
+}
+
+/// The number of streams opened by the peer, and the limit of it. The limit is doubled whenever it is reached
+struct StreamLimit {
+    cnt: Counter,
+    max: u32,
+}
+
+impl StreamLimit {
+    fn new(max: u32) -> Self {
+        Self {
+            cnt: Counter::new(),
+            max,
+        }
+    }
+
+    fn reg(&mut self, set_max: impl FnOnce(u32)) -> Register {
+        let reg = self.cnt.reg();
+
+        if self.cnt.count() as u32 >= self.max {
+            self.max = self.max.saturating_mul(2);
+            set_max(self.max);
+        }
+
+        reg
+    }
+}
+
+impl Debug for StreamLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("StreamLimit")
+            .field("cnt", &self.cnt.count())
+            .field("max", &self.max)
+            .finish()
+    }
+}
+
+/// Keeps a stream accepted by [`Tasks`] counted until the task holding it is dropped
+#[derive(Clone, Default)]
+pub(crate) struct StreamReg(Option<Register>);
+
+impl StreamReg {
+    pub(crate) fn new(reg: Register) -> Self {
+        Self(Some(reg))
+    }
+}
+
+impl Debug for StreamReg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_tuple("StreamReg").field(&self.0.is_some()).finish()
+    }
+}
+
+/// Validates `Authenticate` commands on the server side, see `Connection::with_authenticator()`
+pub(crate) type Authenticator<T> = Arc<dyn Fn(&Authenticate<T>) -> bool + Send + Sync>;
+
+/// The authentication state of a server side connection
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum AuthState {
+    Pending,
+    Authenticated(Uuid),
+    Failed,
+}
+
+impl<Side, T> Connection<Side, T>
+where
+    T: Transport,
+{
+    async fn negotiate(
+        &self,
+        parse: impl Future<Output = Result<Task<T>, Error<T>>>,
+    ) -> Result<Task<T>, Error<T>> {
+        match self.task_negotiation_timeout {
+            Some(timeout) => time::timeout(timeout, parse)
+                .await
+                .map_err(|_| Error::TaskNegotiationTimeout)?,
+            None => parse.await,
+        }
+    }
+
+    /// Holds tasks other than `Authenticate` back until the connection is authenticated, or cancels them with `None` if the authentication fails. Only the server side is gated
+    async fn gate(&self, task: Task<T>, pending: &Counter) -> Option<Result<Task<T>, Error<T>>> {
+        let Some(state) = &self.auth else {
+            return Some(Ok(task));
+        };
+
+        if let Task::Authenticate(auth) = &task {
+            return Some(self.validate(auth).map(|()| task));
+        }
+
+        if *state.borrow() == AuthState::Pending {
+            if pending.count() >= self.max_pending_tasks {
+                task.refuse(ErrorCode::TooManyTasks);
+                return Some(Err(Error::TooManyPendingTasks));
+            }
+
+            let _reg = pending.reg();
+            let _ = state
+                .subscribe()
+                .wait_for(|state| *state != AuthState::Pending)
+                .await;
+        }
+
+        let is_authenticated = matches!(*state.borrow(), AuthState::Authenticated(_));
+        is_authenticated.then_some(Ok(task))
+    }
+
+    /// Validates an `Authenticate` command with the authenticator, if there is one
+    fn validate(&self, auth: &Authenticate<T>) -> Result<(), Error<T>> {
+        let (Some(state), Some(authenticator)) = (&self.auth, &self.authenticator) else {
+            return Ok(());
+        };
+
+        match *state.borrow() {
+            AuthState::Pending => {}
+            AuthState::Authenticated(_) => return Err(Error::DuplicatedAuth),
+            AuthState::Failed => return Err(Error::AuthFailed(auth.uuid())),
+        }
+
+        if authenticator(auth) {
+            state.send_replace(AuthState::Authenticated(auth.uuid()));
+            Ok(())
+        } else {
+            state.send_replace(AuthState::Failed);
+            Err(Error::AuthFailed(auth.uuid()))
+        }
+    }
+
+    /// While draining, refuses packets of UDP sessions that do not exist yet with `None`, stopping the stream they came on. Packets of existing sessions, `Dissociate` and `Heartbeat` are still accepted
+    fn refuse_if_draining(&self, task: Task<T>) -> Option<Task<T>> {
+        if !self.is_draining() {
+            return Some(task);
+        }
+
+        match task {
+            Task::Packet(pkt) if !self.model.has_udp_session(pkt.assoc_id()) => {
+                if let PacketSource::Quic(mut recv) = pkt.src {
+                    let _ = T::stop(&mut recv, ErrorCode::ServerShutdown);
+                }
+                None
+            }
+            task => Some(task),
+        }
+    }
+
+    /// Fails the authentication if it is still pending, cancelling the held tasks. Returns `true` if it was pending
+    fn fail_authentication(&self) -> bool {
+        self.auth.as_ref().is_some_and(|state| {
+            state.send_if_modified(|state| {
+                let is_pending = *state == AuthState::Pending;
+                if is_pending {
+                    *state = AuthState::Failed;
+                }
+                is_pending
+            })
+        })
+    }
+}
+
+impl<T> Connection<side::Client, T>
+where
+    T: Transport,
+{
+    /// Returns the stream of tasks received from the server. See [`Tasks`].
+    ///
+    /// Only one stream should be polled per connection, as the streams opened by the peer are counted by it.
+    pub fn tasks(&self) -> Tasks<side::Client, T> {
+        Tasks::new(
+            self.clone(),
+            |conn, recv| async move { conn.accept_uni_stream(recv).await }.boxed(),
+            |conn, send, recv| async move { conn.accept_bi_stream(send, recv).await }.boxed(),
+            |conn, dg| async move { conn.accept_datagram(dg) }.boxed(),
+        )
+    }
+}
+
+impl<T> Connection<side::Server, T>
+where
+    T: Transport,
+{
+    /// Returns the stream of tasks received from the client. See [`Tasks`].
+    ///
+    /// Tasks other than `Authenticate` are held back until the connection is authenticated by the [authenticator](Self::with_authenticator) or marked with [`set_authenticated()`](Self::set_authenticated), and cancelled if the authentication fails or times out. Only one stream should be polled per connection, as the streams opened by the peer are counted by it.
+    pub fn tasks(&self) -> Tasks<side::Server, T> {
+        Tasks::new(
+            self.clone(),
+            |conn, recv| async move { conn.parse_uni_stream(recv).await }.boxed(),
+            |conn, send, recv| async move { conn.parse_bi_stream(send, recv).await }.boxed(),
+            |conn, dg| async move { conn.accept_datagram(dg) }.boxed(),
+        )
+    }
+}
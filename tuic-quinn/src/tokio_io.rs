@@ -0,0 +1,105 @@
+//! tokio `AsyncRead` and `AsyncWrite` implementations, bridged from the `futures` ones.
+
+use crate::{Connect, ConnectReadHalf, ConnectWriteHalf, Transport};
+use futures_util::{AsyncRead as FuturesAsyncRead, AsyncWrite as FuturesAsyncWrite};
+use std::{
+    io::Error as IoError,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Wraps a [`Connect`] or one of its halves, implementing the tokio `AsyncRead` and `AsyncWrite` in place of the `futures` ones.
+///
+/// The wrapped relay only implements the `futures` traits, so that the extension methods of the two do not clash.
+#[derive(Debug)]
+pub struct Compat<T>(T);
+
+impl<T> Compat<T> {
+    /// Wraps a relay
+    pub fn new(inner: T) -> Self {
+        Self(inner)
+    }
+
+    /// Returns a reference to the wrapped relay
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the wrapped relay, e.g. to reset it
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Unwraps the relay
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Connect<T>
+where
+    T: Transport,
+{
+    /// Wraps the relay in a [`Compat`], which implements the tokio `AsyncRead` and `AsyncWrite`
+    pub fn compat(self) -> Compat<Self> {
+        Compat::new(self)
+    }
+}
+
+impl<T> ConnectReadHalf<T>
+where
+    T: Transport,
+{
+    /// Wraps the read half in a [`Compat`], which implements the tokio `AsyncRead`
+    pub fn compat(self) -> Compat<Self> {
+        Compat::new(self)
+    }
+}
+
+impl<T> ConnectWriteHalf<T>
+where
+    T: Transport,
+{
+    /// Wraps the write half in a [`Compat`], which implements the tokio `AsyncWrite`
+    pub fn compat(self) -> Compat<Self> {
+        Compat::new(self)
+    }
+}
+
+impl<T> AsyncRead for Compat<T>
+where
+    T: FuturesAsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<(), IoError>> {
+        let reader = Pin::new(&mut self.get_mut().0);
+        let n = ready!(reader.poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncWrite for Compat<T>
+where
+    T: FuturesAsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Pin::new(&mut self.get_mut().0).poll_close(cx)
+    }
+}
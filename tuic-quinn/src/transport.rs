@@ -0,0 +1,143 @@
+use crate::ErrorCode;
+use bytes::Bytes;
+use futures_util::{AsyncRead, AsyncWrite};
+#[cfg(feature = "quinn")]
+use quinn::{
+    ClosedStream, Connection as QuinnConnection, ConnectionError, RecvStream, SendDatagramError,
+    SendStream, VarInt,
+};
+use std::{error::Error as StdError, fmt::Debug, future::Future};
+
+/// The connection TUIC runs on.
+///
+/// TUIC only needs reliable unidirectional / bidirectional streams, unreliable datagrams and keying material export from the underlying connection. With the `quinn` feature (enabled by default) it is implemented for `quinn::Connection`, which is then the default transport of all types in this crate. Other QUIC implementations, test doubles or non-QUIC transports can be used by implementing this trait.
+pub trait Transport: Clone + Debug + Send + Sync + 'static {
+    /// The sending side of a stream
+    type SendStream: AsyncWrite + Debug + Send + Unpin + 'static;
+    /// The receiving side of a stream
+    type RecvStream: AsyncRead + Debug + Send + Unpin + 'static;
+    /// The error of opening / accepting streams and receiving datagrams, usually meaning the connection is lost
+    type ConnectionError: StdError + Send + Sync + 'static;
+    /// The error of sending a datagram
+    type SendDatagramError: StdError + Send + Sync + 'static;
+    /// The error of resetting / stopping a stream that is already closed
+    type ClosedStream: StdError + Send + Sync + 'static;
+
+    /// Opens a unidirectional stream
+    fn open_uni(
+        &self,
+    ) -> impl Future<Output = Result<Self::SendStream, Self::ConnectionError>> + Send;
+
+    /// Opens a bidirectional stream
+    fn open_bi(
+        &self,
+    ) -> impl Future<Output = Result<(Self::SendStream, Self::RecvStream), Self::ConnectionError>> + Send;
+
+    /// Accepts a unidirectional stream opened by the peer
+    fn accept_uni(
+        &self,
+    ) -> impl Future<Output = Result<Self::RecvStream, Self::ConnectionError>> + Send;
+
+    /// Accepts a bidirectional stream opened by the peer
+    fn accept_bi(
+        &self,
+    ) -> impl Future<Output = Result<(Self::SendStream, Self::RecvStream), Self::ConnectionError>> + Send;
+
+    /// Sends an unreliable datagram
+    fn send_datagram(&self, data: Bytes) -> Result<(), Self::SendDatagramError>;
+
+    /// Receives an unreliable datagram
+    fn read_datagram(&self) -> impl Future<Output = Result<Bytes, Self::ConnectionError>> + Send;
+
+    /// Returns the maximum size of a datagram that can be sent, or the error sending one would fail with if datagrams are unavailable
+    fn max_datagram_size(&self) -> Result<usize, Self::SendDatagramError>;
+
+    /// Fills `output` with keying material derived from the connection secret. TUIC can not authenticate without it, so implementations may panic if it is unavailable
+    fn export_keying_material(&self, output: &mut [u8], label: &[u8], context: &[u8]);
+
+    /// Closes the connection immediately
+    fn close(&self, error_code: ErrorCode, reason: &[u8]);
+
+    /// Closes the sending side of a stream immediately, abandoning unsent data
+    fn reset(send: &mut Self::SendStream, error_code: ErrorCode) -> Result<(), Self::ClosedStream>;
+
+    /// Asks the peer to stop sending on the receiving side of a stream
+    fn stop(recv: &mut Self::RecvStream, error_code: ErrorCode) -> Result<(), Self::ClosedStream>;
+
+    /// Sets the number of unidirectional streams the peer may have open at the same time. Transports without such a limit can ignore it
+    fn set_max_concurrent_uni_streams(&self, count: u64) {
+        let _ = count;
+    }
+
+    /// Sets the number of bidirectional streams the peer may have open at the same time. Transports without such a limit can ignore it
+    fn set_max_concurrent_bi_streams(&self, count: u64) {
+        let _ = count;
+    }
+}
+
+#[cfg(feature = "quinn")]
+impl Transport for QuinnConnection {
+    type SendStream = SendStream;
+    type RecvStream = RecvStream;
+    type ConnectionError = ConnectionError;
+    type SendDatagramError = SendDatagramError;
+    type ClosedStream = ClosedStream;
+
+    async fn open_uni(&self) -> Result<SendStream, ConnectionError> {
+        QuinnConnection::open_uni(self).await
+    }
+
+    async fn open_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        QuinnConnection::open_bi(self).await
+    }
+
+    async fn accept_uni(&self) -> Result<RecvStream, ConnectionError> {
+        QuinnConnection::accept_uni(self).await
+    }
+
+    async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        QuinnConnection::accept_bi(self).await
+    }
+
+    fn send_datagram(&self, data: Bytes) -> Result<(), SendDatagramError> {
+        QuinnConnection::send_datagram(self, data)
+    }
+
+    async fn read_datagram(&self) -> Result<Bytes, ConnectionError> {
+        QuinnConnection::read_datagram(self).await
+    }
+
+    fn max_datagram_size(&self) -> Result<usize, SendDatagramError> {
+        QuinnConnection::max_datagram_size(self).ok_or(SendDatagramError::Disabled)
+    }
+
+    fn export_keying_material(&self, output: &mut [u8], label: &[u8], context: &[u8]) {
+        QuinnConnection::export_keying_material(self, output, label, context).unwrap();
+    }
+
+    fn close(&self, error_code: ErrorCode, reason: &[u8]) {
+        QuinnConnection::close(self, VarInt::from(error_code), reason);
+    }
+
+    fn reset(send: &mut SendStream, error_code: ErrorCode) -> Result<(), ClosedStream> {
+        send.reset(VarInt::from(error_code))
+    }
+
+    fn stop(recv: &mut RecvStream, error_code: ErrorCode) -> Result<(), ClosedStream> {
+        recv.stop(VarInt::from(error_code))
+    }
+
+    fn set_max_concurrent_uni_streams(&self, count: u64) {
+        QuinnConnection::set_max_concurrent_uni_streams(
+            self,
+            VarInt::from_u64(count).unwrap_or(VarInt::MAX),
+        );
+    }
+
+    fn set_max_concurrent_bi_streams(&self, count: u64) {
+        QuinnConnection::set_max_concurrent_bi_streams(
+            self,
+            VarInt::from_u64(count).unwrap_or(VarInt::MAX),
+        );
+    }
+}
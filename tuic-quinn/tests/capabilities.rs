@@ -0,0 +1,57 @@
+mod common;
+
+use common::Pair;
+use tuic::{Authenticate, Capabilities, Header};
+use tuic_quinn::{side, Connection, Task};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn exchange() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone())
+        .with_capabilities(Capabilities::UDP_RELAY_QUIC);
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+
+    let recv = pair.server.accept_uni().await.unwrap();
+    let Ok(Task::Authenticate(auth)) = server.accept_uni_stream(recv).await else {
+        panic!("expecting an authentication");
+    };
+    assert!(auth.validate(b"password"));
+    assert_eq!(
+        server.peer_capabilities(),
+        Some(Capabilities::UDP_RELAY_QUIC)
+    );
+
+    assert_eq!(client.peer_capabilities(), None);
+    server.advertise_capabilities().await.unwrap();
+
+    let recv = pair.client.accept_uni().await.unwrap();
+    let Ok(Task::Capabilities(caps)) = client.accept_uni_stream(recv).await else {
+        panic!("expecting capabilities");
+    };
+    assert_eq!(Some(caps), client.peer_capabilities());
+    assert!(caps.contains(Capabilities::UDP_RELAY_NATIVE | Capabilities::UDP_RELAY_QUIC));
+}
+
+#[tokio::test]
+async fn authenticate_without_capabilities() {
+    let pair = Pair::new().await;
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    // an older client finishes the stream right after `Authenticate`
+    let mut send = pair.client.open_uni().await.unwrap();
+    let header = Header::Authenticate(Authenticate::new(Uuid::nil(), [0; 32]));
+    let mut buf = Vec::new();
+    header.write(&mut buf);
+    send.write_all(&buf).await.unwrap();
+    send.finish().unwrap();
+
+    let recv = pair.server.accept_uni().await.unwrap();
+    assert!(matches!(
+        server.accept_uni_stream(recv).await,
+        Ok(Task::Authenticate(_)),
+    ));
+    assert_eq!(server.peer_capabilities(), None);
+}
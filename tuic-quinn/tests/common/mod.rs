@@ -0,0 +1,61 @@
+use quinn::{
+    crypto::rustls::{QuicClientConfig, QuicServerConfig},
+    ClientConfig, Connection, Endpoint, ServerConfig,
+};
+use rustls::{
+    pki_types::{CertificateDer, PrivatePkcs8KeyDer},
+    version, ClientConfig as RustlsClientConfig, RootCertStore, ServerConfig as RustlsServerConfig,
+};
+use std::{net::Ipv4Addr, sync::Arc};
+
+/// A pair of connected QUIC connections over the loopback interface
+pub struct Pair {
+    pub client: Connection,
+    pub server: Connection,
+    _endpoints: (Endpoint, Endpoint),
+}
+
+impl Pair {
+    pub async fn new() -> Self {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_der = CertificateDer::from(cert.cert);
+        let key_der = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let server_crypto = RustlsServerConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&version::TLS13])
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert_der.clone()], key_der.into())
+            .unwrap();
+        let server_config =
+            ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(server_crypto).unwrap()));
+        let server_ep = Endpoint::server(server_config, (Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert_der).unwrap();
+
+        let client_crypto = RustlsClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&version::TLS13])
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let mut client_ep = Endpoint::client((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        client_ep.set_default_client_config(ClientConfig::new(Arc::new(
+            QuicClientConfig::try_from(client_crypto).unwrap(),
+        )));
+
+        let connecting = client_ep
+            .connect(server_ep.local_addr().unwrap(), "localhost")
+            .unwrap();
+        let (client, server) = tokio::join!(connecting, async {
+            server_ep.accept().await.unwrap().await
+        });
+
+        Self {
+            client: client.unwrap(),
+            server: server.unwrap(),
+            _endpoints: (client_ep, server_ep),
+        }
+    }
+}
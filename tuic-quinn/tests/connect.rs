@@ -0,0 +1,142 @@
+mod common;
+
+use common::Pair;
+use futures_util::{AsyncReadExt, AsyncWriteExt};
+use std::net::{Ipv4Addr, SocketAddr};
+use tuic::{Address, ConnectStatus};
+use tuic_quinn::{side, Connect, Connection, ErrorCode, Task};
+use uuid::Uuid;
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from((Ipv4Addr::LOCALHOST, 80)))
+}
+
+/// Exchanges capabilities between both sides
+async fn negotiate(
+    pair: &Pair,
+    client: &Connection<side::Client>,
+    server: &Connection<side::Server>,
+) {
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let recv = pair.server.accept_uni().await.unwrap();
+    server.accept_uni_stream(recv).await.unwrap();
+
+    server.advertise_capabilities().await.unwrap();
+    let recv = pair.client.accept_uni().await.unwrap();
+    client.accept_uni_stream(recv).await.unwrap();
+}
+
+#[tokio::test]
+async fn result_requested() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+    negotiate(&pair, &client, &server).await;
+
+    let mut relay = client.connect(addr()).await.unwrap();
+
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::Connect(mut conn)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+    assert_eq!(conn.addr(), &addr());
+
+    conn.respond(ConnectStatus::Refused).await.unwrap();
+    conn.close().await.unwrap();
+
+    assert_eq!(relay.result().await.unwrap(), Some(ConnectStatus::Refused));
+    assert_eq!(relay.result().await.unwrap(), None);
+
+    let mut buf = Vec::new();
+    relay.read_to_end(&mut buf).await.unwrap();
+    assert!(buf.is_empty());
+}
+
+#[tokio::test]
+async fn result_not_requested() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    // the server's capabilities are not known yet
+    let mut relay = client.connect(addr()).await.unwrap();
+
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::Connect(mut conn)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+
+    conn.respond(ConnectStatus::Connected).await.unwrap();
+    conn.write_all(b"data").await.unwrap();
+    conn.close().await.unwrap();
+
+    assert_eq!(relay.result().await.unwrap(), None);
+
+    let mut buf = Vec::new();
+    relay.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"data");
+}
+
+/// Opens a relay that requests a `ConnectResult`, returning the client and server `Connect`
+async fn relay(pair: &Pair) -> (Connect, Connect) {
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+    negotiate(pair, &client, &server).await;
+
+    let relay = client.connect(addr()).await.unwrap();
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::Connect(conn)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+    (relay, conn)
+}
+
+#[tokio::test]
+async fn split() {
+    let pair = Pair::new().await;
+    let (relay, conn) = relay(&pair).await;
+    let (mut relay_read, mut relay_write) = relay.into_split();
+    let (mut conn_read, mut conn_write) = conn.into_split();
+
+    // the result is only read by the client read half and sent by the server write half
+    assert_eq!(conn_read.result().await.unwrap(), None);
+    conn_write.respond(ConnectStatus::Connected).await.unwrap();
+    assert_eq!(
+        relay_read.result().await.unwrap(),
+        Some(ConnectStatus::Connected)
+    );
+
+    relay_write.write_all(b"request").await.unwrap();
+    relay_write.close().await.unwrap();
+    let mut buf = Vec::new();
+    conn_read.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"request");
+
+    drop(conn_read);
+    conn_write.write_all(b"response").await.unwrap();
+    conn_write.close().await.unwrap();
+    let mut buf = Vec::new();
+    relay_read.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"response");
+}
+
+#[tokio::test]
+async fn split_reset() {
+    let pair = Pair::new().await;
+    let (relay, conn) = relay(&pair).await;
+    let (mut relay_read, mut relay_write) = relay.into_split();
+    let (mut conn_read, mut conn_write) = conn.into_split();
+    conn_write.respond(ConnectStatus::Connected).await.unwrap();
+    relay_read.result().await.unwrap();
+
+    // resetting one direction leaves the other one open
+    conn_write.reset(ErrorCode::TargetUnreachable).unwrap();
+    let mut buf = [0; 8];
+    assert!(relay_read.read(&mut buf).await.is_err());
+
+    relay_write.write_all(b"request").await.unwrap();
+    relay_write.close().await.unwrap();
+    let mut buf = Vec::new();
+    conn_read.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"request");
+}
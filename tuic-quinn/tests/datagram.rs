@@ -0,0 +1,145 @@
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::Pair;
+use std::net::SocketAddr;
+use tuic::{Address, Header, Packet as PacketHeader};
+use tuic_quinn::{side, Connection, Error, Task};
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 80)))
+}
+
+fn packet_datagram(size: u16, payload_len: usize) -> Bytes {
+    let mut buf = Vec::new();
+    Header::Packet(PacketHeader::new(0, 0, 1, 0, size, addr())).write(&mut buf);
+    buf.resize(buf.len() + payload_len, 0);
+    Bytes::from(buf)
+}
+
+#[tokio::test]
+async fn server_truncated_payload() {
+    let pair = Pair::new().await;
+    let conn = Connection::<side::Server>::new(pair.server.clone());
+
+    assert!(matches!(
+        conn.accept_datagram(packet_datagram(100, 10)),
+        Err(Error::PayloadLength(100, 10)),
+    ));
+
+    // a payload longer than `size` is cut to `size`
+    let Ok(Task::Packet(pkt)) = conn.accept_datagram(packet_datagram(10, 100)) else {
+        panic!("expecting a packet");
+    };
+    let (pkt, _, _) = pkt.accept().await.unwrap().unwrap();
+    assert_eq!(pkt.len(), 10);
+}
+
+#[tokio::test]
+async fn client_truncated_payload() {
+    let pair = Pair::new().await;
+    let conn = Connection::<side::Client>::new(pair.client.clone());
+
+    // register the UDP session on the client side
+    conn.packet_native(b"", addr(), 0).unwrap();
+
+    assert!(matches!(
+        conn.accept_datagram(packet_datagram(100, 10)),
+        Err(Error::PayloadLength(100, 10)),
+    ));
+}
+
+#[tokio::test]
+async fn native_relay() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+    let max_dg_size = pair.client.max_datagram_size().unwrap();
+
+    // payloads around the fragment boundaries
+    for len in [
+        0,
+        1,
+        max_dg_size - 17,
+        max_dg_size - 16,
+        2 * max_dg_size - 28,
+        4096,
+    ] {
+        let payload = (0..len).map(|b| b as u8).collect::<Vec<_>>();
+        client.packet_native(&payload, addr(), 0).unwrap();
+
+        loop {
+            let dg = pair.server.read_datagram().await.unwrap();
+            let Ok(Task::Packet(pkt)) = server.accept_datagram(dg) else {
+                panic!("expecting a packet");
+            };
+
+            if let Some((pkt, pkt_addr, assoc_id)) = pkt.accept().await.unwrap() {
+                assert_eq!(pkt, payload);
+                assert_eq!(pkt_addr, addr());
+                assert_eq!(assoc_id, 0);
+                break;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn accept_into() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    let payload = vec![1; 4096];
+    client.packet_native(&payload, addr(), 0).unwrap();
+
+    let mut buf = BytesMut::from(&b"prefix"[..]);
+
+    loop {
+        let dg = pair.server.read_datagram().await.unwrap();
+        let Ok(Task::Packet(pkt)) = server.accept_datagram(dg) else {
+            panic!("expecting a packet");
+        };
+
+        if let Some((pkt_addr, assoc_id)) = pkt.accept_into(&mut buf).await.unwrap() {
+            assert_eq!(&buf[..6], b"prefix");
+            assert_eq!(buf[6..], payload);
+            assert_eq!(pkt_addr, addr());
+            assert_eq!(assoc_id, 0);
+            break;
+        }
+
+        assert_eq!(buf, b"prefix"[..]);
+    }
+}
+
+#[tokio::test]
+async fn domain_address() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+    let addr = Address::DomainAddress("example.com".to_string(), 53);
+
+    client.packet_native(b"query", addr.clone(), 0).unwrap();
+    let dg = pair.server.read_datagram().await.unwrap();
+    let Ok(Task::Packet(pkt)) = server.accept_datagram(dg) else {
+        panic!("expecting a packet");
+    };
+    assert_eq!((pkt.assoc_id(), pkt.frag_id(), pkt.frag_total()), (0, 0, 1));
+    assert_eq!(
+        pkt.accept().await.unwrap(),
+        Some((Bytes::from_static(b"query"), addr.clone(), 0)),
+    );
+
+    // the UDP session is checked again once the packet is accepted
+    server.packet_native(b"reply", addr, 0).unwrap();
+    let dg = pair.client.read_datagram().await.unwrap();
+    let Ok(Task::Packet(pkt)) = client.accept_datagram(dg) else {
+        panic!("expecting a packet");
+    };
+    client.dissociate(0).await.unwrap();
+    assert!(matches!(
+        pkt.accept().await,
+        Err(Error::InvalidUdpSession(0, _)),
+    ));
+}
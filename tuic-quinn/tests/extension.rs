@@ -0,0 +1,79 @@
+mod common;
+
+use common::Pair;
+use tuic::Extension as ExtensionHeader;
+use tuic_quinn::{side, Connection, Task};
+
+fn ext() -> ExtensionHeader {
+    ExtensionHeader::new(0x80, b"payload".to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn uni_stream() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    let mut send = client.extension_uni_stream(ext()).await.unwrap();
+    send.write_all(b"data").await.unwrap();
+    send.finish().unwrap();
+
+    let recv = pair.server.accept_uni().await.unwrap();
+    let Ok(Task::Extension(ext)) = server.accept_uni_stream(recv).await else {
+        panic!("expecting an extension");
+    };
+
+    assert!(ext.is_from_uni_stream());
+    assert_eq!((ext.type_code(), ext.payload()), (0x80, &b"payload"[..]));
+
+    let (_, send, recv) = ext.into_parts();
+    assert!(send.is_none());
+
+    let buf = recv.unwrap().read_to_end(1024).await.unwrap();
+    assert_eq!(buf, b"data");
+}
+
+#[tokio::test]
+async fn bi_stream() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    let (mut send, mut recv) = client.extension_bi_stream(ext()).await.unwrap();
+    send.finish().unwrap();
+
+    let (server_send, server_recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::Extension(ext)) = server.accept_bi_stream(server_send, server_recv).await else {
+        panic!("expecting an extension");
+    };
+
+    assert!(ext.is_from_bi_stream());
+
+    let (header, Some(mut send), Some(_)) = ext.into_parts() else {
+        panic!("expecting both streams");
+    };
+    assert_eq!(header, self::ext());
+
+    send.write_all(b"reply").await.unwrap();
+    send.finish().unwrap();
+
+    let buf = recv.read_to_end(1024).await.unwrap();
+    assert_eq!(buf, b"reply");
+}
+
+#[tokio::test]
+async fn datagram() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    // both sides can send extensions
+    server.extension_datagram(ext()).unwrap();
+    let dg = pair.client.read_datagram().await.unwrap();
+    let Ok(Task::Extension(ext)) = client.accept_datagram(dg) else {
+        panic!("expecting an extension");
+    };
+
+    assert!(ext.is_from_datagram());
+    assert_eq!(ext.into_parts().0, self::ext());
+}
@@ -0,0 +1,263 @@
+use futures_util::{AsyncReadExt, AsyncWriteExt};
+use std::{net::SocketAddr, time::Duration};
+use tokio::time::Instant;
+use tuic::{Address, ConnectStatus};
+use tuic_quinn::{
+    loopback::{self, Config, ConnectionError, Loopback, SendDatagramError},
+    side, Connection, Error, ErrorCode, Task, Transport,
+};
+use uuid::Uuid;
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 53)))
+}
+
+fn connections(
+    config: Config,
+) -> (
+    Loopback,
+    Loopback,
+    Connection<side::Client, Loopback>,
+    Connection<side::Server, Loopback>,
+) {
+    let (client, server) = loopback::pair(config);
+    let client_conn = Connection::<side::Client, _>::new(client.clone());
+    let server_conn = Connection::<side::Server, _>::new(server.clone());
+    (client, server, client_conn, server_conn)
+}
+
+/// Relays the packets the server receives until the client closes the connection
+async fn drain_packets(
+    server: &Loopback,
+    server_conn: &Connection<side::Server, Loopback>,
+) -> Vec<Vec<u8>> {
+    let mut pkts = Vec::new();
+    while let Ok(dg) = server.read_datagram().await {
+        let Ok(Task::Packet(pkt)) = server_conn.accept_datagram(dg) else {
+            panic!("expecting a packet");
+        };
+        if let Some((pkt, pkt_addr, _)) = pkt.accept().await.unwrap() {
+            assert_eq!(pkt_addr, addr());
+            pkts.push(pkt.to_vec());
+        }
+    }
+    pkts
+}
+
+#[tokio::test]
+async fn authenticate() {
+    let (client, server, client_conn, server_conn) = connections(Config::default());
+    let uuid = Uuid::from_u128(0x1234);
+
+    client_conn.authenticate(uuid, b"password").await.unwrap();
+    let recv = server.accept_uni().await.unwrap();
+    let Ok(Task::Authenticate(auth)) = server_conn.accept_uni_stream(recv).await else {
+        panic!("expecting an authentication");
+    };
+    assert_eq!(auth.uuid(), uuid);
+    assert!(auth.validate(b"password"));
+    assert!(!auth.validate(b"wrong"));
+
+    // tokens are bound to the connection
+    let (other, _) = loopback::pair(Config {
+        seed: 1,
+        ..Config::default()
+    });
+    let mut token = [0; 32];
+    other.export_keying_material(&mut token, uuid.as_bytes(), b"password");
+    assert_ne!(token, auth.token());
+    client.export_keying_material(&mut token, uuid.as_bytes(), b"password");
+    assert_eq!(token, auth.token());
+}
+
+#[tokio::test]
+async fn connect() {
+    let (client, server, client_conn, server_conn) = connections(Config::default());
+
+    client_conn
+        .authenticate(Uuid::nil(), b"password")
+        .await
+        .unwrap();
+    let recv = server.accept_uni().await.unwrap();
+    server_conn.accept_uni_stream(recv).await.unwrap();
+
+    server_conn.advertise_capabilities().await.unwrap();
+    let recv = client.accept_uni().await.unwrap();
+    client_conn.accept_uni_stream(recv).await.unwrap();
+
+    let mut relay = client_conn.connect(addr()).await.unwrap();
+    let (send, recv) = server.accept_bi().await.unwrap();
+    let Ok(Task::Connect(mut conn)) = server_conn.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+    assert_eq!(conn.addr(), &addr());
+
+    conn.respond(ConnectStatus::Connected).await.unwrap();
+    assert_eq!(
+        relay.result().await.unwrap(),
+        Some(ConnectStatus::Connected)
+    );
+
+    relay.write_all(b"request").await.unwrap();
+    relay.close().await.unwrap();
+    let mut buf = Vec::new();
+    conn.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"request");
+
+    conn.write_all(b"response").await.unwrap();
+    let _ = conn.reset(ErrorCode::TargetUnreachable);
+    let mut buf = [0; 8];
+    relay.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"response");
+    assert!(relay.read(&mut buf).await.is_err());
+}
+
+#[tokio::test]
+async fn fragmentation() {
+    let config = Config {
+        mtu: Some(200),
+        reorder: 0.3,
+        ..Config::default()
+    };
+    let (client, server, client_conn, server_conn) = connections(config);
+
+    let payloads = (0..16)
+        .map(|i| (0..i * 100).map(|b| b as u8).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for payload in &payloads {
+        client_conn.packet_native(payload, addr(), 0).unwrap();
+    }
+    // the last datagram may be held back forever
+    client_conn.heartbeat().await.unwrap();
+    client.close(ErrorCode::Unspecified, b"");
+
+    let mut pkts = Vec::new();
+    while let Ok(dg) = server.read_datagram().await {
+        match server_conn.accept_datagram(dg) {
+            Ok(Task::Packet(pkt)) => {
+                if let Some((pkt, _, _)) = pkt.accept().await.unwrap() {
+                    pkts.push(pkt.to_vec());
+                }
+            }
+            Ok(Task::Heartbeat) => {}
+            _ => panic!("expecting a packet"),
+        }
+    }
+
+    pkts.sort_by_key(Vec::len);
+    assert_eq!(pkts, payloads);
+}
+
+#[tokio::test]
+async fn loss() {
+    let config = Config {
+        mtu: Some(200),
+        loss: 0.2,
+        seed: 42,
+        ..Config::default()
+    };
+    let (client, server, client_conn, server_conn) = connections(config);
+
+    let payloads = (0..64).map(|i| vec![i as u8; 500]).collect::<Vec<_>>();
+    for payload in &payloads {
+        client_conn.packet_native(payload, addr(), 0).unwrap();
+    }
+    client.close(ErrorCode::Unspecified, b"");
+
+    // a packet losing any fragment is never assembled
+    let pkts = drain_packets(&server, &server_conn).await;
+    assert!(!pkts.is_empty());
+    assert!(pkts.len() < payloads.len());
+    assert!(pkts.iter().all(|pkt| payloads.contains(pkt)));
+}
+
+#[tokio::test]
+async fn mtu() {
+    // the server must be kept, or the connection is closed
+    let (_, _server, client_conn, _) = connections(Config {
+        mtu: None,
+        ..Config::default()
+    });
+    assert!(matches!(
+        client_conn.packet_native(b"data", addr(), 0),
+        Err(Error::SendDatagram(SendDatagramError::Disabled)),
+    ));
+
+    let (client, server, client_conn, server_conn) = connections(Config {
+        mtu: Some(64),
+        ..Config::default()
+    });
+    assert!(matches!(
+        client.send_datagram(vec![0; 65].into()),
+        Err(SendDatagramError::TooLarge),
+    ));
+
+    client_conn.packet_native(vec![0; 1000], addr(), 0).unwrap();
+    client.close(ErrorCode::Unspecified, b"");
+    assert_eq!(drain_packets(&server, &server_conn).await, [vec![0; 1000]]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn heartbeat() {
+    let delay = Duration::from_millis(100);
+    let (_, server, client_conn, server_conn) = connections(Config {
+        delay,
+        ..Config::default()
+    });
+
+    let start = Instant::now();
+    client_conn.heartbeat().await.unwrap();
+    let dg = server.read_datagram().await.unwrap();
+    assert!(matches!(
+        server_conn.accept_datagram(dg),
+        Ok(Task::Heartbeat)
+    ));
+    assert!(start.elapsed() >= delay);
+}
+
+#[tokio::test]
+async fn dissociate() {
+    let (client, server, client_conn, server_conn) = connections(Config::default());
+
+    client_conn
+        .packet_quic(b"data", addr(), 0x1234)
+        .await
+        .unwrap();
+    let recv = server.accept_uni().await.unwrap();
+    let Ok(Task::Packet(pkt)) = server_conn.accept_uni_stream(recv).await else {
+        panic!("expecting a packet");
+    };
+    assert!(pkt.is_from_quic());
+    assert!(pkt.accept().await.unwrap().is_some());
+
+    client_conn.dissociate(0x1234).await.unwrap();
+    let recv = server.accept_uni().await.unwrap();
+    assert!(matches!(
+        server_conn.accept_uni_stream(recv).await,
+        Ok(Task::Dissociate(0x1234)),
+    ));
+
+    client.close(ErrorCode::Unknown(7), b"");
+    assert!(matches!(
+        server.accept_uni().await,
+        Err(ConnectionError::Closed(ErrorCode::Unknown(7))),
+    ));
+}
+
+#[tokio::test]
+async fn error_code() {
+    let (client, _server, _, server_conn) = connections(Config::default());
+
+    server_conn.close(ErrorCode::AuthFailed);
+    let Err(ConnectionError::Closed(code)) = client.accept_uni().await else {
+        panic!("expecting the connection closed");
+    };
+    assert_eq!(code, ErrorCode::AuthFailed);
+
+    // unknown codes are kept as is
+    let code = ErrorCode::from(0x1234);
+    assert_eq!(code, ErrorCode::Unknown(0x1234));
+    assert_eq!(u64::from(code), 0x1234);
+    assert_eq!(ErrorCode::from(0x06), ErrorCode::ServerShutdown);
+    assert_eq!(ErrorCode::from(0x07), ErrorCode::TooManyTasks);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use bytes::Bytes;
+use common::Pair;
+use std::net::SocketAddr;
+use tuic::{Address, Header, Packet as PacketHeader};
+use tuic_quinn::{side, Connection, Error, Task};
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 53)))
+}
+
+#[tokio::test]
+async fn stream_relay() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    let mut stream = client.packet_stream(0x1234).await.unwrap();
+    assert_eq!(stream.assoc_id(), 0x1234);
+
+    // larger than any datagram, and still not fragmented
+    let payloads = [Bytes::from_static(b"query"), Bytes::from(vec![0xab; 60000])];
+    for payload in &payloads {
+        stream.send(payload, addr()).await.unwrap();
+    }
+
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::PacketStream(server_stream)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a packet stream");
+    };
+    assert_eq!(server_stream.assoc_id(), 0x1234);
+    let (mut server_send, mut server_recv) = server_stream.into_split();
+
+    for payload in &payloads {
+        let pkt = server_recv.recv().await.unwrap().unwrap();
+        assert!(pkt.is_from_stream());
+        assert_eq!(pkt.frag_total(), 1);
+
+        let (pkt, pkt_addr, assoc_id) = pkt.accept().await.unwrap().unwrap();
+        assert_eq!(&pkt, payload);
+        assert_eq!(pkt_addr, addr());
+        assert_eq!(assoc_id, 0x1234);
+    }
+
+    // replies are sent back on the same stream
+    server_send.send(b"answer", addr()).await.unwrap();
+    server_send.close().await.unwrap();
+
+    let pkt = stream.recv().await.unwrap().unwrap();
+    let (pkt, _, assoc_id) = pkt.accept().await.unwrap().unwrap();
+    assert_eq!(&pkt[..], b"answer");
+    assert_eq!(assoc_id, 0x1234);
+    assert!(stream.recv().await.unwrap().is_none());
+
+    let (mut client_send, _) = stream.into_split();
+    client_send.close().await.unwrap();
+    assert!(server_recv.recv().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn foreign_assoc_id() {
+    let pair = Pair::new().await;
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    let mut buf = Vec::new();
+    for assoc_id in [0, 1] {
+        Header::Packet(PacketHeader::new(assoc_id, 0, 1, 0, 4, addr())).write(&mut buf);
+        buf.extend_from_slice(b"data");
+    }
+
+    let (mut send, _recv) = pair.client.open_bi().await.unwrap();
+    send.write_all(&buf).await.unwrap();
+
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::PacketStream(mut stream)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a packet stream");
+    };
+
+    assert!(stream.recv().await.unwrap().is_some());
+    assert!(matches!(
+        stream.recv().await,
+        Err(Error::PacketStreamAssocId(0, 1)),
+    ));
+}
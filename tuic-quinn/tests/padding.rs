@@ -0,0 +1,159 @@
+mod common;
+
+use common::Pair;
+use futures_util::AsyncWriteExt;
+use std::net::{Ipv4Addr, SocketAddr};
+use tuic::{Address, Capabilities, Connect, ConnectStatus, Header, Padding};
+use tuic_quinn::{side, Connection, Error, PaddingPolicy, Task};
+use uuid::Uuid;
+
+fn padding() -> PaddingPolicy {
+    PaddingPolicy {
+        handshake: Some(100..=200),
+        heartbeat: Some(100..=200),
+        connect: Some(100..=200),
+    }
+}
+
+#[tokio::test]
+async fn send_padding() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    server.send_padding(1024).await.unwrap();
+
+    let recv = pair.client.accept_uni().await.unwrap();
+    assert!(matches!(
+        client.accept_uni_stream(recv).await,
+        Ok(Task::Padding),
+    ));
+}
+
+#[tokio::test]
+async fn handshake_and_heartbeat() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone()).with_padding(padding());
+    let server = Connection::<side::Server>::new(pair.server.clone()).with_padding(padding());
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let recv = pair.server.accept_uni().await.unwrap();
+    let Ok(Task::Authenticate(auth)) = server.accept_uni_stream(recv).await else {
+        panic!("expecting an authentication");
+    };
+    assert!(auth.validate(b"password"));
+    assert!(server.peer_capabilities().is_some());
+
+    server.advertise_capabilities().await.unwrap();
+    let recv = pair.client.accept_uni().await.unwrap();
+    assert!(matches!(
+        client.accept_uni_stream(recv).await,
+        Ok(Task::Capabilities(_)),
+    ));
+
+    client.heartbeat().await.unwrap();
+    let dg = pair.server.read_datagram().await.unwrap();
+    assert!(dg.len() >= 2 + 4 + 100);
+    assert!(matches!(server.accept_datagram(dg), Ok(Task::Heartbeat)));
+}
+
+#[tokio::test]
+async fn connect() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone()).with_padding(padding());
+    let server = Connection::<side::Server>::new(pair.server.clone()).with_padding(padding());
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let recv = pair.server.accept_uni().await.unwrap();
+    server.accept_uni_stream(recv).await.unwrap();
+
+    server.advertise_capabilities().await.unwrap();
+    let recv = pair.client.accept_uni().await.unwrap();
+    client.accept_uni_stream(recv).await.unwrap();
+    assert!(client
+        .peer_capabilities()
+        .unwrap()
+        .contains(Capabilities::PADDING | Capabilities::CONNECT_RESULT));
+
+    let addr = Address::SocketAddress(SocketAddr::from((Ipv4Addr::LOCALHOST, 80)));
+    let mut relay = client.connect(addr.clone()).await.unwrap();
+
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    let Ok(Task::Connect(mut conn)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+    assert_eq!(conn.addr(), &addr);
+
+    conn.respond(ConnectStatus::Connected).await.unwrap();
+    conn.close().await.unwrap();
+
+    assert_eq!(
+        relay.result().await.unwrap(),
+        Some(ConnectStatus::Connected)
+    );
+}
+
+#[tokio::test]
+async fn heartbeat_not_negotiated() {
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone()).with_padding(padding());
+    let server = Connection::<side::Server>::new(pair.server.clone())
+        .with_capabilities(Capabilities::UDP_RELAY_NATIVE);
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let recv = pair.server.accept_uni().await.unwrap();
+    server.accept_uni_stream(recv).await.unwrap();
+
+    server.advertise_capabilities().await.unwrap();
+    let recv = pair.client.accept_uni().await.unwrap();
+    client.accept_uni_stream(recv).await.unwrap();
+
+    client.heartbeat().await.unwrap();
+    let dg = pair.server.read_datagram().await.unwrap();
+    assert_eq!(dg.len(), 2);
+    assert!(matches!(server.accept_datagram(dg), Ok(Task::Heartbeat)));
+}
+
+#[tokio::test]
+async fn repeated_leading_headers() {
+    let pair = Pair::new().await;
+    let server = Connection::<side::Server>::new(pair.server.clone());
+
+    let addr = Address::SocketAddress(SocketAddr::from((Ipv4Addr::LOCALHOST, 80)));
+    let padding = Header::Padding(Padding::new(16));
+    let caps = Header::Capabilities(Capabilities::CONNECT_RESULT);
+    let connect = Header::Connect(Connect::new(addr));
+
+    for (leading, cmd) in [
+        (vec![&padding, &padding], "padding"),
+        (vec![&caps, &padding, &caps], "capabilities"),
+    ] {
+        let (mut send, _recv) = pair.client.open_bi().await.unwrap();
+        let mut buf = Vec::new();
+        for header in leading.into_iter().chain([&connect]) {
+            header.write(&mut buf);
+        }
+        send.write_all(&buf).await.unwrap();
+
+        let (send, recv) = pair.server.accept_bi().await.unwrap();
+        let Err(err) = server.accept_bi_stream(send, recv).await else {
+            panic!("expecting a bad command");
+        };
+        assert!(err.is_bad_command());
+        assert!(matches!(err, Error::BadCommandBiStream(err, _, _) if err == cmd));
+    }
+
+    // one of each, in either order, is accepted
+    let (mut send, _recv) = pair.client.open_bi().await.unwrap();
+    let mut buf = Vec::new();
+    for header in [&caps, &padding, &connect] {
+        header.write(&mut buf);
+    }
+    send.write_all(&buf).await.unwrap();
+
+    let (send, recv) = pair.server.accept_bi().await.unwrap();
+    assert!(matches!(
+        server.accept_bi_stream(send, recv).await,
+        Ok(Task::Connect(_)),
+    ));
+}
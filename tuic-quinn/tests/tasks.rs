@@ -0,0 +1,380 @@
+mod common;
+
+use common::Pair;
+use futures_util::{AsyncReadExt, AsyncWriteExt, StreamExt};
+use std::{net::SocketAddr, time::Duration};
+use tokio::time;
+use tuic::Address;
+use tuic_quinn::{
+    loopback::{self, Config, ConnectionError},
+    side, Connection, Error, ErrorCode, Task, Transport,
+};
+use uuid::Uuid;
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 53)))
+}
+
+#[tokio::test]
+async fn authentication_gating() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server);
+    let mut tasks = server.tasks();
+
+    client.heartbeat().await.unwrap();
+    client.dissociate(0).await.unwrap();
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+
+    let Some(Ok(Task::Authenticate(auth))) = tasks.next().await else {
+        panic!("expecting an authentication");
+    };
+    assert!(auth.validate(b"password"));
+    assert!(server.authenticated().is_none());
+
+    // everything else is held back until then
+    assert!(time::timeout(Duration::from_millis(50), tasks.next())
+        .await
+        .is_err());
+
+    server.set_authenticated(auth.uuid());
+    assert_eq!(server.authenticated(), Some(Uuid::nil()));
+
+    let mut heartbeat = false;
+    let mut dissociate = false;
+    for _ in 0..2 {
+        match tasks.next().await {
+            Some(Ok(Task::Heartbeat)) => heartbeat = true,
+            Some(Ok(Task::Dissociate(0))) => dissociate = true,
+            _ => panic!("expecting a heartbeat or dissociate"),
+        }
+    }
+    assert!(heartbeat && dissociate);
+}
+
+#[tokio::test]
+async fn connect_counted_after_gating() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|auth| auth.validate(b"password"));
+    let mut tasks = server.tasks();
+
+    let _relay = client.connect(addr()).await.unwrap();
+    assert!(time::timeout(Duration::from_millis(50), tasks.next())
+        .await
+        .is_err());
+    assert_eq!(server.task_connect_count(), 0);
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let mut conn = None;
+    for _ in 0..2 {
+        match tasks.next().await {
+            Some(Ok(Task::Authenticate(_))) => {}
+            Some(Ok(Task::Connect(task))) => conn = Some(task),
+            _ => panic!("expecting an authentication or connect"),
+        }
+    }
+    assert!(conn.is_some());
+    assert_eq!(server.task_connect_count(), 1);
+
+    drop(conn);
+    assert_eq!(server.task_connect_count(), 0);
+}
+
+#[tokio::test]
+async fn authenticator() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|auth| auth.validate(b"password"));
+    let mut tasks = server.tasks();
+
+    client.heartbeat().await.unwrap();
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+
+    let Some(Ok(Task::Authenticate(auth))) = tasks.next().await else {
+        panic!("expecting an authentication");
+    };
+    assert_eq!(auth.uuid(), Uuid::nil());
+    assert_eq!(server.authenticated(), Some(Uuid::nil()));
+    assert!(matches!(tasks.next().await, Some(Ok(Task::Heartbeat))));
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    assert!(matches!(
+        tasks.next().await,
+        Some(Err(Error::DuplicatedAuth)),
+    ));
+}
+
+#[tokio::test]
+async fn authentication_failure() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|auth| auth.validate(b"password"));
+    let mut tasks = server.tasks();
+
+    client.heartbeat().await.unwrap();
+    client.dissociate(0).await.unwrap();
+    time::sleep(Duration::from_millis(50)).await;
+    client.authenticate(Uuid::nil(), b"wrong").await.unwrap();
+
+    assert!(matches!(
+        tasks.next().await,
+        Some(Err(Error::AuthFailed(uuid))) if uuid.is_nil(),
+    ));
+    assert!(server.authenticated().is_none());
+
+    // the held tasks are cancelled
+    assert!(time::timeout(Duration::from_millis(50), tasks.next())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn dissociate_gating() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|auth| auth.validate(b"password"));
+    let mut tasks = server.tasks();
+
+    server.packet_native(b"data", addr(), 0).unwrap();
+    assert_eq!(server.task_associate_count(), 1);
+
+    // a held back `Dissociate` does not touch the UDP session
+    client.dissociate(0).await.unwrap();
+    time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.task_associate_count(), 1);
+
+    // nor once it is cancelled by a failed authentication
+    client.authenticate(Uuid::nil(), b"wrong").await.unwrap();
+    assert!(matches!(
+        tasks.next().await,
+        Some(Err(Error::AuthFailed(_))),
+    ));
+    assert!(time::timeout(Duration::from_millis(50), tasks.next())
+        .await
+        .is_err());
+    assert_eq!(server.task_associate_count(), 1);
+
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|auth| auth.validate(b"password"));
+    let mut tasks = server.tasks();
+
+    server.packet_native(b"data", addr(), 0).unwrap();
+    client.dissociate(0).await.unwrap();
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+
+    assert!(matches!(
+        tasks.next().await,
+        Some(Ok(Task::Authenticate(_))),
+    ));
+    assert!(matches!(tasks.next().await, Some(Ok(Task::Dissociate(0)))));
+    assert_eq!(server.task_associate_count(), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn authentication_timeout() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|_| true)
+        .with_auth_timeout(Duration::from_secs(3));
+    let mut tasks = server.tasks();
+
+    client.heartbeat().await.unwrap();
+    assert!(matches!(tasks.next().await, Some(Err(Error::AuthTimeout))));
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    assert!(matches!(
+        tasks.next().await,
+        Some(Err(Error::AuthFailed(_))),
+    ));
+    assert!(server.authenticated().is_none());
+}
+
+#[tokio::test]
+async fn pending_task_limit() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|_| true)
+        .with_max_pending_tasks(2);
+    let mut tasks = server.tasks();
+
+    for _ in 0..3 {
+        client.heartbeat().await.unwrap();
+    }
+    assert!(matches!(
+        tasks.next().await,
+        Some(Err(Error::TooManyPendingTasks)),
+    ));
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let mut heartbeats = 0;
+    for _ in 0..3 {
+        match tasks.next().await {
+            Some(Ok(Task::Authenticate(_))) => {}
+            Some(Ok(Task::Heartbeat)) => heartbeats += 1,
+            _ => panic!("expecting an authentication or heartbeat"),
+        }
+    }
+    assert_eq!(heartbeats, 2);
+}
+
+#[tokio::test]
+async fn pending_task_limit_stream() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server)
+        .with_authenticator(|_| true)
+        .with_max_pending_tasks(0);
+    let mut tasks = server.tasks();
+
+    let mut relay = client.connect(addr()).await.unwrap();
+    let Some(Err(err)) = tasks.next().await else {
+        panic!("expecting too many pending tasks");
+    };
+    assert!(matches!(err, Error::TooManyPendingTasks));
+    assert!(!err.is_bad_command());
+
+    // only the stream is refused
+    let mut buf = [0; 4];
+    let err = relay.read(&mut buf).await.unwrap_err();
+    assert!(err
+        .to_string()
+        .contains(&ErrorCode::TooManyTasks.to_string()));
+    assert_eq!(server.task_connect_count(), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn negotiation_timeout() {
+    let (client, server) = loopback::pair(Config::default());
+    let server = Connection::<side::Server, _>::new(server)
+        .with_task_negotiation_timeout(Duration::from_secs(3));
+    let mut tasks = server.tasks();
+
+    let mut send = client.open_uni().await.unwrap();
+    send.write_all(&[0x05]).await.unwrap();
+
+    let Some(Err(err)) = tasks.next().await else {
+        panic!("expecting a negotiation timeout");
+    };
+    assert!(matches!(err, Error::TaskNegotiationTimeout));
+    assert!(!err.is_bad_command());
+}
+
+#[tokio::test]
+async fn connection_lost() {
+    let (client_transport, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client_transport.clone());
+    let mut tasks = client.tasks();
+
+    let server = Connection::<side::Server, _>::new(server);
+    server.advertise_capabilities().await.unwrap();
+    assert!(matches!(
+        tasks.next().await,
+        Some(Ok(Task::Capabilities(_))),
+    ));
+
+    client_transport.close(ErrorCode::AuthFailed, b"");
+    assert!(matches!(
+        tasks.next().await,
+        Some(Err(Error::Connection(ConnectionError::Closed(_)))),
+    ));
+    assert!(tasks.next().await.is_none());
+}
+
+#[tokio::test]
+async fn drain() {
+    let (client, server) = loopback::pair(Config::default());
+    let client = Connection::<side::Client, _>::new(client);
+    let server = Connection::<side::Server, _>::new(server);
+    server.set_authenticated(Uuid::nil());
+    let mut tasks = server.tasks();
+
+    let mut relay = client.connect(addr()).await.unwrap();
+    let Some(Ok(Task::Connect(mut conn))) = tasks.next().await else {
+        panic!("expecting a connect");
+    };
+
+    client.packet_native(b"data", addr(), 0).unwrap();
+    let Some(Ok(Task::Packet(pkt))) = tasks.next().await else {
+        panic!("expecting a packet");
+    };
+    assert!(pkt.accept().await.unwrap().is_some());
+    assert_eq!(server.task_associate_count(), 1);
+
+    server.drain();
+    assert!(server.is_draining());
+
+    // new relays and UDP sessions are refused
+    let mut refused = client.connect(addr()).await.unwrap();
+    client.packet_native(b"data", addr(), 1).unwrap();
+    client.packet_quic(b"data", addr(), 2).await.unwrap();
+    assert!(time::timeout(Duration::from_millis(50), tasks.next())
+        .await
+        .is_err());
+    let mut buf = [0; 4];
+    assert!(refused.read(&mut buf).await.is_err());
+    assert_eq!(server.task_associate_count(), 1);
+
+    // while the existing UDP sessions keep going
+    client.heartbeat().await.unwrap();
+    assert!(matches!(tasks.next().await, Some(Ok(Task::Heartbeat))));
+
+    client.packet_native(b"data", addr(), 0).unwrap();
+    let Some(Ok(Task::Packet(pkt))) = tasks.next().await else {
+        panic!("expecting a packet");
+    };
+    assert!(pkt.accept().await.unwrap().is_some());
+
+    client.dissociate(0).await.unwrap();
+    assert!(matches!(tasks.next().await, Some(Ok(Task::Dissociate(0)))));
+    assert_eq!(server.task_associate_count(), 0);
+
+    // and the existing relays are left to finish
+    assert_eq!(server.task_connect_count(), 1);
+    conn.write_all(b"data").await.unwrap();
+    conn.close().await.unwrap();
+    relay.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"data");
+
+    drop(conn);
+    assert_eq!(server.task_connect_count(), 0);
+}
+
+#[tokio::test]
+async fn stream_limit_growth() {
+    // quinn allows 100 concurrent streams by default
+    let pair = Pair::new().await;
+    let client = Connection::<side::Client>::new(pair.client.clone());
+    let server =
+        Connection::<side::Server>::new(pair.server.clone()).with_max_concurrent_streams(100);
+    server.set_authenticated(Uuid::nil());
+    let mut tasks = server.tasks();
+
+    let send = tokio::spawn(async move {
+        for _ in 0..250 {
+            client.packet_quic(b"data", addr(), 0).await.unwrap();
+        }
+    });
+
+    // the streams are kept open by the tasks holding them
+    let mut pkts = Vec::new();
+    let recv = async {
+        while pkts.len() < 250 {
+            let Some(Ok(Task::Packet(pkt))) = tasks.next().await else {
+                panic!("expecting a packet");
+            };
+            pkts.push(pkt);
+        }
+    };
+
+    time::timeout(Duration::from_secs(5), recv).await.unwrap();
+    send.await.unwrap();
+}
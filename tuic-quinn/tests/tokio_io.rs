@@ -0,0 +1,78 @@
+use std::net::SocketAddr;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tuic::{Address, ConnectStatus};
+use tuic_quinn::{
+    loopback::{self, Config, Loopback},
+    side, Connect, Connection, ErrorCode, Task, Transport,
+};
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 80)))
+}
+
+/// Opens a relay, returning the transports that must be kept to keep the connection open
+async fn relay() -> ((Loopback, Loopback), Connect<Loopback>, Connect<Loopback>) {
+    let (client, server) = loopback::pair(Config::default());
+    let client_conn = Connection::<side::Client, _>::new(client.clone());
+    let server_conn = Connection::<side::Server, _>::new(server.clone());
+
+    let relay = client_conn.connect(addr()).await.unwrap();
+    let (send, recv) = server.accept_bi().await.unwrap();
+    let Ok(Task::Connect(conn)) = server_conn.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+    ((client, server), relay, conn)
+}
+
+#[tokio::test]
+async fn copy_bidirectional() {
+    let (_transports, mut relay, mut conn) = relay().await;
+    conn.respond(ConnectStatus::Connected).await.unwrap();
+    relay.result().await.unwrap();
+    let mut relay = relay.compat();
+
+    // echoes everything back through an in-memory pipe
+    let (mut local, mut remote) = io::duplex(64);
+    let echo = tokio::spawn(async move {
+        let (mut read, mut write) = io::split(&mut remote);
+        io::copy(&mut read, &mut write).await.unwrap();
+    });
+    let proxy = tokio::spawn(async move {
+        let mut conn = conn.compat();
+        io::copy_bidirectional(&mut conn, &mut local).await
+    });
+
+    relay.write_all(b"request").await.unwrap();
+    relay.shutdown().await.unwrap();
+    let mut buf = Vec::new();
+    relay.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"request");
+
+    assert_eq!(proxy.await.unwrap().unwrap(), (7, 7));
+    echo.await.unwrap();
+}
+
+#[tokio::test]
+async fn split_halves() {
+    let (_transports, relay, conn) = relay().await;
+    let (relay_read, relay_write) = relay.into_split();
+    let (conn_read, conn_write) = conn.into_split();
+    let (mut relay_read, mut relay_write) = (relay_read.compat(), relay_write.compat());
+    let (mut conn_read, mut conn_write) = (conn_read.compat(), conn_write.compat());
+
+    relay_write.write_all(b"request").await.unwrap();
+    relay_write.shutdown().await.unwrap();
+    let mut buf = Vec::new();
+    conn_read.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"request");
+
+    conn_write.write_all(b"response").await.unwrap();
+    conn_write.shutdown().await.unwrap();
+    let mut buf = Vec::new();
+    relay_read.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"response");
+
+    // the relay is still reachable through the wrapper
+    let mut conn_write = conn_write.into_inner();
+    assert!(conn_write.reset(ErrorCode::Unspecified).is_err());
+}
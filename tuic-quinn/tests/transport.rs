@@ -0,0 +1,123 @@
+mod common;
+
+use bytes::Bytes;
+use common::Pair;
+use quinn::{
+    ClosedStream, Connection as QuinnConnection, ConnectionError, RecvStream, SendDatagramError,
+    SendStream,
+};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tuic::Address;
+use tuic_quinn::{side, Connection, ErrorCode, Task, Transport};
+use uuid::Uuid;
+
+/// A transport delegating to quinn, counting the streams opened
+#[derive(Clone, Debug)]
+struct Counting {
+    conn: QuinnConnection,
+    opened: Arc<AtomicUsize>,
+}
+
+impl Counting {
+    fn new(conn: QuinnConnection) -> Self {
+        Self {
+            conn,
+            opened: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn opened(&self) -> usize {
+        self.opened.load(Ordering::Relaxed)
+    }
+}
+
+impl Transport for Counting {
+    type SendStream = SendStream;
+    type RecvStream = RecvStream;
+    type ConnectionError = ConnectionError;
+    type SendDatagramError = SendDatagramError;
+    type ClosedStream = ClosedStream;
+
+    async fn open_uni(&self) -> Result<SendStream, ConnectionError> {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        self.conn.open_uni().await
+    }
+
+    async fn open_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        self.conn.open_bi().await
+    }
+
+    async fn accept_uni(&self) -> Result<RecvStream, ConnectionError> {
+        self.conn.accept_uni().await
+    }
+
+    async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        self.conn.accept_bi().await
+    }
+
+    fn send_datagram(&self, data: Bytes) -> Result<(), SendDatagramError> {
+        self.conn.send_datagram(data)
+    }
+
+    async fn read_datagram(&self) -> Result<Bytes, ConnectionError> {
+        self.conn.read_datagram().await
+    }
+
+    fn max_datagram_size(&self) -> Result<usize, SendDatagramError> {
+        Transport::max_datagram_size(&self.conn)
+    }
+
+    fn export_keying_material(&self, output: &mut [u8], label: &[u8], context: &[u8]) {
+        Transport::export_keying_material(&self.conn, output, label, context);
+    }
+
+    fn close(&self, error_code: ErrorCode, reason: &[u8]) {
+        Transport::close(&self.conn, error_code, reason);
+    }
+
+    fn reset(send: &mut SendStream, error_code: ErrorCode) -> Result<(), ClosedStream> {
+        QuinnConnection::reset(send, error_code)
+    }
+
+    fn stop(recv: &mut RecvStream, error_code: ErrorCode) -> Result<(), ClosedStream> {
+        QuinnConnection::stop(recv, error_code)
+    }
+}
+
+#[tokio::test]
+async fn custom_transport() {
+    let pair = Pair::new().await;
+    let client_transport = Counting::new(pair.client.clone());
+    let server_transport = Counting::new(pair.server.clone());
+    let client = Connection::<side::Client, _>::new(client_transport.clone());
+    let server = Connection::<side::Server, _>::new(server_transport.clone());
+
+    client.authenticate(Uuid::nil(), b"password").await.unwrap();
+    let recv = server_transport.accept_uni().await.unwrap();
+    let Ok(Task::Authenticate(auth)) = server.accept_uni_stream(recv).await else {
+        panic!("expecting an authentication");
+    };
+    assert!(auth.validate(b"password"));
+
+    let addr = Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 80)));
+    let _relay = client.connect(addr.clone()).await.unwrap();
+    let (send, recv) = server_transport.accept_bi().await.unwrap();
+    let Ok(Task::Connect(conn)) = server.accept_bi_stream(send, recv).await else {
+        panic!("expecting a connect");
+    };
+    assert_eq!(conn.addr(), &addr);
+
+    client.heartbeat().await.unwrap();
+    let dg = server_transport.read_datagram().await.unwrap();
+    assert!(matches!(server.accept_datagram(dg), Ok(Task::Heartbeat)));
+
+    assert_eq!(client_transport.opened(), 2);
+    assert_eq!(server_transport.opened(), 0);
+}
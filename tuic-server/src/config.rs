@@ -6,9 +6,11 @@ use serde::{de::Error as DeError, Deserialize, Deserializer};
 use serde_json::Error as SerdeError;
 use std::{
     collections::HashMap, env::ArgsOs, fmt::Display, fs::File, io::Error as IoError,
-    net::SocketAddr, path::PathBuf, str::FromStr, time::Duration,
+    net::SocketAddr, ops::RangeInclusive, path::PathBuf, str::FromStr, time::Duration,
 };
 use thiserror::Error;
+use tuic::model::ReassemblyLimits;
+use tuic_quinn::PaddingPolicy;
 use uuid::Uuid;
 
 const HELP_MSG: &str = r#"
@@ -67,6 +69,12 @@ pub struct Config {
     )]
     pub max_idle_time: Duration,
 
+    #[serde(
+        default = "default::drain_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub drain_timeout: Duration,
+
     #[serde(default = "default::max_external_packet_size")]
     pub max_external_packet_size: usize,
 
@@ -76,6 +84,18 @@ pub struct Config {
     #[serde(default = "default::receive_window")]
     pub receive_window: u32,
 
+    #[serde(
+        default = "default::udp_session_idle_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub udp_session_idle_timeout: Duration,
+
+    #[serde(default = "default::max_udp_sessions")]
+    pub max_udp_sessions: usize,
+
+    #[serde(default)]
+    pub udp_reassembly: UdpReassembly,
+
     #[serde(
         default = "default::gc_interval",
         deserialize_with = "deserialize_duration"
@@ -88,10 +108,68 @@ pub struct Config {
     )]
     pub gc_lifetime: Duration,
 
+    #[serde(default)]
+    pub padding: Padding,
+
     #[serde(default = "default::log_level")]
     pub log_level: LevelFilter,
 }
 
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Padding {
+    #[serde(deserialize_with = "deserialize_padding", default)]
+    pub capabilities: Option<RangeInclusive<u16>>,
+
+    #[serde(deserialize_with = "deserialize_padding", default)]
+    pub connect: Option<RangeInclusive<u16>>,
+}
+
+impl From<Padding> for PaddingPolicy {
+    fn from(padding: Padding) -> Self {
+        Self {
+            handshake: padding.capabilities,
+            heartbeat: None,
+            connect: padding.connect,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UdpReassembly {
+    pub max_pending_packets_per_session: usize,
+    pub max_pending_packets: usize,
+    pub max_buffered_bytes_per_session: usize,
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for UdpReassembly {
+    fn default() -> Self {
+        let limits = ReassemblyLimits::default();
+
+        Self {
+            max_pending_packets_per_session: limits.max_pending_packets_per_session,
+            max_pending_packets: limits.max_pending_packets,
+            max_buffered_bytes_per_session: limits.max_buffered_bytes_per_session,
+            max_buffered_bytes: limits.max_buffered_bytes,
+        }
+    }
+}
+
+impl UdpReassembly {
+    /// Returns the reassembly limits, allowing at most `max_sessions` UDP sessions
+    pub fn limits(&self, max_sessions: usize) -> ReassemblyLimits {
+        ReassemblyLimits {
+            max_sessions,
+            max_pending_packets_per_session: self.max_pending_packets_per_session,
+            max_pending_packets: self.max_pending_packets,
+            max_buffered_bytes_per_session: self.max_buffered_bytes_per_session,
+            max_buffered_bytes: self.max_buffered_bytes,
+        }
+    }
+}
+
 impl Config {
     pub fn parse(args: ArgsOs) -> Result<Self, ConfigError> {
         let mut parser = Parser::from_iter(args);
@@ -99,12 +177,8 @@ impl Config {
 
         while let Some(arg) = parser.next()? {
             match arg {
-                Arg::Short('c') | Arg::Long("config") => {
-                    if path.is_none() {
-                        path = Some(parser.value()?);
-                    } else {
-                        return Err(ConfigError::Argument(arg.unexpected()));
-                    }
+                Arg::Short('c') | Arg::Long("config") if path.is_none() => {
+                    path = Some(parser.value()?);
                 }
                 Arg::Short('v') | Arg::Long("version") => {
                     return Err(ConfigError::Version(env!("CARGO_PKG_VERSION")))
@@ -156,6 +230,10 @@ mod default {
         Duration::from_secs(10)
     }
 
+    pub fn drain_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
     pub fn max_external_packet_size() -> usize {
         1500
     }
@@ -168,6 +246,14 @@ mod default {
         8 * 1024 * 1024
     }
 
+    pub fn udp_session_idle_timeout() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    pub fn max_udp_sessions() -> usize {
+        256
+    }
+
     pub fn gc_interval() -> Duration {
         Duration::from_secs(3)
     }
@@ -226,6 +312,19 @@ where
         .map_err(DeError::custom)
 }
 
+pub fn deserialize_padding<'de, D>(deserializer: D) -> Result<Option<RangeInclusive<u16>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let [min, max] = <[u16; 2]>::deserialize(deserializer)?;
+
+    if min > max {
+        return Err(DeError::custom("invalid padding range"));
+    }
+
+    Ok(Some(min..=max))
+}
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error(transparent)]
@@ -1,43 +1,77 @@
-use super::{Connection, UdpSession, ERROR_CODE};
+use super::{Connection, UdpSession};
 use crate::{error::Error, utils::UdpRelayMode};
 use bytes::Bytes;
+use futures_util::AsyncWriteExt as FuturesAsyncWriteExt;
+use log::Level;
 use std::{
-    collections::hash_map::Entry,
     io::{Error as IoError, ErrorKind},
     net::SocketAddr,
+    sync::Arc,
 };
 use tokio::{
     io::{self, AsyncWriteExt},
     net::{self, TcpStream},
+    sync::Mutex as AsyncMutex,
+};
+use tuic::{model::AssembleError, Address, ConnectStatus};
+use tuic_quinn::{
+    Authenticate, Connect, Error as ModelError, ErrorCode, Extension, Packet, PacketStream, Task,
 };
-use tokio_util::compat::FuturesAsyncReadCompatExt;
-use tuic::Address;
-use tuic_quinn::{Authenticate, Connect, Packet};
 
 impl Connection {
+    pub async fn handle_task(self, task: Task) {
+        match task {
+            Task::Authenticate(auth) => self.handle_authenticate(auth).await,
+            Task::Connect(conn) => self.handle_connect(conn).await,
+            Task::Packet(pkt) if pkt.is_from_native() => {
+                self.handle_packet(pkt, UdpRelayMode::Native).await
+            }
+            Task::Packet(pkt) => self.handle_packet(pkt, UdpRelayMode::Quic).await,
+            Task::PacketStream(stream) => self.handle_packet_stream(stream).await,
+            Task::Dissociate(assoc_id) => self.handle_dissociate(assoc_id).await,
+            Task::Heartbeat => self.handle_heartbeat().await,
+            Task::Extension(ext) => self.handle_extension(ext).await,
+            Task::Padding => {}
+            _ => unreachable!(), // already filtered in `tuic_quinn`
+        }
+    }
+
     pub async fn handle_authenticate(&self, auth: Authenticate) {
         log::info!(
             "[{id:#010x}] [{addr}] [{user}] [authenticate] {auth_uuid}",
             id = self.id(),
             addr = self.inner.remote_address(),
-            user = self.auth,
+            user = self.user(),
             auth_uuid = auth.uuid(),
         );
+
+        // only clients that advertised their capabilities know the command
+        if self.model.peer_capabilities().is_some() {
+            if let Err(err) = self.model.advertise_capabilities().await {
+                log::warn!(
+                    "[{id:#010x}] [{addr}] [{user}] [authenticate] failed advertising capabilities: {err}",
+                    id = self.id(),
+                    addr = self.inner.remote_address(),
+                    user = self.user(),
+                );
+            }
+        }
     }
 
-    pub async fn handle_connect(&self, conn: Connect) {
+    pub async fn handle_connect(&self, mut conn: Connect) {
         let target_addr = conn.addr().to_string();
 
         log::info!(
             "[{id:#010x}] [{addr}] [{user}] [connect] {target_addr}",
             id = self.id(),
             addr = self.inner.remote_address(),
-            user = self.auth,
+            user = self.user(),
         );
 
         let process = async {
             let mut stream = None;
             let mut last_err = None;
+            let mut status = ConnectStatus::DnsFailure;
 
             match resolve_dns(conn.addr()).await {
                 Ok(addrs) => {
@@ -45,24 +79,44 @@ impl Connection {
                         match TcpStream::connect(addr).await {
                             Ok(s) => {
                                 stream = Some(s);
+                                status = ConnectStatus::Connected;
                                 break;
                             }
-                            Err(err) => last_err = Some(err),
+                            Err(err) => {
+                                status = connect_status(&err);
+                                last_err = Some(err);
+                            }
                         }
                     }
                 }
                 Err(err) => last_err = Some(err),
             }
 
+            // the client may be holding its application back until it knows the outcome
+            let is_result_requested = conn.is_result_pending();
+            conn.respond(status).await?;
+
             if let Some(mut stream) = stream {
                 let mut conn = conn.compat();
                 let res = io::copy_bidirectional(&mut conn, &mut stream).await;
-                let _ = conn.get_mut().reset(ERROR_CODE);
+                let _ = conn.get_mut().reset(ErrorCode::Unspecified);
                 let _ = stream.shutdown().await;
                 res?;
                 Ok::<_, Error>(())
             } else {
-                let _ = conn.compat().shutdown().await;
+                let error_code = match status {
+                    ConnectStatus::Denied => ErrorCode::PolicyDenied,
+                    _ => ErrorCode::TargetUnreachable,
+                };
+                if is_result_requested {
+                    // the client may still be sending data for the target
+                    let (mut read, mut write) = conn.into_split();
+                    let _ = write.close().await;
+                    let _ = read.stop(error_code);
+                } else {
+                    // without a result, a clean end of the stream would read as a relay closed by the target
+                    let _ = conn.reset(error_code);
+                }
                 Err(last_err
                     .unwrap_or_else(|| IoError::new(ErrorKind::NotFound, "no address resolved")))?
             }
@@ -74,7 +128,7 @@ impl Connection {
                 "[{id:#010x}] [{addr}] [{user}] [connect] {target_addr}: {err}",
                 id = self.id(),
                 addr = self.inner.remote_address(),
-                user = self.auth,
+                user = self.user(),
             ),
         }
     }
@@ -89,21 +143,41 @@ impl Connection {
             "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] fragment {frag_id}/{frag_total}",
             id = self.id(),
             addr = self.inner.remote_address(),
-            user = self.auth,
+            user = self.user(),
             frag_id = frag_id + 1,
         );
 
-        self.udp_relay_mode.store(Some(mode));
+        // packets of a UDP session in mode `stream` must all come from its stream, as the replies are sent back on it
+        let session = self.udp_sessions.read().await.get(&assoc_id).cloned();
+        if session.is_some_and(|session| {
+            (session.relay_mode() == UdpRelayMode::Stream) != (mode == UdpRelayMode::Stream)
+        }) {
+            log::warn!(
+                "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] fragment {frag_id}/{frag_total}: {err}",
+                id = self.id(),
+                addr = self.inner.remote_address(),
+                user = self.user(),
+                frag_id = frag_id + 1,
+                err = Error::UnexpectedPacketSource,
+            );
+            return;
+        }
 
         let (pkt, addr, assoc_id) = match pkt.accept().await {
             Ok(None) => return,
             Ok(Some(res)) => res,
             Err(err) => {
-                log::warn!(
+                // every packet of a UDP session over the limit is rejected, too many for warnings
+                let level = match err {
+                    ModelError::Assemble(AssembleError::TooManySessions(_)) => Level::Debug,
+                    _ => Level::Warn,
+                };
+                log::log!(
+                    level,
                     "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] fragment {frag_id}/{frag_total}: {err}",
                     id = self.id(),
                     addr = self.inner.remote_address(),
-                    user = self.auth,
+                    user = self.user(),
                     frag_id = frag_id + 1,
                 );
                 return;
@@ -115,7 +189,7 @@ impl Connection {
                 "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] to {src_addr}",
                 id = self.id(),
                 addr = self.inner.remote_address(),
-                user = self.auth,
+                user = self.user(),
                 src_addr = addr,
             );
 
@@ -124,21 +198,33 @@ impl Connection {
             drop(guard);
             let session = match session {
                 Some(v) => v,
-                None => match self.udp_sessions.write().await.entry(assoc_id) {
-                    Entry::Occupied(entry) => entry.get().clone(),
-                    Entry::Vacant(entry) => {
-                        let session = UdpSession::new(
-                            self.clone(),
-                            assoc_id,
-                            self.udp_relay_ipv6,
-                            self.max_external_pkt_size,
-                        )?;
-                        entry.insert(session.clone());
-                        session
+                None => {
+                    let mut sessions = self.udp_sessions.write().await;
+                    // the number of sessions is already limited by the reassembly of `accept()`
+                    match sessions.get(&assoc_id) {
+                        Some(session) => session.clone(),
+                        None => {
+                            let session = UdpSession::new(
+                                self.clone(),
+                                assoc_id,
+                                mode,
+                                self.udp_relay_ipv6,
+                                self.max_external_pkt_size,
+                            )
+                            .inspect_err(|_| {
+                                // without a relay, the session would never be closed by the idle timeout
+                                self.model.close_udp_session(assoc_id);
+                            })?;
+                            sessions.insert(assoc_id, session.clone());
+                            session
+                        }
                     }
-                },
+                }
             };
 
+            // replies use the mode of the last packet, as clients in mode `auto` mix native and quic
+            session.set_relay_mode(mode);
+
             let Some(socket_addr) = resolve_dns(&addr).await?.next() else {
                 return Err(Error::from(IoError::new(
                     ErrorKind::NotFound,
@@ -154,18 +240,54 @@ impl Connection {
                 "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-{mode}] [{pkt_id:#06x}] to {src_addr}: {err}",
                 id = self.id(),
                 addr = self.inner.remote_address(),
-                user = self.auth,
+                user = self.user(),
                 src_addr = addr,
             );
         }
     }
 
+    pub async fn handle_packet_stream(&self, stream: PacketStream) {
+        let assoc_id = stream.assoc_id();
+
+        log::info!(
+            "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-stream] stream opened",
+            id = self.id(),
+            addr = self.inner.remote_address(),
+            user = self.user(),
+        );
+
+        let (send, mut recv) = stream.into_split();
+        self.packet_streams
+            .write()
+            .await
+            .insert(assoc_id, Arc::new(AsyncMutex::new(send)));
+
+        // packets are handled one by one to keep their order
+        loop {
+            match recv.recv().await {
+                Ok(Some(pkt)) => self.handle_packet(pkt, UdpRelayMode::Stream).await,
+                Ok(None) => break,
+                Err(err) => {
+                    log::warn!(
+                        "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [from-stream] packet receiving error: {err}",
+                        id = self.id(),
+                        addr = self.inner.remote_address(),
+                        user = self.user(),
+                    );
+                    break;
+                }
+            }
+        }
+
+        self.packet_streams.write().await.remove(&assoc_id);
+    }
+
     pub async fn handle_dissociate(&self, assoc_id: u16) {
         log::info!(
             "[{id:#010x}] [{addr}] [{user}] [dissociate] [{assoc_id:#06x}]",
             id = self.id(),
             addr = self.inner.remote_address(),
-            user = self.auth,
+            user = self.user(),
         );
 
         if let Some(session) = self.udp_sessions.write().await.remove(&assoc_id) {
@@ -178,25 +300,49 @@ impl Connection {
             "[{id:#010x}] [{addr}] [{user}] [heartbeat]",
             id = self.id(),
             addr = self.inner.remote_address(),
-            user = self.auth,
+            user = self.user(),
+        );
+    }
+
+    pub async fn handle_extension(&self, ext: Extension) {
+        // no extension is supported yet, dropping the streams it came with
+        log::debug!(
+            "[{id:#010x}] [{addr}] [{user}] [extension] [{type_code:#04x}] ignoring unknown extension",
+            id = self.id(),
+            addr = self.inner.remote_address(),
+            user = self.user(),
+            type_code = ext.type_code(),
         );
     }
 
-    pub async fn relay_packet(self, pkt: Bytes, addr: Address, assoc_id: u16) {
+    pub async fn relay_packet(self, pkt: Bytes, addr: Address, assoc_id: u16, mode: UdpRelayMode) {
         let addr_display = addr.to_string();
 
         log::info!(
             "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [to-{mode}] from {src_addr}",
             id = self.id(),
             addr = self.inner.remote_address(),
-            user = self.auth,
-            mode = self.udp_relay_mode.load().unwrap(),
+            user = self.user(),
             src_addr = addr_display,
         );
 
-        let res = match self.udp_relay_mode.load().unwrap() {
-            UdpRelayMode::Native => self.model.packet_native(pkt, addr, assoc_id),
-            UdpRelayMode::Quic => self.model.packet_quic(pkt, addr, assoc_id).await,
+        let res = match mode {
+            UdpRelayMode::Native => self
+                .model
+                .packet_native(pkt, addr, assoc_id)
+                .map_err(Error::from),
+            UdpRelayMode::Quic => self
+                .model
+                .packet_quic(pkt, addr, assoc_id)
+                .await
+                .map_err(Error::from),
+            UdpRelayMode::Stream => {
+                let send = self.packet_streams.read().await.get(&assoc_id).cloned();
+                match send {
+                    Some(send) => send.lock().await.send(pkt, addr).await.map_err(Error::from),
+                    None => Err(Error::NoPacketStream(assoc_id)),
+                }
+            }
         };
 
         if let Err(err) = res {
@@ -204,14 +350,23 @@ impl Connection {
                 "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] [to-{mode}] from {src_addr}: {err}",
                 id = self.id(),
                 addr = self.inner.remote_address(),
-                user = self.auth,
-                mode = self.udp_relay_mode.load().unwrap(),
-                src_addr = addr_display,
+                user = self.user(),
+                    src_addr = addr_display,
             );
         }
     }
 }
 
+fn connect_status(err: &IoError) -> ConnectStatus {
+    match err.kind() {
+        ErrorKind::ConnectionRefused => ConnectStatus::Refused,
+        ErrorKind::TimedOut => ConnectStatus::Timeout,
+        ErrorKind::PermissionDenied => ConnectStatus::Denied,
+        // `ErrorKind::{HostUnreachable, NetworkUnreachable}` are newer than the minimum supported Rust version
+        _ => ConnectStatus::Unreachable,
+    }
+}
+
 async fn resolve_dns(addr: &Address) -> Result<impl Iterator<Item = SocketAddr>, IoError> {
     match addr {
         Address::None => Err(IoError::new(ErrorKind::InvalidInput, "empty address")),
@@ -1,41 +1,36 @@
-use self::{authenticated::Authenticated, udp_session::UdpSession};
-use crate::{error::Error, utils::UdpRelayMode};
-use crossbeam_utils::atomic::AtomicCell;
-use quinn::{Incoming, Connection as QuinnConnection, VarInt};
-use register_count::Counter;
+use self::udp_session::UdpSession;
+use crate::error::Error;
+use futures_util::StreamExt;
+use quinn::{Incoming, Connection as QuinnConnection};
 use std::{
     collections::HashMap,
-    sync::{atomic::AtomicU32, Arc},
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::Arc,
     time::Duration,
 };
-use tokio::sync::RwLock as AsyncRwLock;
+use tokio::sync::{watch::Receiver as WatchReceiver, Mutex as AsyncMutex, RwLock as AsyncRwLock};
 use tokio::time;
-use tuic_quinn::{side, Authenticate, Connection as Model};
+use tuic::model::ReassemblyLimits;
+use tuic_quinn::{
+    side, Connection as Model, Error as ModelError, ErrorCode, PacketSender, PaddingPolicy,
+};
 use uuid::Uuid;
 
-mod authenticated;
-mod handle_stream;
 mod handle_task;
 mod udp_session;
 
-pub const ERROR_CODE: VarInt = VarInt::from_u32(0);
 pub const DEFAULT_CONCURRENT_STREAMS: u32 = 32;
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Clone)]
 pub struct Connection {
     inner: QuinnConnection,
     model: Model<side::Server>,
-    users: Arc<HashMap<Uuid, Box<[u8]>>>,
     udp_relay_ipv6: bool,
-    auth: Authenticated,
-    task_negotiation_timeout: Duration,
     udp_sessions: Arc<AsyncRwLock<HashMap<u16, UdpSession>>>,
-    udp_relay_mode: Arc<AtomicCell<Option<UdpRelayMode>>>,
+    packet_streams: Arc<AsyncRwLock<HashMap<u16, Arc<AsyncMutex<PacketSender>>>>>,
     max_external_pkt_size: usize,
-    remote_uni_stream_cnt: Counter,
-    remote_bi_stream_cnt: Counter,
-    max_concurrent_uni_streams: Arc<AtomicU32>,
-    max_concurrent_bi_streams: Arc<AtomicU32>,
+    udp_session_idle_timeout: Duration,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -48,8 +43,12 @@ impl Connection {
         auth_timeout: Duration,
         task_negotiation_timeout: Duration,
         max_external_pkt_size: usize,
+        udp_session_idle_timeout: Duration,
+        reassembly_limits: ReassemblyLimits,
         gc_interval: Duration,
         gc_lifetime: Duration,
+        padding: PaddingPolicy,
+        shutdown: WatchReceiver<bool>,
     ) {
         let addr = handshake.remote_address();
         let init = async {
@@ -66,8 +65,12 @@ impl Connection {
                 conn,
                 users,
                 udp_relay_ipv6,
+                auth_timeout,
                 task_negotiation_timeout,
                 max_external_pkt_size,
+                udp_session_idle_timeout,
+                reassembly_limits,
+                padding,
             ))
         };
 
@@ -76,44 +79,63 @@ impl Connection {
                 log::info!(
                     "[{id:#010x}] [{addr}] [{user}] connection established",
                     id = conn.id(),
-                    user = conn.auth,
+                    user = conn.user(),
                 );
 
-                tokio::spawn(conn.clone().timeout_authenticate(auth_timeout));
                 tokio::spawn(conn.clone().collect_garbage(gc_interval, gc_lifetime));
+                tokio::spawn(conn.clone().drain_on_shutdown(shutdown));
 
-                loop {
-                    if conn.is_closed() {
-                        break;
-                    }
+                let mut tasks = conn.model.tasks();
 
-                    let handle_incoming = async {
-                        tokio::select! {
-                            res = conn.inner.accept_uni() =>
-                                tokio::spawn(conn.clone().handle_uni_stream(res?, conn.remote_uni_stream_cnt.reg())),
-                            res = conn.inner.accept_bi() =>
-                                tokio::spawn(conn.clone().handle_bi_stream(res?, conn.remote_bi_stream_cnt.reg())),
-                            res = conn.inner.read_datagram() =>
-                                tokio::spawn(conn.clone().handle_datagram(res?)),
-                        };
-
-                        Ok::<_, Error>(())
-                    };
-
-                    match handle_incoming.await {
-                        Ok(()) => {}
-                        Err(err) if err.is_trivial() => {
-                            log::debug!(
+                while let Some(res) = tasks.next().await {
+                    match res {
+                        Ok(task) => {
+                            tokio::spawn(conn.clone().handle_task(task));
+                        }
+                        Err(ModelError::Connection(err)) => match Error::from(err) {
+                            err if err.is_trivial() => log::debug!(
                                 "[{id:#010x}] [{addr}] [{user}] {err}",
                                 id = conn.id(),
-                                user = conn.auth,
+                                user = conn.user(),
+                            ),
+                            err => log::warn!(
+                                "[{id:#010x}] [{addr}] [{user}] connection error: {err}",
+                                id = conn.id(),
+                                user = conn.user(),
+                            ),
+                        },
+                        Err(err @ (ModelError::DuplicatedAuth | ModelError::AuthFailed(_))) => {
+                            log::warn!(
+                                "[{id:#010x}] [{addr}] [{user}] [authenticate] {err}",
+                                id = conn.id(),
+                                user = conn.user(),
+                            );
+                            conn.close(ErrorCode::AuthFailed);
+                        }
+                        Err(err @ ModelError::AuthTimeout) => {
+                            log::warn!(
+                                "[{id:#010x}] [{addr}] [{user}] [authenticate] {err}",
+                                id = conn.id(),
+                                user = conn.user(),
+                            );
+                            conn.close(ErrorCode::AuthTimeout);
+                        }
+                        Err(err) if err.is_bad_command() => {
+                            log::warn!(
+                                "[{id:#010x}] [{addr}] [{user}] handling incoming task error: {err}",
+                                id = conn.id(),
+                                user = conn.user(),
+                            );
+                            conn.close(ErrorCode::BadCommand);
+                        }
+                        // only the stream the task came on is dropped
+                        Err(err) => {
+                            log::warn!(
+                                "[{id:#010x}] [{addr}] [{user}] handling incoming task error: {err}",
+                                id = conn.id(),
+                                user = conn.user(),
                             );
                         }
-                        Err(err) => log::warn!(
-                            "[{id:#010x}] [{addr}] [{user}] connection error: {err}",
-                            id = conn.id(),
-                            user = conn.auth,
-                        ),
                     }
                 }
             }
@@ -136,51 +158,33 @@ impl Connection {
         conn: QuinnConnection,
         users: Arc<HashMap<Uuid, Box<[u8]>>>,
         udp_relay_ipv6: bool,
+        auth_timeout: Duration,
         task_negotiation_timeout: Duration,
         max_external_pkt_size: usize,
+        udp_session_idle_timeout: Duration,
+        reassembly_limits: ReassemblyLimits,
+        padding: PaddingPolicy,
     ) -> Self {
+        let model = Model::<side::Server>::new(conn.clone())
+            .with_padding(padding)
+            .with_max_concurrent_streams(DEFAULT_CONCURRENT_STREAMS)
+            .with_task_negotiation_timeout(task_negotiation_timeout)
+            .with_auth_timeout(auth_timeout)
+            .with_authenticator(move |auth| {
+                users
+                    .get(&auth.uuid())
+                    .is_some_and(|password| auth.validate(password))
+            });
+        model.set_reassembly_limits(reassembly_limits);
+
         Self {
-            inner: conn.clone(),
-            model: Model::<side::Server>::new(conn),
-            users,
+            inner: conn,
+            model,
             udp_relay_ipv6,
-            auth: Authenticated::new(),
-            task_negotiation_timeout,
             udp_sessions: Arc::new(AsyncRwLock::new(HashMap::new())),
-            udp_relay_mode: Arc::new(AtomicCell::new(None)),
+            packet_streams: Arc::new(AsyncRwLock::new(HashMap::new())),
             max_external_pkt_size,
-            remote_uni_stream_cnt: Counter::new(),
-            remote_bi_stream_cnt: Counter::new(),
-            max_concurrent_uni_streams: Arc::new(AtomicU32::new(DEFAULT_CONCURRENT_STREAMS)),
-            max_concurrent_bi_streams: Arc::new(AtomicU32::new(DEFAULT_CONCURRENT_STREAMS)),
-        }
-    }
-
-    async fn authenticate(&self, auth: &Authenticate) -> Result<(), Error> {
-        if self.auth.get().is_some() {
-            Err(Error::DuplicatedAuth)
-        } else if self
-            .users
-            .get(&auth.uuid())
-            .map_or(false, |password| auth.validate(password))
-        {
-            self.auth.set(auth.uuid()).await;
-            Ok(())
-        } else {
-            Err(Error::AuthFailed(auth.uuid()))
-        }
-    }
-
-    async fn timeout_authenticate(self, timeout: Duration) {
-        time::sleep(timeout).await;
-
-        if self.auth.get().is_none() {
-            log::warn!(
-                "[{id:#010x}] [{addr}] [unauthenticated] [authenticate] timeout",
-                id = self.id(),
-                addr = self.inner.remote_address(),
-            );
-            self.close();
+            udp_session_idle_timeout,
         }
     }
 
@@ -196,12 +200,47 @@ impl Connection {
                 "[{id:#010x}] [{addr}] [{user}] packet fragment garbage collecting event",
                 id = self.id(),
                 addr = self.inner.remote_address(),
-                user = self.auth,
+                user = self.user(),
             );
             self.model.collect_garbage(gc_lifetime);
         }
     }
 
+    /// Refuses new TCP relays and UDP sessions once the server is shutting down, and closes the connection after the existing ones finish.
+    ///
+    /// UDP sessions keep the connection open as TCP relays do, since their packets are still relayed while draining. They end when the client dissociates them or they idle out, and the server closes the connections still open after `drain_timeout`
+    async fn drain_on_shutdown(self, mut shutdown: WatchReceiver<bool>) {
+        tokio::select! {
+            res = shutdown.wait_for(|is_shutdown| *is_shutdown) => {
+                if res.is_err() {
+                    return;
+                }
+            }
+            _ = self.inner.closed() => return,
+        }
+
+        log::info!(
+            "[{id:#010x}] [{addr}] [{user}] draining connection",
+            id = self.id(),
+            addr = self.inner.remote_address(),
+            user = self.user(),
+        );
+        self.model.drain();
+
+        while self.model.task_connect_count() > 0 || self.model.task_associate_count() > 0 {
+            if self.is_closed() {
+                return;
+            }
+            time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+
+        self.close(ErrorCode::ServerShutdown);
+    }
+
+    fn user(&self) -> User {
+        User(self.model.authenticated())
+    }
+
     fn id(&self) -> u32 {
         self.inner.stable_id() as u32
     }
@@ -210,7 +249,20 @@ impl Connection {
         self.inner.close_reason().is_some()
     }
 
-    fn close(&self) {
-        self.inner.close(ERROR_CODE, &[]);
+    fn close(&self, error_code: ErrorCode) {
+        self.model.close(error_code);
+    }
+}
+
+/// The UUID of the authenticated user, for logging
+struct User(Option<Uuid>);
+
+impl Display for User {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if let Some(uuid) = self.0 {
+            write!(f, "{uuid}")
+        } else {
+            write!(f, "unauthenticated")
+        }
     }
 }
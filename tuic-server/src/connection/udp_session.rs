@@ -1,6 +1,7 @@
 use super::Connection;
-use crate::error::Error;
+use crate::{error::Error, utils::UdpRelayMode};
 use bytes::Bytes;
+use crossbeam_utils::atomic::AtomicCell;
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use std::{
     io::Error as IoError,
@@ -11,6 +12,7 @@ use tokio::sync::RwLock as AsyncRwLock;
 use tokio::{
     net::UdpSocket,
     sync::oneshot::{self, Sender},
+    time::{self, Instant},
 };
 use tuic::Address;
 
@@ -20,9 +22,11 @@ pub struct UdpSession(Arc<UdpSessionInner>);
 struct UdpSessionInner {
     assoc_id: u16,
     conn: Connection,
+    relay_mode: AtomicCell<UdpRelayMode>,
     socket_v4: UdpSocket,
     socket_v6: Option<UdpSocket>,
     max_pkt_size: usize,
+    last_active: AtomicCell<Instant>,
     close: AsyncRwLock<Option<Sender<()>>>,
 }
 
@@ -30,6 +34,7 @@ impl UdpSession {
     pub fn new(
         conn: Connection,
         assoc_id: u16,
+        relay_mode: UdpRelayMode,
         udp_relay_ipv6: bool,
         max_pkt_size: usize,
     ) -> Result<Self, Error> {
@@ -86,9 +91,11 @@ impl UdpSession {
         let session = Self(Arc::new(UdpSessionInner {
             conn,
             assoc_id,
+            relay_mode: AtomicCell::new(relay_mode),
             socket_v4,
             socket_v6,
             max_pkt_size,
+            last_active: AtomicCell::new(Instant::now()),
             close: AsyncRwLock::new(Some(tx)),
         }));
 
@@ -102,30 +109,66 @@ impl UdpSession {
                             "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] outbound listening error: {err}",
                             id = session_listening.0.conn.id(),
                             addr = session_listening.0.conn.inner.remote_address(),
-                            user = session_listening.0.conn.auth,
+                            user = session_listening.0.conn.user(),
                         );
                         continue;
                     }
                 };
 
+                session_listening.touch();
+
                 tokio::spawn(session_listening.0.conn.clone().relay_packet(
                     pkt,
                     Address::SocketAddress(addr),
                     session_listening.0.assoc_id,
+                    session_listening.relay_mode(),
                 ));
             }
         };
 
+        // sessions the client never dissociates are closed when idle or when the connection closes
+        let session_closing = session.clone();
         tokio::spawn(async move {
-            tokio::select! {
+            let conn = &session_closing.0.conn;
+
+            let reason = tokio::select! {
                 _ = listen => unreachable!(),
-                _ = rx => {},
+                _ = rx => return,
+                () = session_closing.idle() => "idle timeout",
+                _ = conn.inner.closed() => "connection closed",
+            };
+
+            // the reassembly state of the session is dropped as a `Dissociate` from the client would
+            let mut sessions = conn.udp_sessions.write().await;
+            if sessions
+                .get(&session_closing.0.assoc_id)
+                .is_some_and(|session| Arc::ptr_eq(&session.0, &session_closing.0))
+            {
+                sessions.remove(&session_closing.0.assoc_id);
+                conn.model.close_udp_session(session_closing.0.assoc_id);
             }
+            drop(sessions);
+
+            log::info!(
+                "[{id:#010x}] [{addr}] [{user}] [packet] [{assoc_id:#06x}] UDP session closed: {reason}",
+                id = conn.id(),
+                addr = conn.inner.remote_address(),
+                user = conn.user(),
+            );
         });
 
         Ok(session)
     }
 
+    /// Returns the UDP relay mode for sending back packets, which is the mode of the last packet from the client
+    pub fn relay_mode(&self) -> UdpRelayMode {
+        self.0.relay_mode.load()
+    }
+
+    pub fn set_relay_mode(&self, mode: UdpRelayMode) {
+        self.0.relay_mode.store(mode);
+    }
+
     pub async fn send(&self, pkt: Bytes, addr: SocketAddr) -> Result<(), Error> {
         let socket = match addr {
             SocketAddr::V4(_) => &self.0.socket_v4,
@@ -137,9 +180,27 @@ impl UdpSession {
         };
 
         socket.send_to(&pkt, addr).await?;
+        self.touch();
         Ok(())
     }
 
+    fn touch(&self) {
+        self.0.last_active.store(Instant::now());
+    }
+
+    /// Resolves once no packet has been relayed for the idle timeout
+    async fn idle(&self) {
+        loop {
+            let deadline = self.0.last_active.load() + self.0.conn.udp_session_idle_timeout;
+
+            if Instant::now() >= deadline {
+                break;
+            }
+
+            time::sleep_until(deadline).await;
+        }
+    }
+
     async fn recv(&self) -> Result<(Bytes, SocketAddr), IoError> {
         async fn recv(
             socket: &UdpSocket,
@@ -162,6 +223,8 @@ impl UdpSession {
     }
 
     pub async fn close(&self) {
-        let _ = self.0.close.write().await.take().unwrap().send(());
+        if let Some(close) = self.0.close.write().await.take() {
+            let _ = close.send(());
+        }
     }
 }
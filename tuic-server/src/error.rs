@@ -3,7 +3,6 @@ use rustls::Error as RustlsError;
 use std::{io::Error as IoError, net::SocketAddr};
 use thiserror::Error;
 use tuic_quinn::Error as ModelError;
-use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -19,16 +18,12 @@ pub enum Error {
     LocallyClosed,
     #[error(transparent)]
     Model(#[from] ModelError),
-    #[error("duplicated authentication")]
-    DuplicatedAuth,
-    #[error("authentication failed: {0}")]
-    AuthFailed(Uuid),
     #[error("received packet from unexpected source")]
     UnexpectedPacketSource,
+    #[error("no packet stream for udp session {0:#06x}")]
+    NoPacketStream(u16),
     #[error("{0}: {1}")]
     Socket(&'static str, IoError),
-    #[error("task negotiation timed out")]
-    TaskNegotiationTimeout,
     #[error("failed sending packet to {0}: relaying IPv6 UDP packet is disabled")]
     UdpRelayIpv6Disabled(SocketAddr),
     #[error("other error")]
@@ -10,10 +10,15 @@ use rustls::{version, ServerConfig as RustlsServerConfig};
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use std::{
     collections::HashMap,
+    future,
+    io::Error as IoError,
     net::{SocketAddr, UdpSocket as StdUdpSocket},
     sync::Arc,
     time::Duration,
 };
+use tokio::{signal, sync::watch, time};
+use tuic::model::ReassemblyLimits;
+use tuic_quinn::{ErrorCode, PaddingPolicy};
 use uuid::Uuid;
 
 pub struct Server {
@@ -24,8 +29,12 @@ pub struct Server {
     auth_timeout: Duration,
     task_negotiation_timeout: Duration,
     max_external_pkt_size: usize,
+    udp_session_idle_timeout: Duration,
+    reassembly_limits: ReassemblyLimits,
     gc_interval: Duration,
     gc_lifetime: Duration,
+    padding: PaddingPolicy,
+    drain_timeout: Duration,
 }
 
 impl Server {
@@ -109,8 +118,12 @@ impl Server {
             auth_timeout: cfg.auth_timeout,
             task_negotiation_timeout: cfg.task_negotiation_timeout,
             max_external_pkt_size: cfg.max_external_packet_size,
+            udp_session_idle_timeout: cfg.udp_session_idle_timeout,
+            reassembly_limits: cfg.udp_reassembly.limits(cfg.max_udp_sessions),
             gc_interval: cfg.gc_interval,
             gc_lifetime: cfg.gc_lifetime,
+            padding: PaddingPolicy::from(cfg.padding),
+            drain_timeout: cfg.drain_timeout,
         })
     }
 
@@ -120,8 +133,22 @@ impl Server {
             self.ep.local_addr().unwrap()
         );
 
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let shutdown = async {
+            if let Err(err) = shutdown_signal().await {
+                log::warn!("failed listening for shutdown signals: {err}");
+                future::pending::<()>().await;
+            }
+        };
+        tokio::pin!(shutdown);
+
         loop {
-            let Some(handshake) = self.ep.accept().await else {
+            let handshake = tokio::select! {
+                handshake = self.ep.accept() => handshake,
+                () = &mut shutdown => break,
+            };
+            let Some(handshake) = handshake else {
                 return;
             };
             tokio::spawn(Connection::handle(
@@ -132,9 +159,47 @@ impl Server {
                 self.auth_timeout,
                 self.task_negotiation_timeout,
                 self.max_external_pkt_size,
+                self.udp_session_idle_timeout,
+                self.reassembly_limits,
                 self.gc_interval,
                 self.gc_lifetime,
+                self.padding.clone(),
+                shutdown_rx.clone(),
             ));
         }
+
+        log::warn!("shutting down, draining connections");
+
+        // refuses new handshakes, connections close themselves once their TCP relays and UDP sessions finish
+        self.ep.set_server_config(None);
+        shutdown_tx.send_replace(true);
+
+        if time::timeout(self.drain_timeout, self.ep.wait_idle())
+            .await
+            .is_err()
+        {
+            log::warn!("drain timed out, closing remaining connections");
+            let code = ErrorCode::ServerShutdown;
+            self.ep.close(VarInt::from(code), code.to_string().as_bytes());
+            self.ep.wait_idle().await;
+        }
+
+        log::warn!("server stopped");
+    }
+}
+
+/// Resolves on SIGTERM or SIGINT, or on Ctrl-C on non-unix platforms
+async fn shutdown_signal() -> Result<(), IoError> {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+
+        tokio::select! {
+            _ = terminate.recv() => Ok(()),
+            res = signal::ctrl_c() => res,
+        }
     }
+
+    #[cfg(not(unix))]
+    signal::ctrl_c().await
 }
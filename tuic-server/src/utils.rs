@@ -53,10 +53,11 @@ pub fn load_priv_key(filename: PathBuf) -> Result<PrivateKeyDer<'static>, IoErro
 
 
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub enum UdpRelayMode {
     Native,
     Quic,
+    Stream,
 }
 
 impl Display for UdpRelayMode {
@@ -64,6 +65,7 @@ impl Display for UdpRelayMode {
         match self {
             Self::Native => write!(f, "native"),
             Self::Quic => write!(f, "quic"),
+            Self::Stream => write!(f, "stream"),
         }
     }
 }
@@ -0,0 +1,131 @@
+// each test crate uses only some of the helpers
+#![allow(dead_code)]
+
+use quinn::{
+    crypto::rustls::QuicClientConfig, ClientConfig, Connection as QuinnConnection, Endpoint,
+};
+use rustls::{
+    pki_types::CertificateDer, version, ClientConfig as RustlsClientConfig, RootCertStore,
+};
+use serde_json::Value;
+use std::{
+    env, fs,
+    net::{Ipv4Addr, SocketAddr, UdpSocket as StdUdpSocket},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time;
+use tuic_quinn::{side, Connection};
+use uuid::Uuid;
+
+/// A `tuic-server` process with a single user of UUID nil and password `password`, killed on drop
+pub struct Server {
+    child: Child,
+    addr: SocketAddr,
+    cert: CertificateDer<'static>,
+    dir: PathBuf,
+}
+
+impl Server {
+    /// Spawns the server with the given config fields on top of the required ones
+    pub fn spawn(name: &str, config: Value) -> Self {
+        let dir = env::temp_dir().join(format!("tuic-server-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        fs::write(dir.join("cert.pem"), cert.cert.pem()).unwrap();
+        fs::write(dir.join("key.pem"), cert.key_pair.serialize_pem()).unwrap();
+
+        // the port is free once the socket is dropped
+        let addr = StdUdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut base = serde_json::json!({
+            "server": addr,
+            "users": { Uuid::nil().to_string(): "password" },
+            "certificate": dir.join("cert.pem"),
+            "private_key": dir.join("key.pem"),
+            "log_level": "off",
+        });
+        if let Value::Object(config) = config {
+            base.as_object_mut().unwrap().extend(config);
+        }
+        fs::write(dir.join("config.json"), base.to_string()).unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_tuic-server"))
+            .arg("-c")
+            .arg(dir.join("config.json"))
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        Self {
+            child,
+            addr,
+            cert: CertificateDer::from(cert.cert),
+            dir,
+        }
+    }
+
+    /// Connects and authenticates to the server. The endpoint must be kept alive along with the connection
+    pub async fn connect(&self) -> (Endpoint, QuinnConnection, Connection<side::Client>) {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.cert.clone()).unwrap();
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let crypto = RustlsClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&version::TLS13])
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let mut ep = Endpoint::client((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        ep.set_default_client_config(ClientConfig::new(Arc::new(
+            QuicClientConfig::try_from(crypto).unwrap(),
+        )));
+
+        // the port is taken once the server is listening
+        for _ in 0..50 {
+            if StdUdpSocket::bind(self.addr).is_err() {
+                let conn = ep.connect(self.addr, "localhost").unwrap().await.unwrap();
+                let client = Connection::<side::Client>::new(conn.clone());
+                client.authenticate(Uuid::nil(), b"password").await.unwrap();
+                return (ep, conn, client);
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("the server is not listening");
+    }
+
+    /// Sends SIGTERM to the server
+    pub fn terminate(&self) {
+        let status = Command::new("kill")
+            .arg("-TERM")
+            .arg(self.child.id().to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Waits for the server to exit, returning `true` if it exited successfully
+    pub async fn wait(mut self) -> bool {
+        for _ in 0..50 {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status.success();
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("the server did not exit");
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
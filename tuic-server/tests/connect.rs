@@ -0,0 +1,29 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Server;
+use futures_util::AsyncReadExt;
+use std::{io::ErrorKind, net::Ipv4Addr};
+use tokio::net::TcpListener;
+use tuic::Address;
+
+#[tokio::test]
+async fn unreachable_target() {
+    let server = Server::spawn("connect", serde_json::json!({}));
+
+    // the port is closed once the listener is dropped
+    let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (_ep, _conn, client) = server.connect().await;
+
+    // without a requested result, the failure is told by resetting the stream
+    let mut relay = client.connect(Address::SocketAddress(addr)).await.unwrap();
+    assert_eq!(relay.result().await.unwrap(), None);
+    let mut buf = Vec::new();
+    let err = relay.read_to_end(&mut buf).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+}
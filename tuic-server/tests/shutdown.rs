@@ -0,0 +1,111 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Server;
+use quinn::{ConnectionError, VarInt};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    time,
+};
+use tuic::Address;
+use tuic_quinn::{Compat, Connect, ErrorCode};
+
+/// Echoes everything received on the first accepted TCP connection
+async fn echo() -> SocketAddr {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 1024];
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(len) => stream.write_all(&buf[..len]).await.unwrap(),
+            }
+        }
+    });
+
+    addr
+}
+
+async fn ping(relay: &mut Compat<Connect>) {
+    relay.write_all(b"ping").await.unwrap();
+    let mut buf = [0; 4];
+    relay.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+}
+
+fn is_shutdown(err: ConnectionError) -> bool {
+    matches!(
+        err,
+        ConnectionError::ApplicationClosed(close)
+            if close.error_code == VarInt::from(ErrorCode::ServerShutdown)
+    )
+}
+
+#[tokio::test]
+async fn drain_timeout() {
+    let server = Server::spawn(
+        "drain-timeout",
+        serde_json::json!({ "drain_timeout": "1s" }),
+    );
+    let target = Address::SocketAddress(echo().await);
+    let (_ep, conn, client) = server.connect().await;
+
+    let mut relay = client.connect(target.clone()).await.unwrap().compat();
+    ping(&mut relay).await;
+
+    server.terminate();
+    time::sleep(Duration::from_millis(300)).await;
+
+    // new relays are refused, while the existing one keeps going
+    let mut refused = client.connect(target).await.unwrap().compat();
+    let mut buf = [0; 4];
+    assert!(refused.read(&mut buf).await.is_err());
+    ping(&mut relay).await;
+
+    // until the drain times out
+    let err = time::timeout(Duration::from_secs(3), conn.closed())
+        .await
+        .unwrap();
+    assert!(is_shutdown(err));
+    assert!(server.wait().await);
+}
+
+#[tokio::test]
+async fn drain() {
+    let server = Server::spawn("drain", serde_json::json!({ "drain_timeout": "30s" }));
+    let target = Address::SocketAddress(echo().await);
+    let udp_target = Address::SocketAddress(SocketAddr::from((Ipv4Addr::LOCALHOST, 9)));
+    let (_ep, conn, client) = server.connect().await;
+
+    let mut relay = client.connect(target).await.unwrap().compat();
+    ping(&mut relay).await;
+    client.packet_quic(b"data", udp_target, 0).await.unwrap();
+    time::sleep(Duration::from_millis(300)).await;
+
+    server.terminate();
+    time::sleep(Duration::from_millis(300)).await;
+
+    // the connection is kept open by the TCP relay
+    relay.shutdown().await.unwrap();
+    drop(relay);
+    time::sleep(Duration::from_millis(300)).await;
+
+    // and then by the UDP session
+    assert!(conn.close_reason().is_none());
+    client.dissociate(0).await.unwrap();
+
+    let err = time::timeout(Duration::from_secs(3), conn.closed())
+        .await
+        .unwrap();
+    assert!(is_shutdown(err));
+    assert!(server.wait().await);
+}
@@ -0,0 +1,77 @@
+#![cfg(unix)]
+
+mod common;
+
+use bytes::BytesMut;
+use common::Server;
+use std::{net::Ipv4Addr, time::Duration};
+use tokio::{net::UdpSocket, time};
+use tuic::{Address, Header, Packet};
+
+async fn recv(socket: &UdpSocket) -> Option<Vec<u8>> {
+    let mut buf = [0; 1024];
+    let recv = time::timeout(Duration::from_millis(300), socket.recv(&mut buf));
+    let len = recv.await.ok()?.unwrap();
+    Some(buf[..len].to_vec())
+}
+
+#[tokio::test]
+async fn limit_and_idle_timeout() {
+    let server = Server::spawn(
+        "udp-session",
+        serde_json::json!({ "max_udp_sessions": 1, "udp_session_idle_timeout": "1s" }),
+    );
+    let target = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = Address::SocketAddress(target.local_addr().unwrap());
+    let (_ep, _conn, client) = server.connect().await;
+
+    client.packet_quic(b"0", addr.clone(), 0).await.unwrap();
+    assert_eq!(recv(&target).await.as_deref(), Some(&b"0"[..]));
+
+    // packets of sessions beyond the limit are dropped
+    client.packet_quic(b"1", addr.clone(), 1).await.unwrap();
+    assert_eq!(recv(&target).await, None);
+
+    // until the idle session is closed, along with its reassembly state
+    time::sleep(Duration::from_millis(1500)).await;
+    client.packet_quic(b"1", addr, 1).await.unwrap();
+    assert_eq!(recv(&target).await.as_deref(), Some(&b"1"[..]));
+}
+
+/// Sends the fragment of a 2-fragment packet in a raw datagram
+fn send_fragment(conn: &quinn::Connection, assoc_id: u16, frag_id: u8, addr: Address) {
+    let mut buf = BytesMut::new();
+    Header::Packet(Packet::new(assoc_id, 0, 2, frag_id, 1, addr)).write(&mut buf);
+    buf.extend_from_slice(b"0");
+    conn.send_datagram(buf.freeze()).unwrap();
+}
+
+#[tokio::test]
+async fn failed_sessions_released() {
+    let server = Server::spawn(
+        "failed-sessions",
+        serde_json::json!({ "max_udp_sessions": 1, "gc_interval": "500ms", "gc_lifetime": "500ms" }),
+    );
+    let target = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = Address::SocketAddress(target.local_addr().unwrap());
+    let (_ep, conn, client) = server.connect().await;
+
+    // invalid fragments do not take up the only session
+    for assoc_id in 0..4 {
+        send_fragment(&conn, assoc_id, 2, addr.clone());
+    }
+    client.packet_native(b"0", addr.clone(), 4).unwrap();
+    assert_eq!(recv(&target).await.as_deref(), Some(&b"0"[..]));
+    client.dissociate(4).await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+
+    // and neither do lost ones, once they are collected
+    send_fragment(&conn, 5, 0, addr.clone());
+    time::sleep(Duration::from_millis(100)).await;
+    client.packet_native(b"1", addr.clone(), 6).unwrap();
+    assert_eq!(recv(&target).await, None);
+
+    time::sleep(Duration::from_millis(1500)).await;
+    client.packet_native(b"1", addr, 6).unwrap();
+    assert_eq!(recv(&target).await.as_deref(), Some(&b"1"[..]));
+}
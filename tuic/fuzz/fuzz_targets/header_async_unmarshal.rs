@@ -0,0 +1,16 @@
+#![no_main]
+
+use futures_util::FutureExt;
+use libfuzzer_sys::fuzz_target;
+use tuic::Header;
+
+fuzz_target!(|data: &[u8]| {
+    let mut s = data;
+
+    // reading from a slice never pends
+    let res = Header::async_unmarshal(&mut s).now_or_never().unwrap();
+
+    if let Ok(header) = res {
+        assert_eq!(header.len(), data.len() - s.len());
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tuic::{Header, HeaderRef, Status};
+
+fuzz_target!(|data: &[u8]| {
+    let mut s = data;
+    let res = Header::unmarshal(&mut s);
+
+    // the borrowed decoder must agree with the stream unmarshaller
+    match (res, HeaderRef::decode(data)) {
+        (Ok(header), Ok(Status::Complete(decoded, len))) => {
+            assert_eq!(len, data.len() - s.len());
+            assert_eq!(len, header.len());
+            assert_eq!(decoded.into_owned(), header);
+        }
+        (Ok(header), res) => panic!("unmarshalled {header:?}, but decoded {res:?}"),
+        (Err(_), Ok(Status::Complete(decoded, _))) => {
+            panic!("failed to unmarshal, but decoded {decoded:?}")
+        }
+        (Err(_), _) => {}
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tuic::{model::Connection, Header};
+
+// The input is a sequence of datagrams, each prefixed by a 2-byte big-endian length.
+// Every datagram is handled like a native mode UDP relay datagram: a `Packet` header followed by the fragment payload.
+fuzz_target!(|data: &[u8]| {
+    let conn = Connection::<Vec<u8>>::new();
+    let mut data = data;
+
+    while data.len() >= 2 {
+        let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+        let dg = &data[2..(2 + len).min(data.len())];
+        data = &data[2 + dg.len()..];
+
+        let mut payload = dg;
+
+        let Ok(Header::Packet(pkt)) = Header::unmarshal(&mut payload) else {
+            continue;
+        };
+
+        let pkt = conn.recv_packet_unrestricted(pkt);
+
+        if let Ok(Some(assemblable)) = pkt.assemble(payload.to_vec()) {
+            let mut buf = Vec::new();
+            assemblable.assemble(&mut buf);
+        }
+    }
+});
@@ -0,0 +1,399 @@
+use crate::{
+    Address, Authenticate, Capabilities, Connect, ConnectResult, ConnectStatus, Dissociate,
+    Extension, Header, Heartbeat, Packet, Padding, UnmarshalError, VERSION,
+};
+use std::{net::SocketAddr, str};
+use uuid::Uuid;
+
+/// The status of decoding a value from a (possibly partial) buffer
+#[derive(Clone, Debug)]
+pub enum Status<T> {
+    /// The value is decoded. The second field is the number of bytes consumed
+    Complete(T, usize),
+    /// The buffer is too short. The field is the minimum number of additional bytes needed to make progress
+    Incomplete(usize),
+}
+
+impl<T> Status<T> {
+    /// Returns `true` if the value is decoded
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Self::Complete(_, _))
+    }
+
+    /// Maps the decoded value with the given function
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Status<U> {
+        match self {
+            Self::Complete(val, len) => Status::Complete(f(val), len),
+            Self::Incomplete(needed) => Status::Incomplete(needed),
+        }
+    }
+}
+
+/// A borrowed [`Header`](crate::Header) decoded from a byte slice
+///
+/// Decoding is stateless. When [`Status::Incomplete`] is returned, feed the same buffer extended by at least the reported number of bytes again.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum HeaderRef<'a> {
+    Authenticate(Authenticate),
+    Connect(ConnectRef<'a>),
+    Packet(PacketRef<'a>),
+    Dissociate(Dissociate),
+    Heartbeat(Heartbeat),
+    Capabilities(Capabilities),
+    ConnectResult(ConnectResult),
+    Padding(Padding),
+    Extension(ExtensionRef<'a>),
+}
+
+impl<'a> HeaderRef<'a> {
+    /// Decodes a header from the beginning of a byte slice, without copying the domain name of the address
+    pub fn decode(buf: &'a [u8]) -> Result<Status<Self>, UnmarshalError> {
+        let mut r = Reader::new(buf);
+
+        match Self::read(&mut r) {
+            Ok(header) => Ok(Status::Complete(header, r.pos)),
+            Err(DecodeError::Incomplete(needed)) => Ok(Status::Incomplete(needed)),
+            Err(DecodeError::Invalid(err)) => Err(err),
+        }
+    }
+
+    fn read(r: &mut Reader<'a>) -> Result<Self, DecodeError> {
+        let ver = r.u8()?;
+
+        if ver != VERSION {
+            return Err(UnmarshalError::InvalidVersion(ver).into());
+        }
+
+        let cmd = r.u8()?;
+
+        match cmd {
+            Header::TYPE_CODE_AUTHENTICATE => {
+                let buf = r.take(48)?;
+                let uuid = Uuid::from_slice(&buf[..16]).map_err(UnmarshalError::from)?;
+                let mut token = [0; 32];
+                token.copy_from_slice(&buf[16..]);
+                Ok(Self::Authenticate(Authenticate::new(uuid, token)))
+            }
+            Header::TYPE_CODE_CONNECT => Ok(Self::Connect(ConnectRef {
+                addr: AddressRef::read(r)?,
+            })),
+            Header::TYPE_CODE_PACKET => {
+                let buf = r.take(8)?;
+                Ok(Self::Packet(PacketRef {
+                    assoc_id: u16::from_be_bytes([buf[0], buf[1]]),
+                    pkt_id: u16::from_be_bytes([buf[2], buf[3]]),
+                    frag_total: buf[4],
+                    frag_id: buf[5],
+                    size: u16::from_be_bytes([buf[6], buf[7]]),
+                    addr: AddressRef::read(r)?,
+                }))
+            }
+            Header::TYPE_CODE_DISSOCIATE => Ok(Self::Dissociate(Dissociate::new(r.u16()?))),
+            Header::TYPE_CODE_HEARTBEAT => Ok(Self::Heartbeat(Heartbeat::new())),
+            Header::TYPE_CODE_CAPABILITIES => {
+                let buf = r.take(8)?;
+                let mut bits = [0; 8];
+                bits.copy_from_slice(buf);
+                let caps = Capabilities::new(u64::from_be_bytes(bits));
+                Ok(Self::Capabilities(caps))
+            }
+            Header::TYPE_CODE_CONNECT_RESULT => Ok(Self::ConnectResult(ConnectResult::new(
+                ConnectStatus::from(r.u8()?),
+            ))),
+            Header::TYPE_CODE_PADDING => {
+                let len = r.u16()?;
+                r.take(len as usize)?;
+                Ok(Self::Padding(Padding::new(len)))
+            }
+            Extension::TYPE_CODE_MIN..=Extension::TYPE_CODE_MAX => {
+                let len = r.u16()? as usize;
+                Ok(Self::Extension(ExtensionRef {
+                    type_code: cmd,
+                    payload: r.take(len)?,
+                }))
+            }
+            _ => Err(UnmarshalError::InvalidCommand(cmd).into()),
+        }
+    }
+
+    /// Returns the command type code
+    pub const fn type_code(&self) -> u8 {
+        match self {
+            Self::Authenticate(_) => Header::TYPE_CODE_AUTHENTICATE,
+            Self::Connect(_) => Header::TYPE_CODE_CONNECT,
+            Self::Packet(_) => Header::TYPE_CODE_PACKET,
+            Self::Dissociate(_) => Header::TYPE_CODE_DISSOCIATE,
+            Self::Heartbeat(_) => Header::TYPE_CODE_HEARTBEAT,
+            Self::Capabilities(_) => Header::TYPE_CODE_CAPABILITIES,
+            Self::ConnectResult(_) => Header::TYPE_CODE_CONNECT_RESULT,
+            Self::Padding(_) => Header::TYPE_CODE_PADDING,
+            Self::Extension(ext) => ext.type_code,
+        }
+    }
+
+    /// Returns the serialized length of the command
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        2 + match self {
+            Self::Authenticate(auth) => auth.len(),
+            Self::Connect(conn) => conn.addr.len(),
+            Self::Packet(packet) => 2 + 2 + 1 + 1 + 2 + packet.addr.len(),
+            Self::Dissociate(dissociate) => dissociate.len(),
+            Self::Heartbeat(heartbeat) => heartbeat.len(),
+            Self::Capabilities(caps) => caps.len(),
+            Self::ConnectResult(result) => result.len(),
+            Self::Padding(padding) => padding.len(),
+            Self::Extension(ext) => 2 + ext.payload.len(),
+        }
+    }
+
+    /// Converts the borrowed header into an owned [`Header`](crate::Header)
+    pub fn into_owned(self) -> Header {
+        match self {
+            Self::Authenticate(auth) => Header::Authenticate(auth),
+            Self::Connect(conn) => Header::Connect(conn.into_owned()),
+            Self::Packet(packet) => Header::Packet(packet.into_owned()),
+            Self::Dissociate(dissociate) => Header::Dissociate(dissociate),
+            Self::Heartbeat(heartbeat) => Header::Heartbeat(heartbeat),
+            Self::Capabilities(caps) => Header::Capabilities(caps),
+            Self::ConnectResult(result) => Header::ConnectResult(result),
+            Self::Padding(padding) => Header::Padding(padding),
+            Self::Extension(ext) => Header::Extension(ext.into_owned()),
+        }
+    }
+}
+
+impl From<HeaderRef<'_>> for Header {
+    fn from(header: HeaderRef<'_>) -> Self {
+        header.into_owned()
+    }
+}
+
+/// A borrowed [`Connect`](crate::Connect) command
+#[derive(Clone, Debug)]
+pub struct ConnectRef<'a> {
+    addr: AddressRef<'a>,
+}
+
+impl<'a> ConnectRef<'a> {
+    /// Returns the address
+    pub fn addr(&self) -> &AddressRef<'a> {
+        &self.addr
+    }
+
+    /// Converts the borrowed command into an owned [`Connect`](crate::Connect)
+    pub fn into_owned(self) -> Connect {
+        Connect::new(self.addr.into_owned())
+    }
+}
+
+/// A borrowed [`Packet`](crate::Packet) command
+#[derive(Clone, Debug)]
+pub struct PacketRef<'a> {
+    assoc_id: u16,
+    pkt_id: u16,
+    frag_total: u8,
+    frag_id: u8,
+    size: u16,
+    addr: AddressRef<'a>,
+}
+
+impl<'a> PacketRef<'a> {
+    /// Returns the UDP relay session ID
+    pub fn assoc_id(&self) -> u16 {
+        self.assoc_id
+    }
+
+    /// Returns the packet ID
+    pub fn pkt_id(&self) -> u16 {
+        self.pkt_id
+    }
+
+    /// Returns the total number of fragments of the UDP packet
+    pub fn frag_total(&self) -> u8 {
+        self.frag_total
+    }
+
+    /// Returns the fragment ID of the UDP packet
+    pub fn frag_id(&self) -> u8 {
+        self.frag_id
+    }
+
+    /// Returns the length of the (fragmented) UDP packet
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Returns the target (from client) or source (from server) address
+    pub fn addr(&self) -> &AddressRef<'a> {
+        &self.addr
+    }
+
+    /// Converts the borrowed command into an owned [`Packet`](crate::Packet)
+    pub fn into_owned(self) -> Packet {
+        Packet::new(
+            self.assoc_id,
+            self.pkt_id,
+            self.frag_total,
+            self.frag_id,
+            self.size,
+            self.addr.into_owned(),
+        )
+    }
+}
+
+/// A borrowed [`Extension`](crate::Extension) command
+#[derive(Clone, Debug)]
+pub struct ExtensionRef<'a> {
+    type_code: u8,
+    payload: &'a [u8],
+}
+
+impl<'a> ExtensionRef<'a> {
+    /// Returns the command type code
+    pub fn type_code(&self) -> u8 {
+        self.type_code
+    }
+
+    /// Returns the payload
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Converts the borrowed command into an owned [`Extension`](crate::Extension)
+    pub fn into_owned(self) -> Extension {
+        Extension::new_unchecked(self.type_code, self.payload.to_vec())
+    }
+}
+
+/// A borrowed [`Address`](crate::Address)
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum AddressRef<'a> {
+    None,
+    DomainAddress(&'a str, u16),
+    SocketAddress(SocketAddr),
+}
+
+impl<'a> AddressRef<'a> {
+    fn read(r: &mut Reader<'a>) -> Result<Self, DecodeError> {
+        let type_code = r.u8()?;
+
+        match type_code {
+            Address::TYPE_CODE_NONE => Ok(Self::None),
+            Address::TYPE_CODE_DOMAIN => {
+                let len = r.u8()? as usize;
+                r.require(len + 2)?;
+                let domain = r.take(len)?;
+                let port = r.u16()?;
+
+                let domain = str::from_utf8(domain).map_err(UnmarshalError::from)?;
+
+                Ok(Self::DomainAddress(domain, port))
+            }
+            Address::TYPE_CODE_IPV4 => {
+                let buf = r.take(6)?;
+                let ip = [buf[0], buf[1], buf[2], buf[3]];
+                let port = u16::from_be_bytes([buf[4], buf[5]]);
+                Ok(Self::SocketAddress(SocketAddr::from((ip, port))))
+            }
+            Address::TYPE_CODE_IPV6 => {
+                let buf = r.take(18)?;
+                let mut ip = [0; 16];
+                ip.copy_from_slice(&buf[..16]);
+                let port = u16::from_be_bytes([buf[16], buf[17]]);
+                Ok(Self::SocketAddress(SocketAddr::from((ip, port))))
+            }
+            _ => Err(UnmarshalError::InvalidAddressType(type_code).into()),
+        }
+    }
+
+    /// Returns the serialized length of the address
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        1 + match self {
+            Self::None => 0,
+            Self::DomainAddress(addr, _) => 1 + addr.len() + 2,
+            Self::SocketAddress(SocketAddr::V4(_)) => 4 + 2,
+            Self::SocketAddress(SocketAddr::V6(_)) => 16 + 2,
+        }
+    }
+
+    /// Returns `true` if the address is `None`
+    pub fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    /// Converts the borrowed address into an owned [`Address`](crate::Address)
+    pub fn into_owned(self) -> Address {
+        match self {
+            Self::None => Address::None,
+            Self::DomainAddress(domain, port) => Address::DomainAddress(domain.to_owned(), port),
+            Self::SocketAddress(addr) => Address::SocketAddress(addr),
+        }
+    }
+}
+
+impl From<AddressRef<'_>> for Address {
+    fn from(addr: AddressRef<'_>) -> Self {
+        addr.into_owned()
+    }
+}
+
+impl<'a> From<&'a Address> for AddressRef<'a> {
+    fn from(addr: &'a Address) -> Self {
+        match addr {
+            Address::None => Self::None,
+            Address::DomainAddress(domain, port) => Self::DomainAddress(domain, *port),
+            Address::SocketAddress(addr) => Self::SocketAddress(*addr),
+        }
+    }
+}
+
+enum DecodeError {
+    Incomplete(usize),
+    Invalid(UnmarshalError),
+}
+
+impl From<UnmarshalError> for DecodeError {
+    fn from(err: UnmarshalError) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn require(&self, len: usize) -> Result<(), DecodeError> {
+        let remaining = self.buf.len() - self.pos;
+
+        if remaining < len {
+            Err(DecodeError::Incomplete(len - remaining))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        self.require(len)?;
+        let buf = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(buf)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        let buf = self.take(2)?;
+        Ok(u16::from_be_bytes([buf[0], buf[1]]))
+    }
+}
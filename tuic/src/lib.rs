@@ -1,19 +1,41 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 mod protocol;
 
 pub use self::protocol::{
-    Address, Authenticate, Connect, Dissociate, Header, Heartbeat, Packet, VERSION,
+    Address, AddressError, Authenticate, Capabilities, Connect, ConnectResult, ConnectStatus,
+    Dissociate, Extension, ExtensionError, Header, Heartbeat, Packet, Padding, VERSION,
 };
 
-#[cfg(any(feature = "async_marshal", feature = "marshal"))]
+#[cfg(feature = "bytes")]
 mod marshal;
 
-#[cfg(any(feature = "async_marshal", feature = "marshal"))]
+#[cfg(any(
+    feature = "async_marshal",
+    feature = "marshal",
+    feature = "tokio_marshal"
+))]
 mod unmarshal;
 
-#[cfg(any(feature = "async_marshal", feature = "marshal"))]
-pub use self::unmarshal::UnmarshalError;
+#[cfg(any(
+    feature = "async_marshal",
+    feature = "marshal",
+    feature = "tokio_marshal"
+))]
+mod decode;
+
+#[cfg(any(
+    feature = "async_marshal",
+    feature = "marshal",
+    feature = "tokio_marshal"
+))]
+pub use self::{
+    decode::{AddressRef, ConnectRef, ExtensionRef, HeaderRef, PacketRef, Status},
+    unmarshal::UnmarshalError,
+};
 
 #[cfg(feature = "model")]
 pub mod model;
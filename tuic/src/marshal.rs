@@ -1,17 +1,38 @@
-use crate::{Address, Authenticate, Connect, Dissociate, Header, Heartbeat, Packet, VERSION};
-use bytes::{BufMut, BytesMut};
-use futures_util::{AsyncWrite, AsyncWriteExt};
-use std::{
-    io::{Error as IoError, Write},
-    net::SocketAddr,
+use crate::{
+    Address, AddressError, Authenticate, Capabilities, Connect, ConnectResult, Dissociate,
+    Extension, Header, Heartbeat, Packet, Padding, VERSION,
+};
+use bytes::BufMut;
+use core::net::SocketAddr;
+
+#[cfg(feature = "std")]
+use rand::RngCore;
+
+#[cfg(any(
+    feature = "async_marshal",
+    feature = "marshal",
+    feature = "tokio_marshal"
+))]
+use {
+    bytes::BytesMut,
+    std::io::{Error as IoError, ErrorKind},
 };
 
+#[cfg(feature = "async_marshal")]
+use futures_util::{AsyncWrite, AsyncWriteExt};
+
+#[cfg(feature = "marshal")]
+use std::io::Write;
+
+#[cfg(feature = "tokio_marshal")]
+use tokio::io::{AsyncWrite as TokioAsyncWrite, AsyncWriteExt as TokioAsyncWriteExt};
+
 impl Header {
     /// Marshals the header into an `AsyncWrite` stream
     #[cfg(feature = "async_marshal")]
     pub async fn async_marshal(&self, s: &mut (impl AsyncWrite + Unpin)) -> Result<(), IoError> {
         let mut buf = BytesMut::with_capacity(self.len());
-        self.write(&mut buf);
+        self.try_write(&mut buf).map_err(invalid_input)?;
         s.write_all(&buf).await
     }
 
@@ -19,12 +40,42 @@ impl Header {
     #[cfg(feature = "marshal")]
     pub fn marshal(&self, s: &mut impl Write) -> Result<(), IoError> {
         let mut buf = BytesMut::with_capacity(self.len());
-        self.write(&mut buf);
+        self.try_write(&mut buf).map_err(invalid_input)?;
         s.write_all(&buf)
     }
 
+    /// Marshals the header into a tokio `AsyncWrite` stream
+    #[cfg(feature = "tokio_marshal")]
+    pub async fn tokio_marshal(
+        &self,
+        s: &mut (impl TokioAsyncWrite + Unpin),
+    ) -> Result<(), IoError> {
+        let mut buf = BytesMut::with_capacity(self.len());
+        self.try_write(&mut buf).map_err(invalid_input)?;
+        s.write_all(&buf).await
+    }
+
     /// Writes the header into a `BufMut`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the header carries a domain address longer than `Address::MAX_DOMAIN_LEN` bytes. Use [`try_write`](Self::try_write) to handle it instead
     pub fn write(&self, buf: &mut impl BufMut) {
+        if let Err(err) = self.try_write(buf) {
+            panic!("failed to write the header: {err}");
+        }
+    }
+
+    /// Writes the header into a `BufMut`. Nothing is written if the header carries a domain address longer than `Address::MAX_DOMAIN_LEN` bytes
+    ///
+    /// Only the length of the domain name is checked, as the wire format requires. Its syntax is left to the resolver of the peer
+    pub fn try_write(&self, buf: &mut impl BufMut) -> Result<(), AddressError> {
+        match self {
+            Self::Connect(conn) => conn.addr().check_len()?,
+            Self::Packet(packet) => packet.addr().check_len()?,
+            _ => {}
+        }
+
         buf.put_u8(VERSION);
         buf.put_u8(self.type_code());
 
@@ -34,11 +85,38 @@ impl Header {
             Self::Packet(packet) => packet.write(buf),
             Self::Dissociate(dissociate) => dissociate.write(buf),
             Self::Heartbeat(heartbeat) => heartbeat.write(buf),
+            Self::Capabilities(caps) => caps.write(buf),
+            Self::ConnectResult(result) => result.write(buf),
+            Self::Padding(padding) => padding.write(buf),
+            Self::Extension(ext) => ext.write(buf),
         }
+
+        Ok(())
     }
 }
 
 impl Address {
+    /// Marshals the address into a tokio `AsyncWrite` stream
+    #[cfg(feature = "tokio_marshal")]
+    pub async fn tokio_marshal(
+        &self,
+        s: &mut (impl TokioAsyncWrite + Unpin),
+    ) -> Result<(), IoError> {
+        self.check_len().map_err(invalid_input)?;
+        let mut buf = BytesMut::with_capacity(self.len());
+        self.write(&mut buf);
+        s.write_all(&buf).await
+    }
+
+    fn check_len(&self) -> Result<(), AddressError> {
+        match self {
+            Self::DomainAddress(domain, _) if domain.len() > Self::MAX_DOMAIN_LEN => {
+                Err(AddressError::DomainTooLong(domain.len()))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn write(&self, buf: &mut impl BufMut) {
         buf.put_u8(self.type_code());
 
@@ -96,3 +174,55 @@ impl Dissociate {
 impl Heartbeat {
     fn write(&self, _buf: &mut impl BufMut) {}
 }
+
+impl Capabilities {
+    fn write(&self, buf: &mut impl BufMut) {
+        buf.put_u64(self.bits());
+    }
+}
+
+impl ConnectResult {
+    fn write(&self, buf: &mut impl BufMut) {
+        buf.put_u8(self.status().code());
+    }
+}
+
+impl Padding {
+    fn write(&self, buf: &mut impl BufMut) {
+        buf.put_u16(self.padding_len());
+
+        #[cfg(feature = "std")]
+        {
+            let mut rng = rand::thread_rng();
+            let mut filler = [0; 256];
+            let mut left = self.padding_len() as usize;
+
+            while left > 0 {
+                let chunk = left.min(filler.len());
+                rng.fill_bytes(&mut filler[..chunk]);
+                buf.put_slice(&filler[..chunk]);
+                left -= chunk;
+            }
+        }
+
+        // no source of randomness without `std`
+        #[cfg(not(feature = "std"))]
+        buf.put_bytes(0, self.padding_len() as usize);
+    }
+}
+
+impl Extension {
+    fn write(&self, buf: &mut impl BufMut) {
+        buf.put_u16(self.payload().len() as u16);
+        buf.put_slice(self.payload());
+    }
+}
+
+#[cfg(any(
+    feature = "async_marshal",
+    feature = "marshal",
+    feature = "tokio_marshal"
+))]
+fn invalid_input(err: AddressError) -> IoError {
+    IoError::new(ErrorKind::InvalidInput, err)
+}
@@ -4,6 +4,8 @@ use crate::{
     Address, Authenticate as AuthenticateHeader, Connect as ConnectHeader,
     Dissociate as DissociateHeader, Heartbeat as HeartbeatHeader, Packet as PacketHeader,
 };
+#[cfg(feature = "bytes")]
+use bytes::{Bytes, BytesMut};
 use parking_lot::Mutex;
 use register_count::{Counter, Register};
 use std::{
@@ -30,7 +32,7 @@ pub use self::{
     connect::Connect,
     dissociate::Dissociate,
     heartbeat::Heartbeat,
-    packet::{Fragments, Packet},
+    packet::{FragmentError, Fragments, Packet},
 };
 
 /// An abstraction of a TUIC connection, with packet fragmentation management and task counters. No I/O operation is involved internally
@@ -110,10 +112,10 @@ where
         )
     }
 
-    /// Receives a `Packet` without checking the association ID
+    /// Receives a `Packet` without checking the association ID. The UDP session is created when the packet is assembled
     pub fn recv_packet_unrestricted(&self, header: PacketHeader) -> Packet<side::Rx, B> {
         let (assoc_id, pkt_id, frag_total, frag_id, size, addr) = header.into();
-        self.udp_sessions.lock().recv_packet_unrestricted(
+        Packet::<side::Rx, B>::new(
             self.udp_sessions.clone(),
             assoc_id,
             pkt_id,
@@ -156,10 +158,56 @@ where
         self.task_associate_count.count()
     }
 
-    /// Removes fragments that can not be reassembled within the specified timeout
+    /// Returns `true` if the UDP session exists, i.e. a packet was sent or assembled in it, or one is pending, and it is not dissociated
+    pub fn has_udp_session(&self, assoc_id: u16) -> bool {
+        self.udp_sessions.lock().sessions.contains_key(&assoc_id)
+    }
+
+    /// Removes fragments that can not be reassembled within the specified timeout, along with the UDP sessions left with neither pending packets nor an assembled one
     pub fn collect_garbage(&self, timeout: Duration) {
         self.udp_sessions.lock().collect_garbage(timeout);
     }
+
+    /// Returns the limits of packet reassembly
+    pub fn limits(&self) -> ReassemblyLimits {
+        self.udp_sessions.lock().limits
+    }
+
+    /// Sets the limits of packet reassembly. The limits are enforced from the next received fragment on
+    pub fn set_limits(&self, limits: ReassemblyLimits) {
+        self.udp_sessions.lock().limits = limits;
+    }
+}
+
+/// Limits on the resources used for reassembling fragmented UDP packets
+///
+/// When a buffering limit is exceeded, the oldest pending packets are evicted first, and the fragment causing it is answered with [`AssembleError::Evicted`].
+///
+/// Buffered bytes include the bookkeeping of each incomplete packet, which grows with its fragment total, not only the received payload.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReassemblyLimits {
+    /// The maximum number of UDP sessions created by received packets. Fragments that would create more sessions are rejected with [`AssembleError::TooManySessions`]. Defaults to 256
+    pub max_sessions: usize,
+    /// The maximum number of incomplete packets in a UDP session. Defaults to 256
+    pub max_pending_packets_per_session: usize,
+    /// The maximum number of incomplete packets in the connection. Defaults to 1024
+    pub max_pending_packets: usize,
+    /// The maximum bytes of buffered fragments in a UDP session. Defaults to 4 MiB
+    pub max_buffered_bytes_per_session: usize,
+    /// The maximum bytes of buffered fragments in the connection. Defaults to 16 MiB
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for ReassemblyLimits {
+    fn default() -> Self {
+        Self {
+            max_sessions: 256,
+            max_pending_packets_per_session: 256,
+            max_pending_packets: 1024,
+            max_buffered_bytes_per_session: 4 * 1024 * 1024,
+            max_buffered_bytes: 16 * 1024 * 1024,
+        }
+    }
 }
 
 impl<B> Debug for Connection<B>
@@ -191,6 +239,10 @@ pub mod side {
 struct UdpSessions<B> {
     sessions: HashMap<u16, UdpSession<B>>,
     task_associate_count: Counter,
+    limits: ReassemblyLimits,
+    buffered_bytes: usize,
+    pending_packets: usize,
+    next_seq: u64,
 }
 
 impl<B> UdpSessions<B>
@@ -201,6 +253,10 @@ where
         Self {
             sessions: HashMap::new(),
             task_associate_count,
+            limits: ReassemblyLimits::default(),
+            buffered_bytes: 0,
+            pending_packets: 0,
+            next_seq: 0,
         }
     }
 
@@ -210,10 +266,12 @@ where
         addr: Address,
         max_pkt_size: usize,
     ) -> Packet<side::Tx, B> {
-        self.sessions
+        let session = self
+            .sessions
             .entry(assoc_id)
-            .or_insert_with(|| UdpSession::new(self.task_associate_count.reg()))
-            .send_packet(assoc_id, addr, max_pkt_size)
+            .or_insert_with(|| UdpSession::new(self.task_associate_count.reg()));
+        session.is_established = true;
+        session.send_packet(assoc_id, addr, max_pkt_size)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -232,33 +290,23 @@ where
         })
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn recv_packet_unrestricted(
-        &mut self,
-        sessions: Arc<Mutex<Self>>,
-        assoc_id: u16,
-        pkt_id: u16,
-        frag_total: u8,
-        frag_id: u8,
-        size: u16,
-        addr: Address,
-    ) -> Packet<side::Rx, B> {
-        self.sessions
-            .entry(assoc_id)
-            .or_insert_with(|| UdpSession::new(self.task_associate_count.reg()))
-            .recv_packet(sessions, assoc_id, pkt_id, frag_total, frag_id, size, addr)
-    }
-
     fn send_dissociate(&mut self, assoc_id: u16) -> Dissociate<side::Tx> {
-        self.sessions.remove(&assoc_id);
+        self.remove_session(assoc_id);
         Dissociate::<side::Tx>::new(assoc_id)
     }
 
     fn recv_dissociate(&mut self, assoc_id: u16) -> Dissociate<side::Rx> {
-        self.sessions.remove(&assoc_id);
+        self.remove_session(assoc_id);
         Dissociate::<side::Rx>::new(assoc_id)
     }
 
+    fn remove_session(&mut self, assoc_id: u16) {
+        if let Some(session) = self.sessions.remove(&assoc_id) {
+            self.buffered_bytes -= session.buffered_bytes;
+            self.pending_packets -= session.pkt_buf.len();
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn insert(
         &mut self,
@@ -270,16 +318,79 @@ where
         addr: Address,
         data: B,
     ) -> Result<Option<Assemblable<B>>, AssembleError> {
-        self.sessions
+        if !self.sessions.contains_key(&assoc_id) && self.sessions.len() >= self.limits.max_sessions
+        {
+            return Err(AssembleError::TooManySessions(self.limits.max_sessions));
+        }
+
+        let session = self
+            .sessions
             .entry(assoc_id)
-            .or_insert_with(|| UdpSession::new(self.task_associate_count.reg()))
-            .insert(assoc_id, pkt_id, frag_total, frag_id, size, addr, data)
+            .or_insert_with(|| UdpSession::new(self.task_associate_count.reg()));
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let bytes_before = session.buffered_bytes;
+        let pending_before = session.pkt_buf.len();
+        let res = session.insert(
+            &self.limits,
+            seq,
+            assoc_id,
+            pkt_id,
+            frag_total,
+            frag_id,
+            size,
+            addr,
+            data,
+        );
+        self.buffered_bytes = self.buffered_bytes - bytes_before + session.buffered_bytes;
+        self.pending_packets = self.pending_packets - pending_before + session.pkt_buf.len();
+
+        if let Ok((Some(_), _)) = res {
+            session.is_established = true;
+        }
+
+        // a session created by fragments that were all rejected or evicted is not kept
+        if session.is_idle() {
+            self.sessions.remove(&assoc_id);
+        }
+
+        let (res, mut evicted) = res?;
+
+        while self.buffered_bytes > self.limits.max_buffered_bytes
+            || self.pending_packets > self.limits.max_pending_packets
+        {
+            let Some(oldest) = self
+                .sessions
+                .values_mut()
+                .filter_map(|session| session.oldest().map(|(seq, _)| (seq, session)))
+                .min_by_key(|(seq, _)| *seq)
+                .map(|(_, session)| session)
+            else {
+                break;
+            };
+
+            self.buffered_bytes -= oldest.evict_oldest();
+            self.pending_packets -= 1;
+            evicted += 1;
+        }
+
+        if evicted > 0 {
+            Err(AssembleError::Evicted(evicted))
+        } else {
+            Ok(res)
+        }
     }
 
     fn collect_garbage(&mut self, timeout: Duration) {
         for (_, session) in self.sessions.iter_mut() {
-            session.collect_garbage(timeout);
+            let pending_before = session.pkt_buf.len();
+            self.buffered_bytes -= session.collect_garbage(timeout);
+            self.pending_packets -= pending_before - session.pkt_buf.len();
         }
+
+        self.sessions.retain(|_, session| !session.is_idle());
     }
 }
 
@@ -290,13 +401,19 @@ where
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("UdpSessions")
             .field("sessions", &self.sessions)
+            .field("limits", &self.limits)
+            .field("buffered_bytes", &self.buffered_bytes)
+            .field("pending_packets", &self.pending_packets)
             .finish()
     }
 }
 
 struct UdpSession<B> {
     pkt_buf: HashMap<u16, PacketBuffer<B>>,
+    buffered_bytes: usize,
     next_pkt_id: AtomicU16,
+    /// Set once a packet is sent or assembled in the session, after which only a `Dissociate` removes it
+    is_established: bool,
     _task_reg: Register,
 }
 
@@ -307,11 +424,18 @@ where
     fn new(task_reg: Register) -> Self {
         Self {
             pkt_buf: HashMap::new(),
+            buffered_bytes: 0,
             next_pkt_id: AtomicU16::new(0),
+            is_established: false,
             _task_reg: task_reg,
         }
     }
 
+    /// Returns `true` if no packet was ever sent or assembled in the session, and none is pending
+    fn is_idle(&self) -> bool {
+        !self.is_established && self.pkt_buf.is_empty()
+    }
+
     fn send_packet(
         &self,
         assoc_id: u16,
@@ -340,9 +464,12 @@ where
         Packet::<side::Rx, B>::new(sessions, assoc_id, pkt_id, frag_total, frag_id, size, addr)
     }
 
+    /// Inserts a fragment, evicting the oldest pending packets of the session if the session limits are exceeded. Returns the number of evicted packets along with the result
     #[allow(clippy::too_many_arguments)]
     fn insert(
         &mut self,
+        limits: &ReassemblyLimits,
+        seq: u64,
         assoc_id: u16,
         pkt_id: u16,
         frag_total: u8,
@@ -350,22 +477,74 @@ where
         size: u16,
         addr: Address,
         data: B,
-    ) -> Result<Option<Assemblable<B>>, AssembleError> {
-        let res = self
+    ) -> Result<(Option<Assemblable<B>>, usize), AssembleError> {
+        let is_new = !self.pkt_buf.contains_key(&pkt_id);
+
+        let buf = self
             .pkt_buf
             .entry(pkt_id)
-            .or_insert_with(|| PacketBuffer::new(frag_total))
-            .insert(assoc_id, frag_total, frag_id, size, addr, data)?;
+            .or_insert_with(|| PacketBuffer::new(frag_total, seq));
+        let bytes_before = if is_new { 0 } else { buf.bytes };
+
+        match buf.insert(assoc_id, frag_total, frag_id, size, addr, data) {
+            Ok(Some(res)) => {
+                self.pkt_buf.remove(&pkt_id);
+                self.buffered_bytes -= bytes_before;
+                return Ok((Some(res), 0));
+            }
+            Ok(None) => self.buffered_bytes += buf.bytes - bytes_before,
+            Err(err) => {
+                if is_new {
+                    self.pkt_buf.remove(&pkt_id);
+                }
+                return Err(err);
+            }
+        }
 
-        if res.is_some() {
-            self.pkt_buf.remove(&pkt_id);
+        let mut evicted = 0;
+
+        while self.pkt_buf.len() > limits.max_pending_packets_per_session
+            || self.buffered_bytes > limits.max_buffered_bytes_per_session
+        {
+            self.evict_oldest();
+            evicted += 1;
         }
 
-        Ok(res)
+        Ok((None, evicted))
     }
 
-    fn collect_garbage(&mut self, timeout: Duration) {
-        self.pkt_buf.retain(|_, buf| buf.c_time.elapsed() < timeout);
+    fn oldest(&self) -> Option<(u64, u16)> {
+        self.pkt_buf
+            .iter()
+            .map(|(pkt_id, buf)| (buf.seq, *pkt_id))
+            .min()
+    }
+
+    /// Removes the oldest pending packet. Returns the number of bytes freed
+    fn evict_oldest(&mut self) -> usize {
+        let Some((_, pkt_id)) = self.oldest() else {
+            return 0;
+        };
+
+        let buf = self.pkt_buf.remove(&pkt_id).unwrap();
+        self.buffered_bytes -= buf.bytes;
+        buf.bytes
+    }
+
+    /// Removes timed out pending packets. Returns the number of bytes freed
+    fn collect_garbage(&mut self, timeout: Duration) -> usize {
+        let mut freed = 0;
+
+        self.pkt_buf.retain(|_, buf| {
+            let keep = buf.c_time.elapsed() < timeout;
+            if !keep {
+                freed += buf.bytes;
+            }
+            keep
+        });
+
+        self.buffered_bytes -= freed;
+        freed
     }
 }
 
@@ -376,7 +555,9 @@ where
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("UdpSession")
             .field("pkt_buf", &self.pkt_buf)
+            .field("buffered_bytes", &self.buffered_bytes)
             .field("next_pkt_id", &self.next_pkt_id)
+            .field("is_established", &self.is_established)
             .finish()
     }
 }
@@ -386,7 +567,9 @@ struct PacketBuffer<B> {
     buf: Vec<Option<B>>,
     frag_total: u8,
     frag_received: u8,
+    bytes: usize,
     addr: Address,
+    seq: u64,
     c_time: Instant,
 }
 
@@ -394,7 +577,7 @@ impl<B> PacketBuffer<B>
 where
     B: AsRef<[u8]>,
 {
-    fn new(frag_total: u8) -> Self {
+    fn new(frag_total: u8, seq: u64) -> Self {
         let mut buf = Vec::with_capacity(frag_total as usize);
         buf.resize_with(frag_total as usize, || None);
 
@@ -402,11 +585,18 @@ where
             buf,
             frag_total,
             frag_received: 0,
+            bytes: Self::overhead(frag_total),
             addr: Address::None,
+            seq,
             c_time: Instant::now(),
         }
     }
 
+    /// The memory used by a pending packet regardless of its payload, so that fragments with tiny payloads are not almost free to buffer
+    fn overhead(frag_total: u8) -> usize {
+        mem::size_of::<Self>() + frag_total as usize * mem::size_of::<Option<B>>()
+    }
+
     fn insert(
         &mut self,
         assoc_id: u16,
@@ -416,7 +606,19 @@ where
         addr: Address,
         data: B,
     ) -> Result<Option<Assemblable<B>>, AssembleError> {
-        assert_eq!(data.as_ref().len(), size as usize);
+        if data.as_ref().len() != size as usize {
+            return Err(AssembleError::PayloadLength(
+                size as usize,
+                data.as_ref().len(),
+            ));
+        }
+
+        if frag_total != self.frag_total {
+            return Err(AssembleError::InconsistentFragmentTotal(
+                self.frag_total,
+                frag_total,
+            ));
+        }
 
         if frag_id >= frag_total {
             return Err(AssembleError::InvalidFragmentId(frag_total, frag_id));
@@ -440,6 +642,7 @@ where
 
         self.buf[frag_id as usize] = Some(data);
         self.frag_received += 1;
+        self.bytes += size as usize;
 
         if frag_id == 0 {
             self.addr = addr;
@@ -477,6 +680,7 @@ where
         }
     }
 
+    /// Assembles the fragments into `buf`, in order
     pub fn assemble<A>(self, buf: &mut A) -> (Address, u16)
     where
         A: Assembler<B>,
@@ -485,9 +689,25 @@ where
         buf.assemble(data);
         (self.addr, self.assoc_id)
     }
+
+    /// Returns the total payload length of the packet
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.buf
+            .iter()
+            .map(|b| b.as_ref().map_or(0, |b| b.as_ref().len()))
+            .sum()
+    }
+
+    /// Returns the number of fragments of the packet
+    pub fn frag_total(&self) -> u8 {
+        self.buf.len() as u8
+    }
 }
 
 /// A trait for assembling a packet
+///
+/// The fragments are appended to the existing content of the buffer. [`PooledBuffer`] reuses the memory of assembled packets, and other pooled buffer types can implement this trait as well
 pub trait Assembler<B>
 where
     Self: Sized,
@@ -507,6 +727,113 @@ where
     }
 }
 
+#[cfg(feature = "bytes")]
+impl<B> Assembler<B> for BytesMut
+where
+    B: AsRef<[u8]>,
+{
+    fn assemble(&mut self, data: impl IntoIterator<Item = B>) {
+        for d in data {
+            self.extend_from_slice(d.as_ref());
+        }
+    }
+}
+
+/// Assembling a single fragment into an empty `Bytes` takes the fragment as is, without copying
+#[cfg(feature = "bytes")]
+impl Assembler<Bytes> for Bytes {
+    fn assemble(&mut self, data: impl IntoIterator<Item = Bytes>) {
+        let mut data = data.into_iter();
+
+        let Some(first) = data.next() else {
+            return;
+        };
+
+        match data.next() {
+            None if self.is_empty() => *self = first,
+            second => {
+                let mut buf = BytesMut::from(&self[..]);
+                buf.extend_from_slice(&first);
+
+                for d in second.into_iter().chain(data) {
+                    buf.extend_from_slice(&d);
+                }
+
+                *self = buf.freeze();
+            }
+        }
+    }
+}
+
+/// A pool of memory for assembling packets into `Bytes`, shared by its clones
+///
+/// Packets are split off a shared `BytesMut`, whose memory is reused for the following packets once all the `Bytes` split off it are dropped. A long-lived packet keeps the whole chunk it was split off alive
+#[cfg(feature = "bytes")]
+#[derive(Clone, Debug, Default)]
+pub struct BufferPool {
+    buf: Arc<Mutex<BytesMut>>,
+}
+
+#[cfg(feature = "bytes")]
+impl BufferPool {
+    /// Creates a new empty `BufferPool`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an empty buffer assembling packets with memory from the pool
+    pub fn buffer(&self) -> PooledBuffer {
+        PooledBuffer {
+            pool: self.clone(),
+            buf: Bytes::new(),
+        }
+    }
+}
+
+/// A buffer assembling packets with memory from a [`BufferPool`]
+///
+/// Assembling a single fragment into an empty buffer takes the fragment as is, without copying
+#[cfg(feature = "bytes")]
+#[derive(Debug)]
+pub struct PooledBuffer {
+    pool: BufferPool,
+    buf: Bytes,
+}
+
+#[cfg(feature = "bytes")]
+impl PooledBuffer {
+    /// Returns the assembled bytes
+    pub fn into_bytes(self) -> Bytes {
+        self.buf
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Assembler<Bytes> for PooledBuffer {
+    fn assemble(&mut self, data: impl IntoIterator<Item = Bytes>) {
+        let mut data = data.into_iter();
+
+        let Some(first) = data.next() else {
+            return;
+        };
+
+        match data.next() {
+            None if self.buf.is_empty() => self.buf = first,
+            second => {
+                let mut pool = self.pool.buf.lock();
+                pool.extend_from_slice(&self.buf);
+                pool.extend_from_slice(&first);
+
+                for d in second.into_iter().chain(data) {
+                    pool.extend_from_slice(&d);
+                }
+
+                self.buf = pool.split().freeze();
+            }
+        }
+    }
+}
+
 /// An error that can occur when assembling a packet
 #[derive(Debug, Error)]
 pub enum AssembleError {
@@ -516,4 +843,13 @@ pub enum AssembleError {
     InvalidAddress(&'static str),
     #[error("duplicated fragment: {0}")]
     DuplicatedFragment(u8),
+    #[error("expecting payload length {0} but got {1}")]
+    PayloadLength(usize, usize),
+    #[error("inconsistent fragment total: expecting {0} but got {1}")]
+    InconsistentFragmentTotal(u8, u8),
+    /// The fragment is buffered, but pending packets (possibly including the one the fragment belongs to) are evicted to stay within the [`ReassemblyLimits`]
+    #[error("{0} pending packet(s) evicted for exceeding the reassembly limits")]
+    Evicted(usize),
+    #[error("too many UDP sessions: {0}")]
+    TooManySessions(usize),
 }
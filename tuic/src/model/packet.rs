@@ -6,10 +6,10 @@ use crate::{Address, Header, Packet as PacketHeader};
 use parking_lot::Mutex;
 use std::{
     fmt::{Debug, Formatter, Result as FmtResult},
-    marker::PhantomData,
-    slice,
+    ops::Range,
     sync::Arc,
 };
+use thiserror::Error;
 
 pub struct Packet<M, B> {
     inner: Side<Tx, Rx<B>>,
@@ -37,14 +37,35 @@ impl<B> Packet<side::Tx, B> {
     }
 
     /// Fragment the payload into multiple packets
-    pub fn into_fragments<'a, P>(self, payload: P) -> Fragments<'a, P>
+    ///
+    /// # Panics
+    ///
+    /// Panics if the payload can not be fragmented within the protocol limits. Use [`try_into_fragments`](Self::try_into_fragments) to handle it instead
+    pub fn into_fragments<P>(self, payload: &P) -> Fragments<'_>
     where
-        P: AsRef<[u8]> + 'a,
+        P: AsRef<[u8]> + ?Sized,
+    {
+        match self.try_into_fragments(payload) {
+            Ok(frags) => frags,
+            Err(err) => panic!("failed to fragment the packet: {err}"),
+        }
+    }
+
+    /// Fragment the payload into multiple packets, each of them (header included) no larger than the maximum packet size
+    pub fn try_into_fragments<P>(self, payload: &P) -> Result<Fragments<'_>, FragmentError>
+    where
+        P: AsRef<[u8]> + ?Sized,
     {
         let Side::Tx(tx) = self.inner else {
             unreachable!()
         };
-        Fragments::new(tx.assoc_id, tx.pkt_id, tx.addr, tx.max_pkt_size, payload)
+        Fragments::new(
+            tx.assoc_id,
+            tx.pkt_id,
+            tx.addr,
+            tx.max_pkt_size,
+            payload.as_ref(),
+        )
     }
 
     /// Returns the UDP session ID
@@ -207,102 +228,133 @@ impl<B> Debug for Packet<side::Rx, B> {
 }
 
 /// Iterator over fragments of a packet
+///
+/// The fragmentation is planned up front. The fragment count and the payload size of each fragment are available before iterating
 #[derive(Debug)]
-pub struct Fragments<'a, P> {
+pub struct Fragments<'a> {
     assoc_id: u16,
     pkt_id: u16,
     addr: Address,
-    max_pkt_size: usize,
     frag_total: u8,
+    first_frag_size: usize,
+    frag_size: usize,
     next_frag_id: u8,
-    next_frag_start: usize,
-    payload: P,
-    _marker: PhantomData<&'a P>,
+    payload: &'a [u8],
 }
 
-impl<'a, P> Fragments<'a, P>
-where
-    P: AsRef<[u8]> + 'a,
-{
-    fn new(assoc_id: u16, pkt_id: u16, addr: Address, max_pkt_size: usize, payload: P) -> Self {
-        let header_addr_ref = Header::Packet(PacketHeader::new(0, 0, 0, 0, 0, addr));
-        let header_addr_none_ref = Header::Packet(PacketHeader::new(0, 0, 0, 0, 0, Address::None));
-
-        let first_frag_size = max_pkt_size - header_addr_ref.len();
-        let frag_size_addr_none = max_pkt_size - header_addr_none_ref.len();
-
-        let Header::Packet(pkt) = header_addr_ref else {
-            unreachable!()
+impl<'a> Fragments<'a> {
+    /// Plans the fragmentation
+    fn new(
+        assoc_id: u16,
+        pkt_id: u16,
+        addr: Address,
+        max_pkt_size: usize,
+        payload: &'a [u8],
+    ) -> Result<Self, FragmentError> {
+        let header_len_addr_none =
+            Header::Packet(PacketHeader::new(0, 0, 0, 0, 0, Address::None)).len();
+        let header_len = header_len_addr_none - Address::None.len() + addr.len();
+
+        // the size field of the header is a `u16`
+        let first_frag_size = max_pkt_size
+            .checked_sub(header_len)
+            .map(|size| size.min(u16::MAX as usize));
+        let frag_size = max_pkt_size
+            .saturating_sub(header_len_addr_none)
+            .min(u16::MAX as usize);
+
+        let payload_len = payload.len();
+
+        let (first_frag_size, frag_total) = match first_frag_size {
+            Some(first_frag_size) if payload_len <= first_frag_size => (first_frag_size, 1),
+            Some(first_frag_size) if frag_size > 0 => (
+                first_frag_size,
+                1 + (payload_len - first_frag_size).div_ceil(frag_size),
+            ),
+            _ => return Err(FragmentError::PacketSizeTooSmall(max_pkt_size)),
         };
-        let (_, _, _, _, _, addr) = pkt.into();
 
-        let frag_total = if first_frag_size < payload.as_ref().len() {
-            (1 + (payload.as_ref().len() - first_frag_size) / frag_size_addr_none + 1) as u8
-        } else {
-            1u8
-        };
+        let frag_total = u8::try_from(frag_total)
+            .map_err(|_| FragmentError::TooManyFragments(payload_len, frag_total))?;
 
-        Self {
+        Ok(Self {
             assoc_id,
             pkt_id,
             addr,
-            max_pkt_size,
             frag_total,
+            first_frag_size,
+            frag_size,
             next_frag_id: 0,
-            next_frag_start: 0,
             payload,
-            _marker: PhantomData,
-        }
+        })
+    }
+
+    /// Returns the total number of fragments
+    pub fn frag_total(&self) -> u8 {
+        self.frag_total
+    }
+
+    /// Returns the payload size of each fragment
+    pub fn frag_sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.frag_total).map(|frag_id| self.frag_range(frag_id).len())
+    }
+
+    fn frag_range(&self, frag_id: u8) -> Range<usize> {
+        let payload_len = self.payload.len();
+
+        let (start, size) = if frag_id == 0 {
+            (0, self.first_frag_size)
+        } else {
+            (
+                self.first_frag_size + (frag_id as usize - 1) * self.frag_size,
+                self.frag_size,
+            )
+        };
+
+        let start = start.min(payload_len);
+        start..(start + size).min(payload_len)
     }
 }
 
-impl<'a, P> Iterator for Fragments<'a, P>
-where
-    P: AsRef<[u8]> + 'a,
-{
+impl<'a> Iterator for Fragments<'a> {
     type Item = (Header, &'a [u8]);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.next_frag_id < self.frag_total {
-            let header_ref = Header::Packet(PacketHeader::new(0, 0, 0, 0, 0, self.addr.take()));
-
-            let payload_size = self.max_pkt_size - header_ref.len();
-            let next_frag_end =
-                (self.next_frag_start + payload_size).min(self.payload.as_ref().len());
-
-            let Header::Packet(pkt) = header_ref else {
-                unreachable!()
-            };
-            let (_, _, _, _, _, addr) = pkt.into();
+            let range = self.frag_range(self.next_frag_id);
 
             let header = Header::Packet(PacketHeader::new(
                 self.assoc_id,
                 self.pkt_id,
                 self.frag_total,
                 self.next_frag_id,
-                (next_frag_end - self.next_frag_start) as u16,
-                addr,
+                range.len() as u16,
+                self.addr.take(),
             ));
 
-            let payload_ptr = &(self.payload.as_ref()[self.next_frag_start]) as *const u8;
-            let payload =
-                unsafe { slice::from_raw_parts(payload_ptr, next_frag_end - self.next_frag_start) };
+            let payload = &self.payload[range];
 
             self.next_frag_id += 1;
-            self.next_frag_start = next_frag_end;
 
             Some((header, payload))
         } else {
             None
         }
     }
-}
 
-impl<P> ExactSizeIterator for Fragments<'_, P>
-where
-    P: AsRef<[u8]>,
-{
-    fn len(&self) -> usize {
-        self.frag_total as usize
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.frag_total - self.next_frag_id) as usize;
+        (len, Some(len))
     }
 }
+
+impl ExactSizeIterator for Fragments<'_> {}
+
+/// An error that can occur when fragmenting a packet
+#[derive(Debug, Error)]
+pub enum FragmentError {
+    #[error("maximum packet size {0} is too small to carry the packet")]
+    PacketSizeTooSmall(usize),
+    #[error("payload of {0} bytes needs {1} fragments, exceeding the maximum of 255")]
+    TooManyFragments(usize, usize),
+}
@@ -0,0 +1,353 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    mem,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+#[cfg(feature = "serde")]
+use serde::{
+    de::Error as DeError, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// Variable-length field that encodes the network address
+///
+/// ```plain
+/// +------+----------+----------+
+/// | TYPE |   ADDR   |   PORT   |
+/// +------+----------+----------+
+/// |  1   | Variable |    2     |
+/// +------+----------+----------+
+/// ```
+///
+/// where:
+///
+/// - `TYPE` - the address type
+/// - `ADDR` - the address
+/// - `PORT` - the port
+///
+/// The address type can be one of the following:
+///
+/// - `0xff`: None
+/// - `0x00`: Fully-qualified domain name (the first byte indicates the length of the domain name)
+/// - `0x01`: IPv4 address
+/// - `0x02`: IPv6 address
+///
+/// Address type `None` is used in `Packet` commands that is not the first fragment of a UDP packet.
+///
+/// The port number is encoded in 2 bytes after the Domain name / IP address.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Address {
+    #[default]
+    None,
+    DomainAddress(String, u16),
+    SocketAddress(SocketAddr),
+}
+
+impl Address {
+    pub const TYPE_CODE_NONE: u8 = 0xff;
+    pub const TYPE_CODE_DOMAIN: u8 = 0x00;
+    pub const TYPE_CODE_IPV4: u8 = 0x01;
+    pub const TYPE_CODE_IPV6: u8 = 0x02;
+
+    /// The maximum length of a domain name in bytes
+    pub const MAX_DOMAIN_LEN: usize = 255;
+
+    /// Creates a new domain address after checking the hostname syntax
+    ///
+    /// IP literals (IPv6 optionally in brackets) are normalized to `SocketAddress`.
+    pub fn new_domain(domain: impl Into<String>, port: u16) -> Result<Self, AddressError> {
+        let domain = domain.into();
+
+        if let Ok(ip) = domain.parse::<IpAddr>() {
+            return Ok(Self::SocketAddress(SocketAddr::new(ip, port)));
+        }
+
+        if let Some(ip) = domain
+            .strip_prefix('[')
+            .and_then(|domain| domain.strip_suffix(']'))
+            .and_then(|ip| ip.parse::<Ipv6Addr>().ok())
+        {
+            return Ok(Self::SocketAddress(SocketAddr::new(IpAddr::V6(ip), port)));
+        }
+
+        validate_domain(&domain)?;
+        Ok(Self::DomainAddress(domain, port))
+    }
+
+    /// Checks that the address can be encoded and, if it is a domain, that it has valid hostname syntax
+    pub fn validate(&self) -> Result<(), AddressError> {
+        match self {
+            Self::DomainAddress(domain, _) => validate_domain(domain),
+            Self::None | Self::SocketAddress(_) => Ok(()),
+        }
+    }
+
+    /// Returns the address type code
+    pub const fn type_code(&self) -> u8 {
+        match self {
+            Self::None => Self::TYPE_CODE_NONE,
+            Self::DomainAddress(_, _) => Self::TYPE_CODE_DOMAIN,
+            Self::SocketAddress(addr) => match addr {
+                SocketAddr::V4(_) => Self::TYPE_CODE_IPV4,
+                SocketAddr::V6(_) => Self::TYPE_CODE_IPV6,
+            },
+        }
+    }
+
+    /// Returns the serialized length of the address
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        1 + match self {
+            Address::None => 0,
+            Address::DomainAddress(addr, _) => 1 + addr.len() + 2,
+            Address::SocketAddress(SocketAddr::V4(_)) => 4 + 2,
+            Address::SocketAddress(SocketAddr::V6(_)) => 16 + 2,
+        }
+    }
+
+    /// Takes the address out, leaving a `None` in its place
+    pub fn take(&mut self) -> Self {
+        mem::take(self)
+    }
+
+    /// Returns `true` if the address is `None`
+    pub fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    /// Returns `true` if the address is a fully-qualified domain name
+    pub fn is_domain(&self) -> bool {
+        matches!(self, Self::DomainAddress(_, _))
+    }
+
+    /// Returns `true` if the address is an IPv4 address
+    pub fn is_ipv4(&self) -> bool {
+        matches!(self, Self::SocketAddress(SocketAddr::V4(_)))
+    }
+
+    /// Returns `true` if the address is an IPv6 address
+    pub fn is_ipv6(&self) -> bool {
+        matches!(self, Self::SocketAddress(SocketAddr::V6(_)))
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::DomainAddress(addr, port) => write!(f, "{addr}:{port}"),
+            Self::SocketAddress(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    /// Parses `host:port`, `ip:port` or `[ipv6]:port`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(addr) = s.parse() {
+            return Ok(Self::SocketAddress(addr));
+        }
+
+        let (host, port) = s
+            .rsplit_once(':')
+            .filter(|_| !s.ends_with(']'))
+            .ok_or(AddressError::MissingPort)?;
+        let port = port.parse().map_err(|_| AddressError::InvalidPort)?;
+
+        // an unbracketed IPv6 address is ambiguous with the port separator
+        if host.contains(':') && !host.starts_with('[') {
+            return Err(AddressError::InvalidDomain);
+        }
+
+        Self::new_domain(host, port)
+    }
+}
+
+impl From<SocketAddr> for Address {
+    fn from(addr: SocketAddr) -> Self {
+        Self::SocketAddress(addr)
+    }
+}
+
+impl TryFrom<(String, u16)> for Address {
+    type Error = AddressError;
+
+    fn try_from((domain, port): (String, u16)) -> Result<Self, Self::Error> {
+        Self::new_domain(domain, port)
+    }
+}
+
+impl TryFrom<(&str, u16)> for Address {
+    type Error = AddressError;
+
+    fn try_from((domain, port): (&str, u16)) -> Result<Self, Self::Error> {
+        Self::new_domain(domain, port)
+    }
+}
+
+impl TryFrom<(Vec<u8>, u16)> for Address {
+    type Error = AddressError;
+
+    fn try_from((domain, port): (Vec<u8>, u16)) -> Result<Self, Self::Error> {
+        let domain = String::from_utf8(domain).map_err(|_| AddressError::InvalidUtf8)?;
+        Self::new_domain(domain, port)
+    }
+}
+
+impl TryFrom<Address> for SocketAddr {
+    type Error = AddressError;
+
+    fn try_from(addr: Address) -> Result<Self, Self::Error> {
+        match addr {
+            Address::None => Err(AddressError::Empty),
+            Address::DomainAddress(_, _) => Err(AddressError::NotSocketAddress),
+            Address::SocketAddress(addr) => Ok(addr),
+        }
+    }
+}
+
+impl TryFrom<Address> for (String, u16) {
+    type Error = AddressError;
+
+    fn try_from(addr: Address) -> Result<Self, Self::Error> {
+        match addr {
+            Address::None => Err(AddressError::Empty),
+            Address::DomainAddress(domain, port) => Ok((domain, port)),
+            Address::SocketAddress(addr) => Ok((addr.ip().to_string(), addr.port())),
+        }
+    }
+}
+
+impl TryFrom<Address> for (Vec<u8>, u16) {
+    type Error = AddressError;
+
+    fn try_from(addr: Address) -> Result<Self, Self::Error> {
+        let (domain, port) = <(String, u16)>::try_from(addr)?;
+        Ok((domain.into_bytes(), port))
+    }
+}
+
+fn validate_domain(domain: &str) -> Result<(), AddressError> {
+    if domain.len() > Address::MAX_DOMAIN_LEN {
+        return Err(AddressError::DomainTooLong(domain.len()));
+    }
+
+    let name = domain.strip_suffix('.').unwrap_or(domain);
+
+    let is_valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    };
+
+    if name.split('.').all(is_valid_label) {
+        Ok(())
+    } else {
+        Err(AddressError::InvalidDomain)
+    }
+}
+
+/// Errors that can occur when constructing or converting an `Address`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AddressError {
+    /// The domain name exceeds `Address::MAX_DOMAIN_LEN` bytes
+    DomainTooLong(usize),
+    /// The domain name is not a valid hostname
+    InvalidDomain,
+    /// The domain name is not valid UTF-8
+    InvalidUtf8,
+    /// The port is missing from the address string
+    MissingPort,
+    /// The port is not a valid number
+    InvalidPort,
+    /// The address is `None`
+    Empty,
+    /// The address is a domain name, not a socket address
+    NotSocketAddress,
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::DomainTooLong(len) => write!(f, "domain name too long: {len} bytes"),
+            Self::InvalidDomain => write!(f, "invalid domain name"),
+            Self::InvalidUtf8 => write!(f, "domain name is not valid UTF-8"),
+            Self::MissingPort => write!(f, "missing port"),
+            Self::InvalidPort => write!(f, "invalid port"),
+            Self::Empty => write!(f, "empty address"),
+            Self::NotSocketAddress => write!(f, "not a socket address"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AddressError {}
+
+/// Serialized as `{"domain": "host", "port": port}`, `{"socket": "ip:port"}`, or `null` for `None`
+///
+/// The domain is kept as is, so an address deserializes into the same variant it was serialized from.
+#[cfg(feature = "serde")]
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::None => s.serialize_none(),
+            Self::DomainAddress(domain, port) => {
+                let mut st = s.serialize_struct("Address", 2)?;
+                st.serialize_field("domain", domain)?;
+                st.serialize_field("port", port)?;
+                st.end()
+            }
+            Self::SocketAddress(addr) => {
+                let mut st = s.serialize_struct("Address", 1)?;
+                st.serialize_field("socket", &addr.to_string())?;
+                st.end()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Repr {
+            #[serde(default)]
+            domain: Option<String>,
+            #[serde(default)]
+            port: Option<u16>,
+            #[serde(default)]
+            socket: Option<String>,
+        }
+
+        match Option::<Repr>::deserialize(d)? {
+            None => Ok(Self::None),
+            Some(Repr {
+                domain: Some(domain),
+                port: Some(port),
+                socket: None,
+            }) => Ok(Self::DomainAddress(domain, port)),
+            Some(Repr {
+                domain: None,
+                port: None,
+                socket: Some(addr),
+            }) => addr
+                .parse()
+                .map(Self::SocketAddress)
+                .map_err(DeError::custom),
+            Some(_) => Err(DeError::custom(
+                "expected either `domain` and `port`, or `socket`",
+            )),
+        }
+    }
+}
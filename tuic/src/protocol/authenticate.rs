@@ -1,5 +1,8 @@
 use uuid::Uuid;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Command `Authenticate`
 /// ```plain
 /// +------+-------+
@@ -13,9 +16,11 @@ use uuid::Uuid;
 ///
 /// - `UUID` - client UUID
 /// - `TOKEN` - client token. The client raw password is hashed into a 256-bit long token using [TLS Keying Material Exporter](https://www.rfc-editor.org/rfc/rfc5705) on current TLS session. While exporting, the `label` should be the client UUID and the `context` should be the raw password.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Authenticate {
     uuid: Uuid,
+    #[cfg_attr(feature = "serde", serde(with = "token_hex"))]
     token: [u8; 32],
 }
 
@@ -54,3 +59,39 @@ impl From<Authenticate> for (Uuid, [u8; 32]) {
         (auth.uuid, auth.token)
     }
 }
+
+/// (De)serializes the token as a hex string
+#[cfg(feature = "serde")]
+mod token_hex {
+    use alloc::string::String;
+    use core::fmt::Write;
+    use serde::{de::Error as DeError, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(token: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(64);
+
+        for b in token {
+            let _ = write!(hex, "{b:02x}");
+        }
+
+        s.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
+        let hex = String::deserialize(d)?;
+
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(DeError::custom("token must be 64 hex digits"));
+        }
+
+        let mut token = [0; 32];
+
+        for (b, digits) in token.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = core::str::from_utf8(digits).unwrap();
+            *b = u8::from_str_radix(digits, 16)
+                .map_err(|_| DeError::custom("token must be 64 hex digits"))?;
+        }
+
+        Ok(token)
+    }
+}
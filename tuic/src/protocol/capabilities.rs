@@ -0,0 +1,102 @@
+use core::ops::{BitAnd, BitOr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Command `Capabilities`
+///
+/// ```plain
+/// +------+
+/// | BITS |
+/// +------+
+/// |  8   |
+/// +------+
+/// ```
+///
+/// where:
+///
+/// - `BITS` - bitmap of the optional features supported by the sender
+///
+/// The client sends it right after `Authenticate` on the same stream, where servers that do not know the command never read it. A server that received it replies with its own on a new unidirectional stream after the client is authenticated.
+///
+/// Unknown bits are reserved for future features and must be ignored.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Capabilities {
+    bits: u64,
+}
+
+impl Capabilities {
+    const TYPE_CODE: u8 = 0x05;
+
+    /// UDP relay mode `native`
+    pub const UDP_RELAY_NATIVE: Self = Self::new(1 << 0);
+
+    /// UDP relay mode `quic`
+    pub const UDP_RELAY_QUIC: Self = Self::new(1 << 1);
+
+    /// `Extension` commands, see [`Extension`](crate::Extension)
+    pub const EXTENSION: Self = Self::new(1 << 2);
+
+    /// `ConnectResult` responses to `Connect`, see [`ConnectResult`](crate::ConnectResult)
+    pub const CONNECT_RESULT: Self = Self::new(1 << 3);
+
+    /// `Padding` commands, see [`Padding`](crate::Padding)
+    pub const PADDING: Self = Self::new(1 << 4);
+
+    /// UDP relay mode `stream`
+    pub const UDP_RELAY_STREAM: Self = Self::new(1 << 5);
+
+    /// Creates a new `Capabilities` command
+    pub const fn new(bits: u64) -> Self {
+        Self { bits }
+    }
+
+    /// Returns a `Capabilities` with no feature set
+    pub const fn empty() -> Self {
+        Self::new(0)
+    }
+
+    /// Returns the bitmap
+    pub const fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    /// Returns `true` if all features in `other` are set
+    pub const fn contains(&self, other: Self) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    /// Returns the command type code
+    pub const fn type_code() -> u8 {
+        Self::TYPE_CODE
+    }
+
+    /// Returns the serialized length of the command
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        8
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self::new(self.bits | rhs.bits)
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self::new(self.bits & rhs.bits)
+    }
+}
+
+impl From<Capabilities> for (u64,) {
+    fn from(caps: Capabilities) -> Self {
+        (caps.bits,)
+    }
+}
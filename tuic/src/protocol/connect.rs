@@ -1,5 +1,8 @@
 use super::Address;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Command `Connect`
 /// ```plain
 /// +----------+
@@ -12,7 +15,8 @@ use super::Address;
 /// where:
 ///
 /// - `ADDR` - target address
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Connect {
     addr: Address,
 }
@@ -0,0 +1,148 @@
+use core::fmt::{Display, Formatter, Result as FmtResult};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Command `ConnectResult`
+///
+/// ```plain
+/// +--------+
+/// | STATUS |
+/// +--------+
+/// |   1    |
+/// +--------+
+/// ```
+///
+/// where:
+///
+/// - `STATUS` - the outcome of connecting to the target address, see [`ConnectStatus`]
+///
+/// The server sends it on the stream of a `Connect` whose client requested it, before relaying any data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ConnectResult {
+    status: ConnectStatus,
+}
+
+impl ConnectResult {
+    const TYPE_CODE: u8 = 0x06;
+
+    /// Creates a new `ConnectResult` command
+    pub const fn new(status: ConnectStatus) -> Self {
+        Self { status }
+    }
+
+    /// Returns the status
+    pub const fn status(&self) -> ConnectStatus {
+        self.status
+    }
+
+    /// Returns the command type code
+    pub const fn type_code() -> u8 {
+        Self::TYPE_CODE
+    }
+
+    /// Returns the serialized length of the command
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        1
+    }
+}
+
+impl From<ConnectResult> for (ConnectStatus,) {
+    fn from(result: ConnectResult) -> Self {
+        (result.status,)
+    }
+}
+
+/// The outcome of a `Connect`, carried by [`ConnectResult`]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ConnectStatus {
+    /// `0x00` - the TCP connection to the target is established
+    Connected,
+    /// `0x01` - the target refused the connection
+    Refused,
+    /// `0x02` - the target host or network is unreachable
+    Unreachable,
+    /// `0x03` - the target domain name could not be resolved
+    DnsFailure,
+    /// `0x04` - the server does not allow connecting to the target
+    Denied,
+    /// `0x05` - connecting to the target timed out
+    Timeout,
+    /// `0xff` - any other failure
+    Failed,
+    /// A status code not known by this implementation, which should be treated as a failure
+    Unknown(u8),
+}
+
+impl ConnectStatus {
+    const CONNECTED: u8 = 0x00;
+    const REFUSED: u8 = 0x01;
+    const UNREACHABLE: u8 = 0x02;
+    const DNS_FAILURE: u8 = 0x03;
+    const DENIED: u8 = 0x04;
+    const TIMEOUT: u8 = 0x05;
+    const FAILED: u8 = 0xff;
+
+    /// Returns the status code
+    pub const fn code(&self) -> u8 {
+        match self {
+            Self::Connected => Self::CONNECTED,
+            Self::Refused => Self::REFUSED,
+            Self::Unreachable => Self::UNREACHABLE,
+            Self::DnsFailure => Self::DNS_FAILURE,
+            Self::Denied => Self::DENIED,
+            Self::Timeout => Self::TIMEOUT,
+            Self::Failed => Self::FAILED,
+            Self::Unknown(code) => *code,
+        }
+    }
+
+    /// Returns `true` if the connection is established
+    pub const fn is_connected(&self) -> bool {
+        matches!(self, Self::Connected)
+    }
+}
+
+impl From<u8> for ConnectStatus {
+    fn from(code: u8) -> Self {
+        match code {
+            Self::CONNECTED => Self::Connected,
+            Self::REFUSED => Self::Refused,
+            Self::UNREACHABLE => Self::Unreachable,
+            Self::DNS_FAILURE => Self::DnsFailure,
+            Self::DENIED => Self::Denied,
+            Self::TIMEOUT => Self::Timeout,
+            Self::FAILED => Self::Failed,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl Display for ConnectStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Connected => write!(f, "connected"),
+            Self::Refused => write!(f, "connection refused"),
+            Self::Unreachable => write!(f, "target unreachable"),
+            Self::DnsFailure => write!(f, "DNS resolution failed"),
+            Self::Denied => write!(f, "denied by server policy"),
+            Self::Timeout => write!(f, "connection timed out"),
+            Self::Failed => write!(f, "connection failed"),
+            Self::Unknown(code) => write!(f, "unknown status {code:#04x}"),
+        }
+    }
+}
+
+impl From<ConnectStatus> for u8 {
+    fn from(status: ConnectStatus) -> Self {
+        status.code()
+    }
+}
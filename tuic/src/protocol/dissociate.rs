@@ -1,3 +1,6 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Command `Dissociate`
 ///
 /// ```plain
@@ -11,7 +14,8 @@
 /// where:
 ///
 /// - `ASSOC_ID` - UDP relay session ID
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Dissociate {
     assoc_id: u16,
 }
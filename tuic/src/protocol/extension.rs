@@ -0,0 +1,135 @@
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Result as FmtResult};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Command `Extension`
+///
+/// ```plain
+/// +-----+----------+
+/// | LEN | PAYLOAD  |
+/// +-----+----------+
+/// |  2  | Variable |
+/// +-----+----------+
+/// ```
+///
+/// where:
+///
+/// - `LEN` - length of the payload
+/// - `PAYLOAD` - extension specific data
+///
+/// Command types `0x80` to `0xff` are reserved for extensions. Their meaning is up to the implementation, and a peer that does not know an extension should ignore it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(try_from = "ExtensionFields")
+)]
+pub struct Extension {
+    type_code: u8,
+    payload: Vec<u8>,
+}
+
+impl Extension {
+    /// The lowest command type code reserved for extensions
+    pub const TYPE_CODE_MIN: u8 = 0x80;
+
+    /// The highest command type code reserved for extensions
+    pub const TYPE_CODE_MAX: u8 = 0xff;
+
+    /// The maximum length of the payload
+    pub const MAX_PAYLOAD_LEN: usize = u16::MAX as usize;
+
+    /// Creates a new `Extension` command
+    pub fn new(type_code: u8, payload: impl Into<Vec<u8>>) -> Result<Self, ExtensionError> {
+        let payload = payload.into();
+
+        if !Self::is_extension(type_code) {
+            return Err(ExtensionError::InvalidTypeCode(type_code));
+        }
+
+        if payload.len() > Self::MAX_PAYLOAD_LEN {
+            return Err(ExtensionError::PayloadTooLong(payload.len()));
+        }
+
+        Ok(Self { type_code, payload })
+    }
+
+    /// Creates a new `Extension` command from a type code and a payload that are known to be valid
+    #[cfg(any(
+        feature = "async_marshal",
+        feature = "marshal",
+        feature = "tokio_marshal"
+    ))]
+    pub(crate) const fn new_unchecked(type_code: u8, payload: Vec<u8>) -> Self {
+        Self { type_code, payload }
+    }
+
+    /// Returns `true` if the command type code is reserved for extensions
+    pub const fn is_extension(type_code: u8) -> bool {
+        type_code >= Self::TYPE_CODE_MIN
+    }
+
+    /// Returns the command type code
+    pub const fn type_code(&self) -> u8 {
+        self.type_code
+    }
+
+    /// Returns the payload
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Returns the serialized length of the command
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        2 + self.payload.len()
+    }
+}
+
+impl From<Extension> for (u8, Vec<u8>) {
+    fn from(ext: Extension) -> Self {
+        (ext.type_code, ext.payload)
+    }
+}
+
+/// Validated on deserialization
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct ExtensionFields {
+    type_code: u8,
+    payload: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ExtensionFields> for Extension {
+    type Error = ExtensionError;
+
+    fn try_from(fields: ExtensionFields) -> Result<Self, Self::Error> {
+        Self::new(fields.type_code, fields.payload)
+    }
+}
+
+/// Errors that can occur when creating an [`Extension`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExtensionError {
+    /// The command type code is not in the range reserved for extensions
+    InvalidTypeCode(u8),
+    /// The payload exceeds `Extension::MAX_PAYLOAD_LEN` bytes
+    PayloadTooLong(usize),
+}
+
+impl Display for ExtensionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::InvalidTypeCode(type_code) => {
+                write!(f, "invalid extension type code: {type_code:#04x}")
+            }
+            Self::PayloadTooLong(len) => write!(f, "extension payload too long: {len} bytes"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExtensionError {}
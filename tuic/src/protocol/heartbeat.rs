@@ -1,3 +1,6 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Command `Heartbeat`
 /// ```plain
 /// +-+
@@ -6,7 +9,8 @@
 /// | |
 /// +-+
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Heartbeat;
 
 impl Heartbeat {
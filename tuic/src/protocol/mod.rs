@@ -1,18 +1,28 @@
-use std::{
-    fmt::{Display, Formatter, Result as FmtResult},
-    mem,
-    net::SocketAddr,
-};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+mod address;
 mod authenticate;
+mod capabilities;
 mod connect;
+mod connect_result;
 mod dissociate;
+mod extension;
 mod heartbeat;
 mod packet;
+mod padding;
 
 pub use self::{
-    authenticate::Authenticate, connect::Connect, dissociate::Dissociate, heartbeat::Heartbeat,
+    address::{Address, AddressError},
+    authenticate::Authenticate,
+    capabilities::Capabilities,
+    connect::Connect,
+    connect_result::{ConnectResult, ConnectStatus},
+    dissociate::Dissociate,
+    extension::{Extension, ExtensionError},
+    heartbeat::Heartbeat,
     packet::Packet,
+    padding::Padding,
 };
 
 /// The TUIC protocol version
@@ -35,23 +45,37 @@ pub const VERSION: u8 = 0x05;
 ///
 /// ## Command Types
 ///
-/// There are five types of command:
+/// There are eight types of command:
 ///
 /// - `0x00` - `Authenticate` - for authenticating the multiplexed stream
 /// - `0x01` - `Connect` - for establishing a TCP relay
 /// - `0x02` - `Packet` - for relaying (fragmented part of) a UDP packet
 /// - `0x03` - `Dissociate` - for terminating a UDP relaying session
 /// - `0x04` - `Heartbeat` - for keeping the QUIC connection alive
+/// - `0x05` - `Capabilities` - for advertising optional features
+/// - `0x06` - `ConnectResult` - for reporting the outcome of a `Connect`
+/// - `0x07` - `Padding` - for obfuscating the size of other commands
+///
+/// Command types `0x80` to `0xff` are reserved for `Extension`s, see [`Extension`]
 ///
 /// Command `Connect` and `Packet` carry payload (stream / packet fragment)
 #[non_exhaustive]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
 pub enum Header {
     Authenticate(Authenticate),
     Connect(Connect),
     Packet(Packet),
     Dissociate(Dissociate),
     Heartbeat(Heartbeat),
+    Capabilities(Capabilities),
+    ConnectResult(ConnectResult),
+    Padding(Padding),
+    Extension(Extension),
 }
 
 impl Header {
@@ -60,6 +84,9 @@ impl Header {
     pub const TYPE_CODE_PACKET: u8 = Packet::type_code();
     pub const TYPE_CODE_DISSOCIATE: u8 = Dissociate::type_code();
     pub const TYPE_CODE_HEARTBEAT: u8 = Heartbeat::type_code();
+    pub const TYPE_CODE_CAPABILITIES: u8 = Capabilities::type_code();
+    pub const TYPE_CODE_CONNECT_RESULT: u8 = ConnectResult::type_code();
+    pub const TYPE_CODE_PADDING: u8 = Padding::type_code();
 
     /// Returns the command type code
     pub const fn type_code(&self) -> u8 {
@@ -69,6 +96,10 @@ impl Header {
             Self::Packet(_) => Packet::type_code(),
             Self::Dissociate(_) => Dissociate::type_code(),
             Self::Heartbeat(_) => Heartbeat::type_code(),
+            Self::Capabilities(_) => Capabilities::type_code(),
+            Self::ConnectResult(_) => ConnectResult::type_code(),
+            Self::Padding(_) => Padding::type_code(),
+            Self::Extension(ext) => ext.type_code(),
         }
     }
 
@@ -81,110 +112,10 @@ impl Header {
             Self::Packet(packet) => packet.len(),
             Self::Dissociate(dissociate) => dissociate.len(),
             Self::Heartbeat(heartbeat) => heartbeat.len(),
+            Self::Capabilities(caps) => caps.len(),
+            Self::ConnectResult(result) => result.len(),
+            Self::Padding(padding) => padding.len(),
+            Self::Extension(ext) => ext.len(),
         }
     }
 }
-
-/// Variable-length field that encodes the network address
-///
-/// ```plain
-/// +------+----------+----------+
-/// | TYPE |   ADDR   |   PORT   |
-/// +------+----------+----------+
-/// |  1   | Variable |    2     |
-/// +------+----------+----------+
-/// ```
-///
-/// where:
-///
-/// - `TYPE` - the address type
-/// - `ADDR` - the address
-/// - `PORT` - the port
-///
-/// The address type can be one of the following:
-///
-/// - `0xff`: None
-/// - `0x00`: Fully-qualified domain name (the first byte indicates the length of the domain name)
-/// - `0x01`: IPv4 address
-/// - `0x02`: IPv6 address
-///
-/// Address type `None` is used in `Packet` commands that is not the first fragment of a UDP packet.
-///
-/// The port number is encoded in 2 bytes after the Domain name / IP address.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub enum Address {
-    None,
-    DomainAddress(String, u16),
-    SocketAddress(SocketAddr),
-}
-
-impl Address {
-    pub const TYPE_CODE_NONE: u8 = 0xff;
-    pub const TYPE_CODE_DOMAIN: u8 = 0x00;
-    pub const TYPE_CODE_IPV4: u8 = 0x01;
-    pub const TYPE_CODE_IPV6: u8 = 0x02;
-
-    /// Returns the address type code
-    pub const fn type_code(&self) -> u8 {
-        match self {
-            Self::None => Self::TYPE_CODE_NONE,
-            Self::DomainAddress(_, _) => Self::TYPE_CODE_DOMAIN,
-            Self::SocketAddress(addr) => match addr {
-                SocketAddr::V4(_) => Self::TYPE_CODE_IPV4,
-                SocketAddr::V6(_) => Self::TYPE_CODE_IPV6,
-            },
-        }
-    }
-
-    /// Returns the serialized length of the address
-    #[allow(clippy::len_without_is_empty)]
-    pub fn len(&self) -> usize {
-        1 + match self {
-            Address::None => 0,
-            Address::DomainAddress(addr, _) => 1 + addr.len() + 2,
-            Address::SocketAddress(SocketAddr::V4(_)) => 4 + 2,
-            Address::SocketAddress(SocketAddr::V6(_)) => 16 + 2,
-        }
-    }
-
-    /// Takes the address out, leaving a `None` in its place
-    pub fn take(&mut self) -> Self {
-        mem::take(self)
-    }
-
-    /// Returns `true` if the address is `None`
-    pub fn is_none(&self) -> bool {
-        matches!(self, Self::None)
-    }
-
-    /// Returns `true` if the address is a fully-qualified domain name
-    pub fn is_domain(&self) -> bool {
-        matches!(self, Self::DomainAddress(_, _))
-    }
-
-    /// Returns `true` if the address is an IPv4 address
-    pub fn is_ipv4(&self) -> bool {
-        matches!(self, Self::SocketAddress(SocketAddr::V4(_)))
-    }
-
-    /// Returns `true` if the address is an IPv6 address
-    pub fn is_ipv6(&self) -> bool {
-        matches!(self, Self::SocketAddress(SocketAddr::V6(_)))
-    }
-}
-
-impl Display for Address {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        match self {
-            Self::None => write!(f, "none"),
-            Self::DomainAddress(addr, port) => write!(f, "{addr}:{port}"),
-            Self::SocketAddress(addr) => write!(f, "{addr}"),
-        }
-    }
-}
-
-impl Default for Address {
-    fn default() -> Self {
-        Self::None
-    }
-}
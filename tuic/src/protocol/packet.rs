@@ -1,5 +1,8 @@
 use super::Address;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Command `Packet`
 /// ```plain
 /// +----------+--------+------------+---------+------+----------+
@@ -17,7 +20,8 @@ use super::Address;
 /// - `FRAG_ID` - fragment ID of the UDP packet
 /// - `SIZE` - length of the (fragmented) UDP packet
 /// - `ADDR` - target (from client) or source (from server) address
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Packet {
     assoc_id: u16,
     pkt_id: u16,
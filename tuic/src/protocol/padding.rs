@@ -0,0 +1,55 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Command `Padding`
+///
+/// ```plain
+/// +-----+----------+
+/// | LEN | PADDING  |
+/// +-----+----------+
+/// |  2  | Variable |
+/// +-----+----------+
+/// ```
+///
+/// where:
+///
+/// - `LEN` - length of the padding
+/// - `PADDING` - filler bytes, discarded by the receiver
+///
+/// It hides the size of the commands it is sent along with. The filler is written as random bytes (zeros without the `std` feature), and its content carries no meaning.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Padding {
+    len: u16,
+}
+
+impl Padding {
+    const TYPE_CODE: u8 = 0x07;
+
+    /// Creates a new `Padding` command with `len` filler bytes
+    pub const fn new(len: u16) -> Self {
+        Self { len }
+    }
+
+    /// Returns the length of the filler
+    pub const fn padding_len(&self) -> u16 {
+        self.len
+    }
+
+    /// Returns the command type code
+    pub const fn type_code() -> u8 {
+        Self::TYPE_CODE
+    }
+
+    /// Returns the serialized length of the command
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        2 + self.len as usize
+    }
+}
+
+impl From<Padding> for (u16,) {
+    fn from(padding: Padding) -> Self {
+        (padding.len,)
+    }
+}
@@ -1,13 +1,20 @@
-use crate::{Address, Authenticate, Connect, Dissociate, Header, Heartbeat, Packet, VERSION};
-use futures_util::{AsyncRead, AsyncReadExt};
-use std::{
-    io::{Error as IoError, Read},
-    net::SocketAddr,
-    string::FromUtf8Error,
+use crate::{
+    Address, Authenticate, Capabilities, Connect, ConnectResult, ConnectStatus, Dissociate,
+    Extension, Header, Heartbeat, Packet, Padding, VERSION,
 };
+use std::{io::Error as IoError, net::SocketAddr, str::Utf8Error};
 use thiserror::Error;
 use uuid::{Error as UuidError, Uuid};
 
+#[cfg(feature = "async_marshal")]
+use futures_util::{AsyncRead, AsyncReadExt};
+
+#[cfg(feature = "marshal")]
+use std::io::Read;
+
+#[cfg(feature = "tokio_marshal")]
+use tokio::io::{AsyncRead as TokioAsyncRead, AsyncReadExt as TokioAsyncReadExt};
+
 impl Header {
     /// Unmarshals a header from an `AsyncRead` stream
     #[cfg(feature = "async_marshal")]
@@ -32,6 +39,16 @@ impl Header {
             Header::TYPE_CODE_PACKET => Packet::async_read(s).await.map(Self::Packet),
             Header::TYPE_CODE_DISSOCIATE => Dissociate::async_read(s).await.map(Self::Dissociate),
             Header::TYPE_CODE_HEARTBEAT => Heartbeat::async_read(s).await.map(Self::Heartbeat),
+            Header::TYPE_CODE_CAPABILITIES => {
+                Capabilities::async_read(s).await.map(Self::Capabilities)
+            }
+            Header::TYPE_CODE_CONNECT_RESULT => {
+                ConnectResult::async_read(s).await.map(Self::ConnectResult)
+            }
+            Header::TYPE_CODE_PADDING => Padding::async_read(s).await.map(Self::Padding),
+            Extension::TYPE_CODE_MIN..=Extension::TYPE_CODE_MAX => {
+                Extension::async_read(s, cmd).await.map(Self::Extension)
+            }
             _ => Err(UnmarshalError::InvalidCommand(cmd)),
         }
     }
@@ -57,6 +74,51 @@ impl Header {
             Header::TYPE_CODE_PACKET => Packet::read(s).map(Self::Packet),
             Header::TYPE_CODE_DISSOCIATE => Dissociate::read(s).map(Self::Dissociate),
             Header::TYPE_CODE_HEARTBEAT => Heartbeat::read(s).map(Self::Heartbeat),
+            Header::TYPE_CODE_CAPABILITIES => Capabilities::read(s).map(Self::Capabilities),
+            Header::TYPE_CODE_CONNECT_RESULT => ConnectResult::read(s).map(Self::ConnectResult),
+            Header::TYPE_CODE_PADDING => Padding::read(s).map(Self::Padding),
+            Extension::TYPE_CODE_MIN..=Extension::TYPE_CODE_MAX => {
+                Extension::read(s, cmd).map(Self::Extension)
+            }
+            _ => Err(UnmarshalError::InvalidCommand(cmd)),
+        }
+    }
+
+    /// Unmarshals a header from a tokio `AsyncRead` stream
+    #[cfg(feature = "tokio_marshal")]
+    pub async fn tokio_unmarshal(
+        s: &mut (impl TokioAsyncRead + Unpin),
+    ) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 1];
+        s.read_exact(&mut buf).await?;
+        let ver = buf[0];
+
+        if ver != VERSION {
+            return Err(UnmarshalError::InvalidVersion(ver));
+        }
+
+        let mut buf = [0; 1];
+        s.read_exact(&mut buf).await?;
+        let cmd = buf[0];
+
+        match cmd {
+            Header::TYPE_CODE_AUTHENTICATE => {
+                Authenticate::tokio_read(s).await.map(Self::Authenticate)
+            }
+            Header::TYPE_CODE_CONNECT => Connect::tokio_read(s).await.map(Self::Connect),
+            Header::TYPE_CODE_PACKET => Packet::tokio_read(s).await.map(Self::Packet),
+            Header::TYPE_CODE_DISSOCIATE => Dissociate::tokio_read(s).await.map(Self::Dissociate),
+            Header::TYPE_CODE_HEARTBEAT => Heartbeat::tokio_read(s).await.map(Self::Heartbeat),
+            Header::TYPE_CODE_CAPABILITIES => {
+                Capabilities::tokio_read(s).await.map(Self::Capabilities)
+            }
+            Header::TYPE_CODE_CONNECT_RESULT => {
+                ConnectResult::tokio_read(s).await.map(Self::ConnectResult)
+            }
+            Header::TYPE_CODE_PADDING => Padding::tokio_read(s).await.map(Self::Padding),
+            Extension::TYPE_CODE_MIN..=Extension::TYPE_CODE_MAX => {
+                Extension::tokio_read(s, cmd).await.map(Self::Extension)
+            }
             _ => Err(UnmarshalError::InvalidCommand(cmd)),
         }
     }
@@ -80,7 +142,7 @@ impl Address {
                 s.read_exact(&mut buf).await?;
                 let port = u16::from_be_bytes([buf[len], buf[len + 1]]);
                 buf.truncate(len);
-                let domain = String::from_utf8(buf)?;
+                let domain = String::from_utf8(buf).map_err(|err| err.utf8_error())?;
 
                 Ok(Self::DomainAddress(domain, port))
             }
@@ -129,7 +191,7 @@ impl Address {
                 s.read_exact(&mut buf)?;
                 let port = u16::from_be_bytes([buf[len], buf[len + 1]]);
                 buf.truncate(len);
-                let domain = String::from_utf8(buf)?;
+                let domain = String::from_utf8(buf).map_err(|err| err.utf8_error())?;
 
                 Ok(Self::DomainAddress(domain, port))
             }
@@ -160,6 +222,58 @@ impl Address {
             _ => Err(UnmarshalError::InvalidAddressType(type_code)),
         }
     }
+
+    /// Unmarshals an address from a tokio `AsyncRead` stream
+    #[cfg(feature = "tokio_marshal")]
+    pub async fn tokio_unmarshal(
+        s: &mut (impl TokioAsyncRead + Unpin),
+    ) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 1];
+        s.read_exact(&mut buf).await?;
+        let type_code = buf[0];
+
+        match type_code {
+            Address::TYPE_CODE_NONE => Ok(Self::None),
+            Address::TYPE_CODE_DOMAIN => {
+                let mut buf = [0; 1];
+                s.read_exact(&mut buf).await?;
+                let len = buf[0] as usize;
+
+                let mut buf = vec![0; len + 2];
+                s.read_exact(&mut buf).await?;
+                let port = u16::from_be_bytes([buf[len], buf[len + 1]]);
+                buf.truncate(len);
+                let domain = String::from_utf8(buf).map_err(|err| err.utf8_error())?;
+
+                Ok(Self::DomainAddress(domain, port))
+            }
+            Address::TYPE_CODE_IPV4 => {
+                let mut buf = [0; 6];
+                s.read_exact(&mut buf).await?;
+                let ip = [buf[0], buf[1], buf[2], buf[3]];
+                let port = u16::from_be_bytes([buf[4], buf[5]]);
+                Ok(Self::SocketAddress(SocketAddr::from((ip, port))))
+            }
+            Address::TYPE_CODE_IPV6 => {
+                let mut buf = [0; 18];
+                s.read_exact(&mut buf).await?;
+                let ip = [
+                    u16::from_be_bytes([buf[0], buf[1]]),
+                    u16::from_be_bytes([buf[2], buf[3]]),
+                    u16::from_be_bytes([buf[4], buf[5]]),
+                    u16::from_be_bytes([buf[6], buf[7]]),
+                    u16::from_be_bytes([buf[8], buf[9]]),
+                    u16::from_be_bytes([buf[10], buf[11]]),
+                    u16::from_be_bytes([buf[12], buf[13]]),
+                    u16::from_be_bytes([buf[14], buf[15]]),
+                ];
+                let port = u16::from_be_bytes([buf[16], buf[17]]);
+
+                Ok(Self::SocketAddress(SocketAddr::from((ip, port))))
+            }
+            _ => Err(UnmarshalError::InvalidAddressType(type_code)),
+        }
+    }
 }
 
 impl Authenticate {
@@ -180,6 +294,15 @@ impl Authenticate {
         let token = TryFrom::try_from(&buf[16..]).unwrap();
         Ok(Self::new(uuid, token))
     }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 48];
+        s.read_exact(&mut buf).await?;
+        let uuid = Uuid::from_slice(&buf[..16])?;
+        let token = TryFrom::try_from(&buf[16..]).unwrap();
+        Ok(Self::new(uuid, token))
+    }
 }
 
 impl Connect {
@@ -192,6 +315,11 @@ impl Connect {
     fn read(s: &mut impl Read) -> Result<Self, UnmarshalError> {
         Ok(Self::new(Address::read(s)?))
     }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        Ok(Self::new(Address::tokio_unmarshal(s).await?))
+    }
 }
 
 impl Packet {
@@ -224,6 +352,21 @@ impl Packet {
 
         Ok(Self::new(assoc_id, pkt_id, frag_total, frag_id, size, addr))
     }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 8];
+        s.read_exact(&mut buf).await?;
+
+        let assoc_id = u16::from_be_bytes([buf[0], buf[1]]);
+        let pkt_id = u16::from_be_bytes([buf[2], buf[3]]);
+        let frag_total = buf[4];
+        let frag_id = buf[5];
+        let size = u16::from_be_bytes([buf[6], buf[7]]);
+        let addr = Address::tokio_unmarshal(s).await?;
+
+        Ok(Self::new(assoc_id, pkt_id, frag_total, frag_id, size, addr))
+    }
 }
 
 impl Dissociate {
@@ -242,6 +385,14 @@ impl Dissociate {
         let assoc_id = u16::from_be_bytes(buf);
         Ok(Self::new(assoc_id))
     }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf).await?;
+        let assoc_id = u16::from_be_bytes(buf);
+        Ok(Self::new(assoc_id))
+    }
 }
 
 impl Heartbeat {
@@ -254,6 +405,154 @@ impl Heartbeat {
     fn read(_s: &mut impl Read) -> Result<Self, UnmarshalError> {
         Ok(Self::new())
     }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(_s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        Ok(Self::new())
+    }
+}
+
+impl Capabilities {
+    #[cfg(feature = "async_marshal")]
+    async fn async_read(s: &mut (impl AsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 8];
+        s.read_exact(&mut buf).await?;
+        Ok(Self::new(u64::from_be_bytes(buf)))
+    }
+
+    #[cfg(feature = "marshal")]
+    fn read(s: &mut impl Read) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 8];
+        s.read_exact(&mut buf)?;
+        Ok(Self::new(u64::from_be_bytes(buf)))
+    }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 8];
+        s.read_exact(&mut buf).await?;
+        Ok(Self::new(u64::from_be_bytes(buf)))
+    }
+}
+
+impl ConnectResult {
+    #[cfg(feature = "async_marshal")]
+    async fn async_read(s: &mut (impl AsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 1];
+        s.read_exact(&mut buf).await?;
+        Ok(Self::new(ConnectStatus::from(buf[0])))
+    }
+
+    #[cfg(feature = "marshal")]
+    fn read(s: &mut impl Read) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 1];
+        s.read_exact(&mut buf)?;
+        Ok(Self::new(ConnectStatus::from(buf[0])))
+    }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 1];
+        s.read_exact(&mut buf).await?;
+        Ok(Self::new(ConnectStatus::from(buf[0])))
+    }
+}
+
+impl Padding {
+    #[cfg(feature = "async_marshal")]
+    async fn async_read(s: &mut (impl AsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf).await?;
+        let len = u16::from_be_bytes(buf);
+
+        // the filler is discarded
+        let mut buf = [0; 256];
+        let mut left = len as usize;
+        while left > 0 {
+            let chunk = left.min(buf.len());
+            s.read_exact(&mut buf[..chunk]).await?;
+            left -= chunk;
+        }
+
+        Ok(Self::new(len))
+    }
+
+    #[cfg(feature = "marshal")]
+    fn read(s: &mut impl Read) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf)?;
+        let len = u16::from_be_bytes(buf);
+
+        // the filler is discarded
+        let mut buf = [0; 256];
+        let mut left = len as usize;
+        while left > 0 {
+            let chunk = left.min(buf.len());
+            s.read_exact(&mut buf[..chunk])?;
+            left -= chunk;
+        }
+
+        Ok(Self::new(len))
+    }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(s: &mut (impl TokioAsyncRead + Unpin)) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf).await?;
+        let len = u16::from_be_bytes(buf);
+
+        // the filler is discarded
+        let mut buf = [0; 256];
+        let mut left = len as usize;
+        while left > 0 {
+            let chunk = left.min(buf.len());
+            s.read_exact(&mut buf[..chunk]).await?;
+            left -= chunk;
+        }
+
+        Ok(Self::new(len))
+    }
+}
+
+impl Extension {
+    #[cfg(feature = "async_marshal")]
+    async fn async_read(
+        s: &mut (impl AsyncRead + Unpin),
+        type_code: u8,
+    ) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf).await?;
+        let len = u16::from_be_bytes(buf) as usize;
+
+        let mut payload = vec![0; len];
+        s.read_exact(&mut payload).await?;
+        Ok(Self::new_unchecked(type_code, payload))
+    }
+
+    #[cfg(feature = "marshal")]
+    fn read(s: &mut impl Read, type_code: u8) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf)?;
+        let len = u16::from_be_bytes(buf) as usize;
+
+        let mut payload = vec![0; len];
+        s.read_exact(&mut payload)?;
+        Ok(Self::new_unchecked(type_code, payload))
+    }
+
+    #[cfg(feature = "tokio_marshal")]
+    async fn tokio_read(
+        s: &mut (impl TokioAsyncRead + Unpin),
+        type_code: u8,
+    ) -> Result<Self, UnmarshalError> {
+        let mut buf = [0; 2];
+        s.read_exact(&mut buf).await?;
+        let len = u16::from_be_bytes(buf) as usize;
+
+        let mut payload = vec![0; len];
+        s.read_exact(&mut payload).await?;
+        Ok(Self::new_unchecked(type_code, payload))
+    }
 }
 
 /// Errors that can occur when unmarshalling a packet
@@ -270,5 +569,5 @@ pub enum UnmarshalError {
     #[error("invalid address type: {0}")]
     InvalidAddressType(u8),
     #[error("address parsing error: {0}")]
-    AddressParse(#[from] FromUtf8Error),
+    AddressParse(#[from] Utf8Error),
 }
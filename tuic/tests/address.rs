@@ -0,0 +1,210 @@
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tuic::{Address, AddressError, Connect, Header, Packet};
+
+fn long_domain() -> String {
+    // 4 labels of 63 characters joined by dots, 255 bytes in total
+    vec!["a".repeat(63); 4].join(".")
+}
+
+#[test]
+fn new_domain() {
+    assert_eq!(
+        Address::new_domain("example.com", 443),
+        Ok(Address::DomainAddress("example.com".to_owned(), 443))
+    );
+    assert_eq!(
+        Address::new_domain("example.com.", 443),
+        Ok(Address::DomainAddress("example.com.".to_owned(), 443))
+    );
+    assert_eq!(
+        Address::new_domain(long_domain(), 80),
+        Ok(Address::DomainAddress(long_domain(), 80))
+    );
+
+    assert_eq!(
+        Address::new_domain(format!("{}a", long_domain()), 80),
+        Err(AddressError::DomainTooLong(256))
+    );
+    assert_eq!(
+        Address::new_domain("foo bar", 80),
+        Err(AddressError::InvalidDomain)
+    );
+    assert_eq!(
+        Address::new_domain("-example.com", 80),
+        Err(AddressError::InvalidDomain)
+    );
+    assert_eq!(
+        Address::new_domain("example..com", 80),
+        Err(AddressError::InvalidDomain)
+    );
+    assert_eq!(
+        Address::new_domain("", 80),
+        Err(AddressError::InvalidDomain)
+    );
+}
+
+#[test]
+fn new_domain_ip_literal() {
+    assert_eq!(
+        Address::new_domain("127.0.0.1", 80),
+        Ok(Address::SocketAddress(SocketAddr::from((
+            [127, 0, 0, 1],
+            80
+        ))))
+    );
+    assert_eq!(
+        Address::new_domain("::1", 80),
+        Ok(Address::SocketAddress(SocketAddr::new(
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            80
+        )))
+    );
+    assert_eq!(
+        Address::new_domain("[::1]", 80),
+        Ok(Address::SocketAddress(SocketAddr::new(
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            80
+        )))
+    );
+}
+
+#[test]
+fn from_str() {
+    assert_eq!(
+        "example.com:443".parse(),
+        Ok(Address::DomainAddress("example.com".to_owned(), 443))
+    );
+    assert_eq!(
+        "127.0.0.1:80".parse(),
+        Ok(Address::SocketAddress(SocketAddr::from((
+            [127, 0, 0, 1],
+            80
+        ))))
+    );
+    assert_eq!(
+        "[::1]:80".parse(),
+        Ok(Address::SocketAddress(SocketAddr::new(
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            80
+        )))
+    );
+
+    assert_eq!(
+        "example.com".parse::<Address>(),
+        Err(AddressError::MissingPort)
+    );
+    assert_eq!("[::1]".parse::<Address>(), Err(AddressError::MissingPort));
+    assert_eq!(
+        "example.com:http".parse::<Address>(),
+        Err(AddressError::InvalidPort)
+    );
+    assert_eq!(
+        "example.com:65536".parse::<Address>(),
+        Err(AddressError::InvalidPort)
+    );
+    assert_eq!(
+        "::1:80".parse::<Address>(),
+        Err(AddressError::InvalidDomain)
+    );
+    assert_eq!(
+        format!("{}a:80", long_domain()).parse::<Address>(),
+        Err(AddressError::DomainTooLong(256))
+    );
+}
+
+#[test]
+fn try_from() {
+    assert_eq!(
+        Address::try_from(("example.com", 80)),
+        Ok(Address::DomainAddress("example.com".to_owned(), 80))
+    );
+    assert_eq!(
+        Address::try_from(("example.com".to_owned(), 80)),
+        Ok(Address::DomainAddress("example.com".to_owned(), 80))
+    );
+    assert_eq!(
+        Address::try_from((b"example.com".to_vec(), 80)),
+        Ok(Address::DomainAddress("example.com".to_owned(), 80))
+    );
+    assert_eq!(
+        Address::try_from((b"127.0.0.1".to_vec(), 80)),
+        Ok(Address::SocketAddress(SocketAddr::from((
+            [127, 0, 0, 1],
+            80
+        ))))
+    );
+    assert_eq!(
+        Address::try_from((vec![0xc3, 0x28], 80)),
+        Err(AddressError::InvalidUtf8)
+    );
+    assert_eq!(
+        Address::try_from((long_domain() + "a", 80)),
+        Err(AddressError::DomainTooLong(256))
+    );
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 80));
+    assert_eq!(Address::from(addr), Address::SocketAddress(addr));
+    assert_eq!(SocketAddr::try_from(Address::SocketAddress(addr)), Ok(addr));
+    assert_eq!(
+        SocketAddr::try_from(Address::DomainAddress("example.com".to_owned(), 80)),
+        Err(AddressError::NotSocketAddress)
+    );
+    assert_eq!(
+        SocketAddr::try_from(Address::None),
+        Err(AddressError::Empty)
+    );
+
+    assert_eq!(
+        <(String, u16)>::try_from(Address::DomainAddress("example.com".to_owned(), 80)),
+        Ok(("example.com".to_owned(), 80))
+    );
+    assert_eq!(
+        <(String, u16)>::try_from(Address::SocketAddress(addr)),
+        Ok(("127.0.0.1".to_owned(), 80))
+    );
+    assert_eq!(
+        <(Vec<u8>, u16)>::try_from(Address::DomainAddress("example.com".to_owned(), 80)),
+        Ok((b"example.com".to_vec(), 80))
+    );
+    assert_eq!(
+        <(Vec<u8>, u16)>::try_from(Address::None),
+        Err(AddressError::Empty)
+    );
+}
+
+#[test]
+fn write_domain_too_long() {
+    let addr = Address::DomainAddress("a".repeat(256), 80);
+    assert_eq!(addr.len(), 1 + 1 + 256 + 2);
+
+    for header in [
+        Header::Connect(Connect::new(addr.clone())),
+        Header::Packet(Packet::new(0, 0, 1, 0, 0, addr.clone())),
+    ] {
+        let mut buf = Vec::new();
+        assert_eq!(
+            header.try_write(&mut buf),
+            Err(AddressError::DomainTooLong(256))
+        );
+        assert!(buf.is_empty());
+
+        let err = header.marshal(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    let mut buf = Vec::new();
+    let header = Header::Connect(Connect::new(Address::DomainAddress(long_domain(), 80)));
+    header.write(&mut buf);
+    assert_eq!(buf.len(), header.len());
+}
+
+#[test]
+#[should_panic]
+fn write_domain_too_long_panics() {
+    let addr = Address::DomainAddress("a".repeat(256), 80);
+    Header::Connect(Connect::new(addr)).write(&mut Vec::new());
+}
@@ -0,0 +1,133 @@
+use std::net::SocketAddr;
+use tuic::{
+    Address, AddressRef, Connect, Dissociate, Header, HeaderRef, Packet, Status, UnmarshalError,
+    VERSION,
+};
+
+fn write(header: &Header) -> Vec<u8> {
+    let mut buf = Vec::new();
+    header.write(&mut buf);
+    buf
+}
+
+#[test]
+fn borrowed_domain() {
+    let buf = write(&Header::Connect(Connect::new(Address::DomainAddress(
+        "example.com".to_owned(),
+        443,
+    ))));
+
+    let Status::Complete(HeaderRef::Connect(conn), len) = HeaderRef::decode(&buf).unwrap() else {
+        panic!("expected a complete connect header");
+    };
+
+    assert_eq!(len, buf.len());
+    assert_eq!(*conn.addr(), AddressRef::DomainAddress("example.com", 443));
+
+    // the domain name points into the input buffer instead of being copied
+    let AddressRef::DomainAddress(domain, _) = conn.addr() else {
+        unreachable!();
+    };
+    assert!(buf.as_ptr_range().contains(&domain.as_ptr()));
+}
+
+#[test]
+fn packet_fields() {
+    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
+    let buf = write(&Header::Packet(Packet::new(
+        1,
+        2,
+        3,
+        0,
+        1200,
+        Address::SocketAddress(addr),
+    )));
+
+    let Status::Complete(HeaderRef::Packet(pkt), len) = HeaderRef::decode(&buf).unwrap() else {
+        panic!("expected a complete packet header");
+    };
+
+    assert_eq!(len, buf.len());
+    assert_eq!(pkt.assoc_id(), 1);
+    assert_eq!(pkt.pkt_id(), 2);
+    assert_eq!(pkt.frag_total(), 3);
+    assert_eq!(pkt.frag_id(), 0);
+    assert_eq!(pkt.size(), 1200);
+    assert_eq!(*pkt.addr(), AddressRef::SocketAddress(addr));
+}
+
+#[test]
+fn trailing_bytes() {
+    let mut buf = write(&Header::Dissociate(Dissociate::new(7)));
+    let header_len = buf.len();
+    buf.extend_from_slice(b"payload");
+
+    match HeaderRef::decode(&buf).unwrap() {
+        Status::Complete(HeaderRef::Dissociate(dissoc), len) => {
+            assert_eq!(dissoc.assoc_id(), 7);
+            assert_eq!(len, header_len);
+        }
+        status => panic!("unexpected status: {status:?}"),
+    }
+}
+
+#[test]
+fn incomplete() {
+    assert!(matches!(HeaderRef::decode(&[]), Ok(Status::Incomplete(1))));
+    assert!(matches!(
+        HeaderRef::decode(&[VERSION]),
+        Ok(Status::Incomplete(1))
+    ));
+    assert!(matches!(
+        HeaderRef::decode(&[VERSION, Header::TYPE_CODE_AUTHENTICATE, 0, 0]),
+        Ok(Status::Incomplete(46))
+    ));
+
+    // the domain length is known once its length byte is read, so the whole rest is requested at once
+    assert!(matches!(
+        HeaderRef::decode(&[
+            VERSION,
+            Header::TYPE_CODE_CONNECT,
+            Address::TYPE_CODE_DOMAIN,
+            11
+        ]),
+        Ok(Status::Incomplete(13))
+    ));
+
+    let buf = write(&Header::Connect(Connect::new(Address::SocketAddress(
+        SocketAddr::from(([0; 16], 443)),
+    ))));
+    assert!(matches!(
+        HeaderRef::decode(&buf[..buf.len() - 1]),
+        Ok(Status::Incomplete(1))
+    ));
+}
+
+#[test]
+fn invalid() {
+    assert!(matches!(
+        HeaderRef::decode(&[0x04, Header::TYPE_CODE_HEARTBEAT]),
+        Err(UnmarshalError::InvalidVersion(0x04))
+    ));
+    assert!(matches!(
+        HeaderRef::decode(&[VERSION, 0x7f]),
+        Err(UnmarshalError::InvalidCommand(0x7f))
+    ));
+    assert!(matches!(
+        HeaderRef::decode(&[VERSION, Header::TYPE_CODE_CONNECT, 0x03]),
+        Err(UnmarshalError::InvalidAddressType(0x03))
+    ));
+    assert!(matches!(
+        HeaderRef::decode(&[
+            VERSION,
+            Header::TYPE_CODE_CONNECT,
+            Address::TYPE_CODE_DOMAIN,
+            2,
+            0xc3,
+            0x28,
+            0,
+            80,
+        ]),
+        Err(UnmarshalError::AddressParse(_))
+    ));
+}
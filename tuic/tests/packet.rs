@@ -0,0 +1,517 @@
+use bytes::{Bytes, BytesMut};
+use std::{net::SocketAddr, time::Duration};
+use tuic::{
+    model::{AssembleError, BufferPool, Connection, FragmentError, ReassemblyLimits},
+    Address, Packet,
+};
+
+fn addr() -> Address {
+    Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 80)))
+}
+
+#[test]
+fn payload_length_mismatch() {
+    let conn = Connection::<Vec<u8>>::new();
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 1, 0, 10, addr()));
+
+    assert!(matches!(
+        pkt.assemble(vec![0; 5]),
+        Err(AssembleError::PayloadLength(10, 5)),
+    ));
+}
+
+#[test]
+fn inconsistent_frag_total() {
+    let conn = Connection::<Vec<u8>>::new();
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 2, 0, 1, addr()));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(None)));
+
+    // a larger `frag_total` would index past the buffer allocated by the first fragment
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 5, 4, 1, Address::None));
+    assert!(matches!(
+        pkt.assemble(vec![0]),
+        Err(AssembleError::InconsistentFragmentTotal(2, 5)),
+    ));
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 2, 1, 1, Address::None));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(Some(_))));
+}
+
+#[test]
+fn invalid_frag_id() {
+    let conn = Connection::<Vec<u8>>::new();
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 0, 0, 0, addr()));
+    assert!(matches!(
+        pkt.assemble(Vec::new()),
+        Err(AssembleError::InvalidFragmentId(0, 0)),
+    ));
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 1, 2, 2, 0, Address::None));
+    assert!(matches!(
+        pkt.assemble(Vec::new()),
+        Err(AssembleError::InvalidFragmentId(2, 2)),
+    ));
+}
+
+#[test]
+fn header_exceeds_max_pkt_size() {
+    let conn = Connection::<Vec<u8>>::new();
+
+    for max_pkt_size in 0..16 {
+        let pkt = conn.send_packet(0, addr(), max_pkt_size);
+        assert!(matches!(
+            pkt.try_into_fragments(&[0; 64]),
+            Err(FragmentError::PacketSizeTooSmall(size)) if size == max_pkt_size,
+        ));
+    }
+}
+
+#[test]
+#[should_panic(expected = "failed to fragment the packet")]
+fn into_fragments_panics() {
+    let conn = Connection::<Vec<u8>>::new();
+    conn.send_packet(0, addr(), 16).into_fragments(&[0; 64]);
+}
+
+#[test]
+fn fragment_and_reassemble() {
+    let tx = Connection::<Vec<u8>>::new();
+    let rx = Connection::<Vec<u8>>::new();
+
+    // covers payloads that fill the last fragment exactly
+    for max_pkt_size in [20, 21, 64] {
+        for len in 0..256 {
+            let payload = (0..len).map(|b| b as u8).collect::<Vec<_>>();
+            let mut res = None;
+
+            for (header, frag) in tx
+                .send_packet(0, addr(), max_pkt_size)
+                .into_fragments(&payload)
+            {
+                assert!(header.len() + frag.len() <= max_pkt_size);
+
+                let tuic::Header::Packet(header) = header else {
+                    unreachable!()
+                };
+
+                res = rx
+                    .recv_packet_unrestricted(header)
+                    .assemble(frag.to_vec())
+                    .unwrap();
+            }
+
+            let mut buf = Vec::new();
+            let (res_addr, _) = res.unwrap().assemble(&mut buf);
+            assert_eq!(res_addr, addr());
+            assert_eq!(buf, payload);
+        }
+    }
+}
+
+#[test]
+fn pending_packets_limit() {
+    let conn = Connection::<Vec<u8>>::new();
+    conn.set_limits(ReassemblyLimits {
+        max_pending_packets_per_session: 2,
+        ..Default::default()
+    });
+
+    for pkt_id in 0..2 {
+        let pkt = conn.recv_packet_unrestricted(Packet::new(0, pkt_id, 2, 0, 1, addr()));
+        assert!(matches!(pkt.assemble(vec![0]), Ok(None)));
+    }
+
+    // the oldest packet 0 is evicted
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 2, 2, 0, 1, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0]),
+        Err(AssembleError::Evicted(1))
+    ));
+
+    for pkt_id in 1..3 {
+        let pkt = conn.recv_packet_unrestricted(Packet::new(0, pkt_id, 2, 1, 1, Address::None));
+        assert!(matches!(pkt.assemble(vec![0]), Ok(Some(_))));
+    }
+
+    // sessions are limited separately
+    let pkt = conn.recv_packet_unrestricted(Packet::new(1, 0, 2, 0, 1, addr()));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(None)));
+}
+
+#[test]
+fn buffered_bytes_limit() {
+    // payloads are large enough for the per-packet bookkeeping to not change the outcome
+    let conn = Connection::<Vec<u8>>::new();
+    conn.set_limits(ReassemblyLimits {
+        max_buffered_bytes_per_session: 30000,
+        max_buffered_bytes: 50000,
+        ..Default::default()
+    });
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 3, 0, 20000, addr()));
+    assert!(matches!(pkt.assemble(vec![0; 20000]), Ok(None)));
+
+    // exceeding the session limit evicts packet 0 of session 0
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 1, 3, 0, 20000, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0; 20000]),
+        Err(AssembleError::Evicted(1))
+    ));
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(1, 0, 3, 0, 20000, addr()));
+    assert!(matches!(pkt.assemble(vec![0; 20000]), Ok(None)));
+
+    // exceeding the connection limit evicts the oldest packet 1 of session 0
+    let pkt = conn.recv_packet_unrestricted(Packet::new(2, 0, 3, 0, 20000, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0; 20000]),
+        Err(AssembleError::Evicted(1))
+    ));
+
+    // a fragment larger than the session limit evicts its own packet
+    let pkt = conn.recv_packet_unrestricted(Packet::new(3, 0, 2, 0, 40000, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0; 40000]),
+        Err(AssembleError::Evicted(1))
+    ));
+
+    // completing a packet never evicts
+    let pkt = conn.recv_packet_unrestricted(Packet::new(1, 0, 3, 1, 5000, Address::None));
+    assert!(matches!(pkt.assemble(vec![0; 5000]), Ok(None)));
+    let pkt = conn.recv_packet_unrestricted(Packet::new(1, 0, 3, 2, 5000, Address::None));
+    assert!(matches!(pkt.assemble(vec![0; 5000]), Ok(Some(_))));
+}
+
+#[test]
+fn connection_pending_packets_limit() {
+    let conn = Connection::<Vec<u8>>::new();
+    conn.set_limits(ReassemblyLimits {
+        max_pending_packets: 2,
+        ..Default::default()
+    });
+
+    for assoc_id in 0..2 {
+        let pkt = conn.recv_packet_unrestricted(Packet::new(assoc_id, 0, 2, 0, 1, addr()));
+        assert!(matches!(pkt.assemble(vec![0]), Ok(None)));
+    }
+
+    // the oldest packet, in session 0, is evicted
+    let pkt = conn.recv_packet_unrestricted(Packet::new(2, 0, 2, 0, 1, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0]),
+        Err(AssembleError::Evicted(1))
+    ));
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 2, 1, 1, Address::None));
+    assert!(matches!(
+        pkt.assemble(vec![0]),
+        Err(AssembleError::Evicted(1))
+    ));
+
+    // dissociating frees the pending packets of the session
+    conn.send_dissociate(1);
+    conn.send_dissociate(2);
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(3, 0, 2, 0, 1, addr()));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(None)));
+}
+
+#[test]
+fn tiny_fragments_flood() {
+    let conn = Connection::<Vec<u8>>::new();
+    let limits = conn.limits();
+
+    // every first fragment of a 255-fragment packet carries a single byte
+    let mut evicted = 0;
+    let mut rejected = 0;
+
+    for assoc_id in 0..1024 {
+        for pkt_id in 0..4 {
+            let pkt =
+                conn.recv_packet_unrestricted(Packet::new(assoc_id, pkt_id, 255, 0, 1, addr()));
+
+            match pkt.assemble(vec![0]) {
+                Ok(None) => {}
+                Err(AssembleError::Evicted(n)) => evicted += n,
+                Err(AssembleError::TooManySessions(max)) => {
+                    assert_eq!(max, limits.max_sessions);
+                    rejected += 1;
+                }
+                res => panic!("unexpected result: {res:?}"),
+            }
+        }
+    }
+
+    assert_eq!(rejected, (1024 - limits.max_sessions) * 4);
+    assert_eq!(conn.task_associate_count(), limits.max_sessions);
+    assert_eq!(
+        evicted,
+        limits.max_sessions * 4 - limits.max_pending_packets
+    );
+
+    // with the pending packet count unbounded, the bookkeeping alone exhausts the byte budget
+    let conn = Connection::<Vec<u8>>::new();
+    conn.set_limits(ReassemblyLimits {
+        max_pending_packets: usize::MAX,
+        max_pending_packets_per_session: usize::MAX,
+        max_buffered_bytes: 1024 * 1024,
+        ..Default::default()
+    });
+
+    let mut evicted = 0;
+
+    for assoc_id in 0..256 {
+        for pkt_id in 0..4 {
+            let pkt =
+                conn.recv_packet_unrestricted(Packet::new(assoc_id, pkt_id, 255, 0, 1, addr()));
+
+            if let Err(AssembleError::Evicted(n)) = pkt.assemble(vec![0]) {
+                evicted += n;
+            }
+        }
+    }
+
+    // 1024 bytes of payload in total, far below the budget if only the payload was counted
+    assert!(evicted > 512);
+}
+
+#[test]
+fn sessions_limit() {
+    let conn = Connection::<Vec<u8>>::new();
+    conn.set_limits(ReassemblyLimits {
+        max_sessions: 1,
+        ..Default::default()
+    });
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 1, 0, 1, addr()));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(Some(_))));
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(1, 0, 1, 0, 1, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0]),
+        Err(AssembleError::TooManySessions(1)),
+    ));
+
+    conn.send_dissociate(0);
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(1, 0, 1, 0, 1, addr()));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(Some(_))));
+}
+
+#[test]
+fn failed_sessions_removed() {
+    let conn = Connection::<Vec<u8>>::new();
+    conn.set_limits(ReassemblyLimits {
+        max_sessions: 2,
+        ..Default::default()
+    });
+
+    // sessions created by invalid fragments are removed right away
+    for assoc_id in 0..4 {
+        let pkt = conn.recv_packet_unrestricted(Packet::new(assoc_id, 0, 2, 2, 1, addr()));
+        assert!(matches!(
+            pkt.assemble(vec![0]),
+            Err(AssembleError::InvalidFragmentId(2, 2)),
+        ));
+        assert!(!conn.has_udp_session(assoc_id));
+    }
+    assert_eq!(conn.task_associate_count(), 0);
+
+    // while the ones of lost fragments are removed once the fragments are collected
+    for assoc_id in 4..6 {
+        let pkt = conn.recv_packet_unrestricted(Packet::new(assoc_id, 0, 2, 0, 1, addr()));
+        assert!(matches!(pkt.assemble(vec![0]), Ok(None)));
+    }
+    let pkt = conn.recv_packet_unrestricted(Packet::new(6, 0, 1, 0, 1, addr()));
+    assert!(matches!(
+        pkt.assemble(vec![0]),
+        Err(AssembleError::TooManySessions(2)),
+    ));
+
+    conn.collect_garbage(Duration::ZERO);
+    assert_eq!(conn.task_associate_count(), 0);
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(6, 0, 1, 0, 1, addr()));
+    assert!(matches!(pkt.assemble(vec![0]), Ok(Some(_))));
+
+    // a session with an assembled packet is kept until it is dissociated
+    conn.collect_garbage(Duration::ZERO);
+    assert!(conn.has_udp_session(6));
+    assert_eq!(conn.task_associate_count(), 1);
+}
+
+#[test]
+fn fragment_plan() {
+    let conn = Connection::<Vec<u8>>::new();
+
+    // header length is 17 with an IPv4 address and 11 without
+    for (len, sizes) in [
+        (0, vec![0]),
+        (3, vec![3]),
+        (4, vec![3, 1]),
+        (12, vec![3, 9]),
+        (13, vec![3, 9, 1]),
+        (21, vec![3, 9, 9]),
+    ] {
+        let payload = vec![0; len];
+        let frags = conn
+            .send_packet(0, addr(), 20)
+            .try_into_fragments(&payload)
+            .unwrap();
+        assert_eq!(frags.frag_total() as usize, sizes.len());
+        assert_eq!(frags.frag_sizes().collect::<Vec<_>>(), sizes);
+        assert_eq!(frags.len(), sizes.len());
+
+        for (frag_id, ((header, frag), size)) in frags.zip(sizes).enumerate() {
+            let tuic::Header::Packet(header) = header else {
+                unreachable!()
+            };
+
+            assert_eq!(header.frag_id() as usize, frag_id);
+            assert_eq!(header.size() as usize, size);
+            assert_eq!(frag.len(), size);
+        }
+    }
+}
+
+#[test]
+fn fragment_limits() {
+    let conn = Connection::<Vec<u8>>::new();
+
+    assert!(matches!(
+        conn.send_packet(0, addr(), 16)
+            .try_into_fragments(&Vec::new()),
+        Err(FragmentError::PacketSizeTooSmall(16)),
+    ));
+
+    // the header fits, but no fragment can carry payload
+    assert!(matches!(
+        conn.send_packet(0, Address::None, 11)
+            .try_into_fragments(&vec![0; 1]),
+        Err(FragmentError::PacketSizeTooSmall(11)),
+    ));
+    assert!(matches!(
+        conn.send_packet(0, Address::None, 11)
+            .try_into_fragments(&Vec::new()),
+        Ok(frags) if frags.frag_total() == 1,
+    ));
+
+    // the first fragment carries no payload
+    assert!(matches!(
+        conn.send_packet(0, addr(), 17)
+            .try_into_fragments(&vec![0; 7]),
+        Ok(frags) if frags.frag_sizes().eq([0, 6, 1]),
+    ));
+
+    // 1 + 254 fragments
+    let max = 3 + 254 * 9;
+    assert!(matches!(
+        conn.send_packet(0, addr(), 20).try_into_fragments(&vec![0; max]),
+        Ok(frags) if frags.frag_total() == 255,
+    ));
+    assert!(matches!(
+        conn.send_packet(0, addr(), 20).try_into_fragments(&vec![0; max + 1]),
+        Err(FragmentError::TooManyFragments(len, 256)) if len == max + 1,
+    ));
+
+    // the size field is a `u16`
+    let payload = vec![0; 70000];
+    let frags = conn
+        .send_packet(0, addr(), usize::MAX)
+        .try_into_fragments(&payload)
+        .unwrap();
+    assert_eq!(
+        frags.frag_sizes().collect::<Vec<_>>(),
+        [u16::MAX as usize, 70000 - u16::MAX as usize],
+    );
+}
+
+#[test]
+fn assemble_bytes() {
+    let conn = Connection::<Bytes>::new();
+
+    // a single fragment is taken as is
+    let payload = Bytes::from(vec![1; 16]);
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 1, 0, 16, addr()));
+    let pkt = pkt.assemble(payload.clone()).unwrap().unwrap();
+    assert_eq!((pkt.frag_total(), pkt.len()), (1, 16));
+
+    let mut buf = Bytes::new();
+    pkt.assemble(&mut buf);
+    assert_eq!(buf.as_ptr(), payload.as_ptr());
+
+    // multiple fragments are copied
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 1, 2, 0, 2, addr()));
+    assert!(matches!(pkt.assemble(Bytes::from_static(b"ab")), Ok(None)));
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 1, 2, 1, 1, Address::None));
+    let pkt = pkt.assemble(Bytes::from_static(b"c")).unwrap().unwrap();
+    assert_eq!((pkt.frag_total(), pkt.len()), (2, 3));
+
+    let mut buf = Bytes::new();
+    pkt.assemble(&mut buf);
+    assert_eq!(buf, b"abc"[..]);
+
+    // existing content is kept
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 2, 1, 0, 1, addr()));
+    let pkt = pkt.assemble(Bytes::from_static(b"b")).unwrap().unwrap();
+
+    let mut buf = Bytes::from_static(b"a");
+    pkt.assemble(&mut buf);
+    assert_eq!(buf, b"ab"[..]);
+}
+
+#[test]
+fn assemble_bytes_mut() {
+    let conn = Connection::<Bytes>::new();
+
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 2, 0, 2, addr()));
+    assert!(matches!(pkt.assemble(Bytes::from_static(b"ab")), Ok(None)));
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 2, 1, 1, Address::None));
+    let pkt = pkt.assemble(Bytes::from_static(b"c")).unwrap().unwrap();
+
+    let mut buf = BytesMut::with_capacity(pkt.len());
+    let (res_addr, assoc_id) = pkt.assemble(&mut buf);
+    assert_eq!((res_addr, assoc_id), (addr(), 0));
+    assert_eq!(buf, b"abc"[..]);
+}
+
+#[test]
+fn assemble_pooled() {
+    let conn = Connection::<Bytes>::new();
+    let pool = BufferPool::new();
+
+    // a single fragment is taken as is
+    let payload = Bytes::from(vec![1; 16]);
+    let pkt = conn.recv_packet_unrestricted(Packet::new(0, 0, 1, 0, 16, addr()));
+    let pkt = pkt.assemble(payload.clone()).unwrap().unwrap();
+
+    let mut buf = pool.buffer();
+    pkt.assemble(&mut buf);
+    assert_eq!(buf.into_bytes().as_ptr(), payload.as_ptr());
+
+    // multiple fragments are copied into the pool, whose memory is reused once the packet is dropped
+    let assemble = |pkt_id| {
+        let pkt = conn.recv_packet_unrestricted(Packet::new(0, pkt_id, 2, 0, 4, addr()));
+        assert!(matches!(
+            pkt.assemble(Bytes::from_static(b"abcd")),
+            Ok(None)
+        ));
+        let pkt = conn.recv_packet_unrestricted(Packet::new(0, pkt_id, 2, 1, 4, Address::None));
+        let pkt = pkt.assemble(Bytes::from_static(b"efgh")).unwrap().unwrap();
+
+        let mut buf = pool.buffer();
+        pkt.assemble(&mut buf);
+        buf.into_bytes()
+    };
+
+    let first = assemble(1);
+    assert_eq!(first, b"abcdefgh"[..]);
+    let ptr = first.as_ptr();
+    drop(first);
+
+    let second = assemble(2);
+    assert_eq!(second, b"abcdefgh"[..]);
+    assert_eq!(second.as_ptr(), ptr);
+}
@@ -0,0 +1,176 @@
+use futures_util::FutureExt;
+use proptest::prelude::*;
+use std::net::{IpAddr, SocketAddr};
+use tuic::{
+    Address, Authenticate, Capabilities, Connect, ConnectResult, ConnectStatus, Dissociate,
+    Extension, ExtensionError, Header, HeaderRef, Heartbeat, Packet, Padding, Status,
+    UnmarshalError,
+};
+use uuid::Uuid;
+
+fn address() -> impl Strategy<Value = Address> {
+    prop_oneof![
+        Just(Address::None),
+        // at most 63 characters of at most 4 bytes each, so the domain always fits in 255 bytes
+        ("\\PC{0,63}", any::<u16>())
+            .prop_map(|(domain, port)| Address::DomainAddress(domain, port)),
+        (any::<IpAddr>(), any::<u16>())
+            .prop_map(|(ip, port)| Address::SocketAddress(SocketAddr::new(ip, port))),
+    ]
+}
+
+fn header() -> impl Strategy<Value = Header> {
+    prop_oneof![
+        (any::<u128>(), any::<[u8; 32]>()).prop_map(|(uuid, token)| {
+            Header::Authenticate(Authenticate::new(Uuid::from_u128(uuid), token))
+        }),
+        address().prop_map(|addr| Header::Connect(Connect::new(addr))),
+        (
+            any::<u16>(),
+            any::<u16>(),
+            any::<u8>(),
+            any::<u8>(),
+            any::<u16>(),
+            address(),
+        )
+            .prop_map(|(assoc_id, pkt_id, frag_total, frag_id, size, addr)| {
+                Header::Packet(Packet::new(
+                    assoc_id, pkt_id, frag_total, frag_id, size, addr,
+                ))
+            }),
+        any::<u16>().prop_map(|assoc_id| Header::Dissociate(Dissociate::new(assoc_id))),
+        Just(Header::Heartbeat(Heartbeat::new())),
+        any::<u64>().prop_map(|bits| Header::Capabilities(Capabilities::new(bits))),
+        any::<u8>()
+            .prop_map(|code| Header::ConnectResult(ConnectResult::new(ConnectStatus::from(code)))),
+        (0..1024u16).prop_map(|len| Header::Padding(Padding::new(len))),
+        (
+            Extension::TYPE_CODE_MIN..=Extension::TYPE_CODE_MAX,
+            prop::collection::vec(any::<u8>(), 0..1024),
+        )
+            .prop_map(|(type_code, payload)| {
+                Header::Extension(Extension::new(type_code, payload).unwrap())
+            }),
+    ]
+}
+
+fn write(header: &Header) -> Vec<u8> {
+    let mut buf = Vec::new();
+    header.write(&mut buf);
+    buf
+}
+
+proptest! {
+    #[test]
+    fn write_len(header in header()) {
+        prop_assert_eq!(write(&header).len(), header.len());
+    }
+
+    #[test]
+    fn unmarshal(header in header()) {
+        let buf = write(&header);
+        let mut s = buf.as_slice();
+        prop_assert_eq!(Header::unmarshal(&mut s).unwrap(), header);
+        prop_assert!(s.is_empty());
+    }
+
+    #[test]
+    fn async_unmarshal(header in header()) {
+        let buf = write(&header);
+        let mut s = buf.as_slice();
+        let res = Header::async_unmarshal(&mut s).now_or_never().unwrap();
+        prop_assert_eq!(res.unwrap(), header);
+        prop_assert!(s.is_empty());
+    }
+
+    #[test]
+    fn tokio_unmarshal(header in header()) {
+        let buf = write(&header);
+        let mut s = buf.as_slice();
+        let res = Header::tokio_unmarshal(&mut s).now_or_never().unwrap();
+        prop_assert_eq!(res.unwrap(), header);
+        prop_assert!(s.is_empty());
+    }
+
+    #[test]
+    fn marshal(header in header()) {
+        let mut buf = Vec::new();
+        header.marshal(&mut buf).unwrap();
+        let expected = write(&header);
+        prop_assert_eq!(buf.len(), expected.len());
+
+        // apart from the random filler of `Padding`
+        if !matches!(header, Header::Padding(_)) {
+            prop_assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn decode(header in header()) {
+        let buf = write(&header);
+
+        match HeaderRef::decode(&buf).unwrap() {
+            Status::Complete(decoded, len) => {
+                prop_assert_eq!(len, buf.len());
+                prop_assert_eq!(decoded.len(), buf.len());
+                prop_assert_eq!(decoded.into_owned(), header);
+            }
+            Status::Incomplete(needed) => prop_assert!(false, "incomplete, {needed} more bytes needed"),
+        }
+
+        for end in 0..buf.len() {
+            prop_assert!(!HeaderRef::decode(&buf[..end]).unwrap().is_complete());
+        }
+    }
+
+    #[test]
+    fn serde(header in header()) {
+        let json = serde_json::to_string(&header).unwrap();
+        prop_assert_eq!(serde_json::from_str::<Header>(&json).unwrap(), header);
+    }
+}
+
+#[test]
+fn extension_type_codes() {
+    for type_code in 0x08..Extension::TYPE_CODE_MIN {
+        assert_eq!(
+            Extension::new(type_code, Vec::new()),
+            Err(ExtensionError::InvalidTypeCode(type_code)),
+        );
+
+        let buf = [tuic::VERSION, type_code, 0, 0];
+        assert!(matches!(
+            Header::unmarshal(&mut buf.as_slice()),
+            Err(UnmarshalError::InvalidCommand(cmd)) if cmd == type_code,
+        ));
+    }
+
+    assert_eq!(
+        Extension::new(0x80, vec![0; Extension::MAX_PAYLOAD_LEN + 1]),
+        Err(ExtensionError::PayloadTooLong(
+            Extension::MAX_PAYLOAD_LEN + 1
+        )),
+    );
+}
+
+#[test]
+fn connect_status_codes() {
+    for code in 0..=u8::MAX {
+        assert_eq!(ConnectStatus::from(code).code(), code);
+    }
+
+    assert!(ConnectStatus::from(0x00).is_connected());
+    assert_eq!(ConnectStatus::from(0x03), ConnectStatus::DnsFailure);
+    assert_eq!(ConnectStatus::from(0x06), ConnectStatus::Unknown(0x06));
+}
+
+#[test]
+fn padding_filler() {
+    let header = Header::Padding(Padding::new(64));
+    let (first, second) = (write(&header), write(&header));
+
+    // the filler is random, rather than a fixed content peers could come to rely on
+    assert_eq!(first[..4], second[..4]);
+    assert_ne!(first[4..], second[4..]);
+    assert!(first[4..].iter().any(|&b| b != 0));
+}
@@ -0,0 +1,69 @@
+use serde_json::json;
+use std::net::SocketAddr;
+use tuic::{Address, Connect, Header, Packet};
+
+#[test]
+fn address_forms() {
+    let domain = Address::DomainAddress("example.com".to_owned(), 443);
+    let socket = Address::SocketAddress(SocketAddr::from(([127, 0, 0, 1], 80)));
+
+    assert_eq!(
+        serde_json::to_value(&domain).unwrap(),
+        json!({ "domain": "example.com", "port": 443 })
+    );
+    assert_eq!(
+        serde_json::to_value(&socket).unwrap(),
+        json!({ "socket": "127.0.0.1:80" })
+    );
+    assert_eq!(serde_json::to_value(Address::None).unwrap(), json!(null));
+
+    for addr in [domain, socket, Address::None] {
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), addr);
+    }
+}
+
+#[test]
+fn domain_kept_as_is() {
+    // neither validated as a hostname nor re-classified as an IP address
+    for domain in ["foo bar", "1.2.3.4", "[::1]", ""] {
+        let addr = Address::DomainAddress(domain.to_owned(), 80);
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), addr);
+    }
+
+    let header: Header = serde_json::from_value(json!({
+        "type": "connect",
+        "addr": { "domain": "foo bar", "port": 80 },
+    }))
+    .unwrap();
+    assert_eq!(
+        header,
+        Header::Connect(Connect::new(Address::DomainAddress(
+            "foo bar".to_owned(),
+            80
+        )))
+    );
+}
+
+#[test]
+fn packet_without_address() {
+    let header = Header::Packet(Packet::new(1, 2, 3, 1, 1200, Address::None));
+    let value = serde_json::to_value(&header).unwrap();
+    assert_eq!(value["addr"], json!(null));
+    assert_eq!(serde_json::from_value::<Header>(value).unwrap(), header);
+}
+
+#[test]
+fn invalid_address() {
+    for value in [
+        json!("example.com:443"),
+        json!({ "domain": "example.com" }),
+        json!({ "port": 443 }),
+        json!({ "domain": "example.com", "port": 443, "socket": "127.0.0.1:80" }),
+        json!({ "socket": "example.com:443" }),
+        json!({ "socket": "127.0.0.1:80", "extra": true }),
+    ] {
+        assert!(serde_json::from_value::<Address>(value).is_err());
+    }
+}